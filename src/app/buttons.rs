@@ -0,0 +1,342 @@
+//! Button gestures.
+//!
+//! The buttons report timestamped press and release edges. The recognizer
+//! turns them into short, long and double presses of one button, or both
+//! buttons held together. Time only advances through the edges and `poll`,
+//! so the recognizer runs the same on the board and in tests.
+
+/// Buttons on the board
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Button {
+    Blue,
+    White,
+}
+
+impl Button {
+    fn index(self) -> usize {
+        match self {
+            Button::Blue => 0,
+            Button::White => 1,
+        }
+    }
+
+    fn other(self) -> Button {
+        match self {
+            Button::Blue => Button::White,
+            Button::White => Button::Blue,
+        }
+    }
+}
+
+/// A button going down or up at `at_ms`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ButtonEdge {
+    pub button: Button,
+    pub pressed: bool,
+    pub at_ms: u32,
+}
+
+/// Gesture made with the buttons
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ButtonEvent {
+    /// Pressed and released
+    Short(Button),
+    /// Held down for the long press time
+    Long(Button),
+    /// Pressed twice in quick succession
+    Double(Button),
+    /// Both buttons held down together
+    Both,
+}
+
+/// Gesture timings
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GestureConfig {
+    /// Edges this soon after the button's previous edge are contact bounce
+    pub debounce_ms: u32,
+    /// Time a button must be held for a long press
+    pub long_press_ms: u32,
+    /// Time after a release in which a second press makes a double press
+    /// (0 = no double presses, short presses are reported on release)
+    pub double_press_ms: u32,
+}
+
+impl GestureConfig {
+    /// 20 ms debounce, 1 s long press, 300 ms double press
+    pub const STANDARD: Self = Self {
+        debounce_ms: 20,
+        long_press_ms: 1000,
+        double_press_ms: 300,
+    };
+}
+
+impl Default for GestureConfig {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ButtonState {
+    Up,
+    /// Held since `since_ms`, no gesture yet
+    Down { since_ms: u32 },
+    /// Released after a short press; a second press makes it a double press
+    Released { at_ms: u32 },
+    /// Held, but its gesture has been reported
+    Used,
+}
+
+impl ButtonState {
+    fn is_down(self) -> bool {
+        matches!(self, ButtonState::Down { .. } | ButtonState::Used)
+    }
+}
+
+/// Turns button edges into gestures
+#[derive(Clone, Copy, Debug)]
+pub struct GestureRecognizer {
+    config: GestureConfig,
+    states: [ButtonState; 2],
+    /// Last accepted edge of each button, for debouncing
+    last_edge_ms: [Option<u32>; 2],
+}
+
+impl GestureRecognizer {
+    pub fn new(config: GestureConfig) -> Self {
+        Self {
+            config,
+            states: [ButtonState::Up; 2],
+            last_edge_ms: [None; 2],
+        }
+    }
+
+    pub fn config(&self) -> GestureConfig {
+        self.config
+    }
+
+    pub fn set_config(&mut self, config: GestureConfig) {
+        self.config = config;
+    }
+
+    /// Feed a press or release, getting the gesture it completes, if any
+    ///
+    /// Bounces, and edges that leave the button as it was, are ignored.
+    pub fn push(&mut self, edge: ButtonEdge) -> Option<ButtonEvent> {
+        let index = edge.button.index();
+        let bounce = self.last_edge_ms[index]
+            .is_some_and(|last_ms| edge.at_ms.wrapping_sub(last_ms) < self.config.debounce_ms);
+        if bounce {
+            return None;
+        }
+        let state = self.states[index];
+        if edge.pressed == state.is_down() {
+            return None;
+        }
+        self.last_edge_ms[index] = Some(edge.at_ms);
+
+        if edge.pressed {
+            let other = edge.button.other().index();
+            if matches!(self.states[other], ButtonState::Down { .. }) {
+                self.states = [ButtonState::Used; 2];
+                return Some(ButtonEvent::Both);
+            }
+
+            self.states[index] = ButtonState::Down { since_ms: edge.at_ms };
+            match state {
+                ButtonState::Released { at_ms }
+                    if edge.at_ms.wrapping_sub(at_ms) < self.config.double_press_ms =>
+                {
+                    self.states[index] = ButtonState::Used;
+                    Some(ButtonEvent::Double(edge.button))
+                }
+                // The first press was not polled out before this one
+                ButtonState::Released { .. } => Some(ButtonEvent::Short(edge.button)),
+                _ => None,
+            }
+        } else {
+            self.states[index] = ButtonState::Up;
+            match state {
+                ButtonState::Down { since_ms }
+                    if edge.at_ms.wrapping_sub(since_ms) >= self.config.long_press_ms =>
+                {
+                    Some(ButtonEvent::Long(edge.button))
+                }
+                ButtonState::Down { .. } if self.config.double_press_ms == 0 => {
+                    Some(ButtonEvent::Short(edge.button))
+                }
+                ButtonState::Down { .. } => {
+                    self.states[index] = ButtonState::Released { at_ms: edge.at_ms };
+                    None
+                }
+                _ => None,
+            }
+        }
+    }
+
+    /// Get a gesture completed by time passing: a button held for a long
+    /// press, or a short press that no second press followed
+    ///
+    /// Call until it returns `None`, as both buttons may have one.
+    pub fn poll(&mut self, now_ms: u32) -> Option<ButtonEvent> {
+        for button in [Button::Blue, Button::White] {
+            let index = button.index();
+            match self.states[index] {
+                ButtonState::Down { since_ms }
+                    if now_ms.wrapping_sub(since_ms) >= self.config.long_press_ms =>
+                {
+                    self.states[index] = ButtonState::Used;
+                    return Some(ButtonEvent::Long(button));
+                }
+                ButtonState::Released { at_ms }
+                    if now_ms.wrapping_sub(at_ms) >= self.config.double_press_ms =>
+                {
+                    self.states[index] = ButtonState::Up;
+                    return Some(ButtonEvent::Short(button));
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+}
+
+impl Default for GestureRecognizer {
+    fn default() -> Self {
+        Self::new(GestureConfig::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(button: Button, at_ms: u32) -> ButtonEdge {
+        ButtonEdge {
+            button,
+            pressed: true,
+            at_ms,
+        }
+    }
+
+    fn release(button: Button, at_ms: u32) -> ButtonEdge {
+        ButtonEdge {
+            button,
+            pressed: false,
+            at_ms,
+        }
+    }
+
+    #[test]
+    fn test_short_press_after_double_press_window() {
+        let mut gestures = GestureRecognizer::default();
+
+        assert_eq!(gestures.push(press(Button::White, 0)), None);
+        assert_eq!(gestures.push(release(Button::White, 100)), None);
+        assert_eq!(gestures.poll(300), None);
+        assert_eq!(gestures.poll(400), Some(ButtonEvent::Short(Button::White)));
+        assert_eq!(gestures.poll(500), None);
+    }
+
+    #[test]
+    fn test_short_press_on_release_without_double_presses() {
+        let mut gestures = GestureRecognizer::new(GestureConfig {
+            double_press_ms: 0,
+            ..GestureConfig::STANDARD
+        });
+
+        gestures.push(press(Button::Blue, 0));
+        assert_eq!(gestures.push(release(Button::Blue, 100)), Some(ButtonEvent::Short(Button::Blue)));
+    }
+
+    #[test]
+    fn test_long_press_reported_while_held() {
+        let mut gestures = GestureRecognizer::default();
+
+        gestures.push(press(Button::Blue, 0));
+        assert_eq!(gestures.poll(999), None);
+        assert_eq!(gestures.poll(1000), Some(ButtonEvent::Long(Button::Blue)));
+        assert_eq!(gestures.poll(2000), None);
+        assert_eq!(gestures.push(release(Button::Blue, 2500)), None);
+        assert_eq!(gestures.poll(3000), None);
+    }
+
+    #[test]
+    fn test_long_press_reported_on_late_release() {
+        let mut gestures = GestureRecognizer::default();
+
+        gestures.push(press(Button::Blue, 0));
+        assert_eq!(gestures.push(release(Button::Blue, 1200)), Some(ButtonEvent::Long(Button::Blue)));
+    }
+
+    #[test]
+    fn test_double_press() {
+        let mut gestures = GestureRecognizer::default();
+
+        gestures.push(press(Button::White, 0));
+        gestures.push(release(Button::White, 80));
+        assert_eq!(gestures.push(press(Button::White, 250)), Some(ButtonEvent::Double(Button::White)));
+        assert_eq!(gestures.push(release(Button::White, 330)), None);
+        assert_eq!(gestures.poll(2000), None);
+    }
+
+    #[test]
+    fn test_late_second_press_is_two_short_presses() {
+        let mut gestures = GestureRecognizer::default();
+
+        gestures.push(press(Button::White, 0));
+        gestures.push(release(Button::White, 80));
+        assert_eq!(gestures.push(press(Button::White, 500)), Some(ButtonEvent::Short(Button::White)));
+        gestures.push(release(Button::White, 580));
+        assert_eq!(gestures.poll(900), Some(ButtonEvent::Short(Button::White)));
+    }
+
+    #[test]
+    fn test_both_buttons_held() {
+        let mut gestures = GestureRecognizer::default();
+
+        gestures.push(press(Button::Blue, 0));
+        assert_eq!(gestures.push(press(Button::White, 50)), Some(ButtonEvent::Both));
+        // Neither button goes on to a long or short press
+        assert_eq!(gestures.poll(2000), None);
+        assert_eq!(gestures.push(release(Button::Blue, 2100)), None);
+        assert_eq!(gestures.push(release(Button::White, 2150)), None);
+        assert_eq!(gestures.poll(3000), None);
+    }
+
+    #[test]
+    fn test_buttons_pressed_in_turn_are_not_a_chord() {
+        let mut gestures = GestureRecognizer::default();
+
+        gestures.push(press(Button::Blue, 0));
+        gestures.push(release(Button::Blue, 100));
+        assert_eq!(gestures.push(press(Button::White, 150)), None);
+        gestures.push(release(Button::White, 250));
+        assert_eq!(gestures.poll(400), Some(ButtonEvent::Short(Button::Blue)));
+        assert_eq!(gestures.poll(550), Some(ButtonEvent::Short(Button::White)));
+    }
+
+    #[test]
+    fn test_contact_bounce_ignored() {
+        let mut gestures = GestureRecognizer::default();
+
+        gestures.push(press(Button::Blue, 0));
+        gestures.push(release(Button::Blue, 5));
+        gestures.push(press(Button::Blue, 10));
+        gestures.push(release(Button::Blue, 100));
+        gestures.push(press(Button::Blue, 108));
+        gestures.push(release(Button::Blue, 112));
+        assert_eq!(gestures.poll(400), Some(ButtonEvent::Short(Button::Blue)));
+        assert_eq!(gestures.poll(2000), None);
+    }
+
+    #[test]
+    fn test_repeated_edge_ignored() {
+        let mut gestures = GestureRecognizer::default();
+
+        gestures.push(press(Button::White, 0));
+        assert_eq!(gestures.push(press(Button::White, 500)), None);
+        assert_eq!(gestures.poll(999), None);
+        assert_eq!(gestures.poll(1000), Some(ButtonEvent::Long(Button::White)));
+    }
+}
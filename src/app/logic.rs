@@ -1,10 +1,35 @@
-use crate::app::traits::{BoardIO, BuzzerIO, ClockDisplayIO, DelayIO, DisplayIO, Hardware};
-use crate::certabo::calibration::{CalibrationData, Piece as CalibrationPiece};
+use crate::app::buttons::{Button, ButtonEvent};
+use crate::app::menu::{CustomField, MenuView, TimeControlMenu};
+use crate::app::recognizer::{
+    calibration_piece_to_type, is_partial, matches_position, recognize, setup_problem, square_matches,
+    Recognition, SetupProblem,
+};
+use crate::app::traits::{
+    BoardIO, BuzzerIO, ClockDisplayIO, DelayIO, DisplayIO, Hardware, RandomIO, TimeSource,
+};
+use crate::certabo::calibration::{layout_with_back_rank, CalibrationData, Piece as CalibrationPiece};
+use crate::certabo::filter::{FilterConfig, ReadingFilter};
 use crate::certabo::leds::LedState;
 use crate::certabo::protocol::RfidReading;
-use crate::game::chess::{BoardStatus, PieceType, UndoInfo};
-use crate::game::state::{GameState, GameStatus};
-use crate::game::timer::Color;
+use crate::game::chess::{
+    chess960_back_rank, BoardStatus, ChessBoard, Move, PieceColor, PieceType, UndoInfo, CHESS960_POSITIONS,
+};
+use crate::game::engine::{Engine, SearchLimits, DEFAULT_LEVEL};
+use crate::game::pgn::{check_pgn, PgnReader};
+use crate::game::san::San;
+use crate::game::state::{GameState, GameStatus, MoveRecord};
+use crate::game::timer::{Color, LowTimeAlarm, LowTimeWarnings, TimeControl, TimeWarning};
+
+/// How long a new time control stage is announced, in 100 ms ticks
+const STAGE_NOTICE_TICKS: u8 = 30;
+
+/// Engine nodes searched per tick while the computer thinks, so a tick
+/// returns to the main loop quickly
+const SEARCH_SLICE_NODES: u32 = 1000;
+
+/// How long the board may show something no move explains before the
+/// differences are pointed out, in milliseconds
+const MISMATCH_GRACE_MS: u32 = 3000;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum AppState {
@@ -12,28 +37,41 @@ pub enum AppState {
     WaitingForCalibration,
     Calibrating,
     WaitingForSetup,
+    /// Pieces are set up; choosing the time control before the game starts
+    SelectingTimeControl,
     GameInProgress,
+    /// Stepping through a loaded PGN game on the physical board
+    Replaying,
     GamePaused,
+    /// The board differs from the game; play resumes once it matches again
+    Recovering,
     GameEnded,
 }
 
-fn calibration_piece_to_type(piece: CalibrationPiece) -> PieceType {
-    match piece {
-        CalibrationPiece::WhitePawn | CalibrationPiece::BlackPawn => PieceType::Pawn,
-        CalibrationPiece::WhiteKnight | CalibrationPiece::BlackKnight => PieceType::Knight,
-        CalibrationPiece::WhiteBishop | CalibrationPiece::BlackBishop => PieceType::Bishop,
-        CalibrationPiece::WhiteRook | CalibrationPiece::BlackRook => PieceType::Rook,
-        CalibrationPiece::WhiteQueen | CalibrationPiece::BlackQueen => PieceType::Queen,
-        CalibrationPiece::WhiteKing | CalibrationPiece::BlackKing => PieceType::King,
+fn white_calibration_piece(piece_type: PieceType) -> CalibrationPiece {
+    match piece_type {
+        PieceType::Pawn => CalibrationPiece::WhitePawn,
+        PieceType::Knight => CalibrationPiece::WhiteKnight,
+        PieceType::Bishop => CalibrationPiece::WhiteBishop,
+        PieceType::Rook => CalibrationPiece::WhiteRook,
+        PieceType::Queen => CalibrationPiece::WhiteQueen,
+        PieceType::King => CalibrationPiece::WhiteKing,
     }
 }
 
-fn is_promotion_rank(square: u8, is_white_pawn: bool) -> bool {
-    let rank = square / 8;
-    if is_white_pawn {
-        rank == 7
-    } else {
-        rank == 0
+/// Side a button belongs to: Blue is black's, White is white's
+fn button_side(button: Button) -> Color {
+    match button {
+        Button::Blue => Color::Black,
+        Button::White => Color::White,
+    }
+}
+
+/// Short reason shown on the display for a claimable draw
+fn claimable_draw_reason(status: BoardStatus) -> &'static str {
+    match status {
+        BoardStatus::ThreefoldRepetition => "3-fold",
+        _ => "50-move",
     }
 }
 
@@ -55,27 +93,15 @@ struct PendingTakeback {
 
 impl PendingTakeback {
     fn from_undo_info(info: &UndoInfo, side_is_white: bool) -> Self {
-        let (castling_rook_from, castling_rook_to) = if let Some(kingside) = info.castling {
-            if side_is_white {
-                if kingside {
-                    (Some(5u8), Some(7u8))
-                } else {
-                    (Some(3u8), Some(0u8))
-                }
-            } else {
-                if kingside {
-                    (Some(61u8), Some(63u8))
-                } else {
-                    (Some(59u8), Some(56u8))
-                }
-            }
-        } else {
-            (None, None)
+        // The rook goes back from its castled square to its start square
+        let (to, castling_rook_from, castling_rook_to) = match info.castling {
+            Some(castle) => (castle.king_to, Some(castle.rook_to), Some(castle.rook_from)),
+            None => (info.to, None, None),
         };
 
         Self {
             from: info.from,
-            to: info.to,
+            to,
             captured_square: info.captured_square,
             has_capture: info.captured.is_some(),
             castling_rook_from,
@@ -115,7 +141,7 @@ impl PendingTakeback {
             if !captured_piece_opponent {
                 return false;
             }
-        } else if board[self.to as usize].is_some() {
+        } else if board[self.to as usize].is_some() && !self.is_home_square(self.to) {
             return false;
         }
 
@@ -131,7 +157,7 @@ impl PendingTakeback {
         }
 
         if let (Some(rook_from), Some(rook_to)) = (self.castling_rook_from, self.castling_rook_to) {
-            if board[rook_from as usize].is_some() {
+            if board[rook_from as usize].is_some() && !self.is_home_square(rook_from) {
                 return false;
             }
             let Some(rook_piece) = board[rook_to as usize] else {
@@ -149,20 +175,95 @@ impl PendingTakeback {
 
         true
     }
+
+    /// Whether a piece returns to `square` (in Chess960 castling the king and
+    /// rook may end up on each other's start squares)
+    fn is_home_square(&self, square: u8) -> bool {
+        square == self.from || Some(square) == self.castling_rook_to
+    }
+}
+
+/// A PGN game being replayed, read from its text one move at a time
+struct Replay {
+    pgn: &'static [u8],
+    /// Ply of the game that `next` is made at
+    ply: u16,
+    /// Move to make at `ply`, `None` once the game is over
+    next: Option<MoveRecord>,
+}
+
+/// Whether moves may be taken back on the board
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TakebackPolicy {
+    /// Takebacks are refused, as in rated games
+    Disabled,
+    /// Any move may be taken back
+    #[default]
+    Always,
+    /// The player to move agrees with their button (White, or Blue for black)
+    OpponentConfirms,
+}
+
+/// Side and strength of the computer in a game against the board
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ComputerOpponent {
+    pub color: Color,
+    /// Strength level, see `SearchLimits::level`
+    pub level: u8,
+}
+
+impl Default for ComputerOpponent {
+    fn default() -> Self {
+        Self {
+            color: Color::Black,
+            level: DEFAULT_LEVEL,
+        }
+    }
 }
 
 pub struct App {
     state: AppState,
     calibration: CalibrationData,
     game: GameState,
+    /// Stabilizes timestamped readings before they are acted on
+    filter: ReadingFilter,
     last_reading: Option<RfidReading>,
     led_state: LedState,
     led_dirty: bool,
     tick_count: u8,
-    last_move: Option<(u8, u8)>,
+    last_move: Option<San>,
     spinner_frame: u8,
     pending_promotion: Option<PendingPromotion>,
     pending_takeback: Option<PendingTakeback>,
+    takeback_policy: TakebackPolicy,
+    /// A takeback is made on the board, waiting for the opponent to agree
+    takeback_request: bool,
+    /// Side asked to confirm its resignation with another press of its button
+    resign_prompt: Option<Color>,
+    /// Side that offered a draw, waiting for the opponent to accept with their button
+    draw_offer: Option<Color>,
+    replay: Option<Replay>,
+    /// PGN game offered for replay before a game
+    study_game: Option<&'static [u8]>,
+    /// Chess960 start position index, if set up for a Chess960 game
+    chess960: Option<u16>,
+    computer: Option<ComputerOpponent>,
+    engine: Engine,
+    /// Move chosen by the computer, waiting to be made on the board
+    computer_move: Option<Move>,
+    time_control: TimeControl,
+    menu: TimeControlMenu,
+    /// Player who just entered a new time control stage, and ticks left to show it
+    stage_notice: Option<(Color, u8)>,
+    /// Timestamp up to which the game clock has been run
+    clock_ms: Option<u32>,
+    low_time: LowTimeAlarm,
+    /// How long the board has shown something no move explains
+    mismatch_ms: Option<u32>,
+    /// State to go back to once the board matches the game again
+    recovering_from: AppState,
+    /// What was last shown as wrong with the start position
+    setup_problem: Option<SetupProblem>,
 }
 
 impl App {
@@ -171,6 +272,7 @@ impl App {
             state: AppState::Initializing,
             calibration: CalibrationData::new(),
             game: GameState::new(),
+            filter: ReadingFilter::default(),
             last_reading: None,
             led_state: LedState::new(),
             led_dirty: false,
@@ -179,6 +281,24 @@ impl App {
             spinner_frame: 0,
             pending_promotion: None,
             pending_takeback: None,
+            takeback_policy: TakebackPolicy::default(),
+            takeback_request: false,
+            resign_prompt: None,
+            draw_offer: None,
+            replay: None,
+            study_game: None,
+            chess960: None,
+            computer: None,
+            engine: Engine::new(),
+            computer_move: None,
+            time_control: TimeControl::default(),
+            menu: TimeControlMenu::new(TimeControl::default()),
+            stage_notice: None,
+            clock_ms: None,
+            low_time: LowTimeAlarm::default(),
+            mismatch_ms: None,
+            recovering_from: AppState::GameInProgress,
+            setup_problem: None,
         }
     }
 
@@ -194,30 +314,284 @@ impl App {
         &self.game
     }
 
-    pub fn start<B, D, Z, C, L>(&mut self, hw: &mut Hardware<B, D, Z, C, L>)
+    /// Load a PGN game for guided replay
+    ///
+    /// The replay starts once the pieces are set up in the game's initial
+    /// position. The moves are read from `pgn` as the replay goes on, so
+    /// they are all checked here. Returns `false` if a game is currently in
+    /// progress or `pgn` is not a valid game.
+    pub fn load_replay<D: DisplayIO>(&mut self, pgn: &'static [u8], display: &mut D) -> bool {
+        match self.state {
+            AppState::WaitingForSetup | AppState::GameEnded => {}
+            _ => return false,
+        }
+        let Ok(initial_position) = check_pgn(pgn) else {
+            return false;
+        };
+
+        self.game.reset_to(ChessBoard::with_position(initial_position));
+        self.game.set_time_control(self.time_control);
+        self.replay = Some(Replay { pgn, ply: 0, next: None });
+        self.read_replay_move();
+        self.chess960 = None;
+        self.computer = None;
+        self.computer_move = None;
+        self.last_move = None;
+        self.state = AppState::WaitingForSetup;
+        self.led_state.clear_all();
+        self.led_dirty = true;
+        display.show_waiting_for_setup();
+        true
+    }
+
+    /// Offer a PGN game for replay: before a game, White cycles on from
+    /// Chess960 to it
+    pub fn set_study_game(&mut self, pgn: &'static [u8]) {
+        self.study_game = Some(pgn);
+    }
+
+    /// Whether a PGN game is loaded for replay
+    pub fn replay_loaded(&self) -> bool {
+        self.replay.is_some()
+    }
+
+    /// Chess960 start position index of the current setup, if any
+    pub fn chess960(&self) -> Option<u16> {
+        self.chess960
+    }
+
+    /// Set up Chess960 start position `index` for the next game
+    ///
+    /// Calibration and the setup check then expect the shuffled back rank.
+    /// Returns `false` if a game is in progress or the index is out of range.
+    pub fn setup_chess960<D: DisplayIO>(&mut self, index: u16, display: &mut D) -> bool {
+        match self.state {
+            AppState::WaitingForSetup | AppState::GameEnded => {}
+            _ => return false,
+        }
+        let (Some(board), Some(back_rank)) = (ChessBoard::chess960(index), chess960_back_rank(index)) else {
+            return false;
+        };
+
+        self.game.reset_to(board);
+        self.game.set_time_control(self.time_control);
+        self.replay = None;
+        self.chess960 = Some(index);
+        self.computer = None;
+        self.computer_move = None;
+        self.last_move = None;
+        self.state = AppState::WaitingForSetup;
+        self.led_state.clear_all();
+        self.led_dirty = true;
+
+        let text = back_rank.map(|piece_type| piece_type.to_char() as u8);
+        display.show_chess960_setup(index, core::str::from_utf8(&text).unwrap_or(""));
+        true
+    }
+
+    /// Computer opponent of the current setup, if any
+    pub fn computer_opponent(&self) -> Option<ComputerOpponent> {
+        self.computer
+    }
+
+    pub fn takeback_policy(&self) -> TakebackPolicy {
+        self.takeback_policy
+    }
+
+    pub fn set_takeback_policy(&mut self, policy: TakebackPolicy) {
+        self.takeback_policy = policy;
+    }
+
+    pub fn time_control(&self) -> TimeControl {
+        self.time_control
+    }
+
+    /// Set the time control for the next game
+    ///
+    /// Returns `false` if a game is in progress or the time control is being
+    /// chosen on the device.
+    pub fn set_time_control(&mut self, time_control: TimeControl) -> bool {
+        if matches!(
+            self.state,
+            AppState::SelectingTimeControl | AppState::GameInProgress | AppState::GamePaused
+        ) {
+            return false;
+        }
+        self.time_control = time_control;
+        self.game.set_time_control(time_control);
+        true
+    }
+
+    pub fn low_time_warnings(&self) -> LowTimeWarnings {
+        self.low_time.warnings()
+    }
+
+    /// Set the remaining times at which low time warnings sound
+    pub fn set_low_time_warnings(&mut self, warnings: LowTimeWarnings) {
+        self.low_time = LowTimeAlarm::new(warnings);
+    }
+
+    pub fn reading_filter(&self) -> FilterConfig {
+        self.filter.config()
+    }
+
+    /// Set how many agreeing readings, or how long, a square needs to change
+    pub fn set_reading_filter(&mut self, config: FilterConfig) {
+        self.filter.set_config(config);
+    }
+
+    /// Move the computer is waiting for the player to make on the board
+    pub fn computer_move(&self) -> Option<Move> {
+        self.computer_move
+    }
+
+    /// Play the next game against the computer, or against a human with `None`
+    ///
+    /// Sets up the standard start position. Returns `false` if a game is in
+    /// progress.
+    pub fn setup_vs_computer<D: DisplayIO>(
+        &mut self,
+        opponent: Option<ComputerOpponent>,
+        display: &mut D,
+    ) -> bool {
+        match self.state {
+            AppState::WaitingForSetup | AppState::GameEnded => {}
+            _ => return false,
+        }
+
+        self.game.reset();
+        self.replay = None;
+        self.chess960 = None;
+        self.computer = opponent;
+        self.computer_move = None;
+        self.last_move = None;
+        self.state = AppState::WaitingForSetup;
+        self.led_state.clear_all();
+        self.led_dirty = true;
+
+        match opponent {
+            Some(opponent) => display.show_vs_computer_setup(opponent.level),
+            None => display.show_waiting_for_setup(),
+        }
+        true
+    }
+
+    pub fn start<B, D, Z, C, L, T, R>(&mut self, hw: &mut Hardware<B, D, Z, C, L, T, R>)
     where
         B: BoardIO,
         D: DisplayIO,
         Z: BuzzerIO,
         C: ClockDisplayIO,
         L: DelayIO,
+        T: TimeSource,
+        R: RandomIO,
     {
         self.state = AppState::WaitingForCalibration;
+        self.clock_ms = Some(hw.time.now_ms());
         hw.display.show_calibration_prompt();
     }
 
-    pub fn on_blue_button<D: DisplayIO>(&mut self, display: &mut D) {
+    /// Act on a button gesture
+    ///
+    /// A short press does what the button is for in the current state. A
+    /// double press counts as one, except that Blue steps two entries through
+    /// the time control menu. During a game, a long press asks the button's
+    /// side (Blue is black's, White is white's) to confirm resigning with a
+    /// press of the same button, and holding both buttons offers a draw for
+    /// the player to move, which the opponent accepts with their button. Any
+    /// other gesture cancels the prompt or offer. Outside a game a long press
+    /// is a short one.
+    pub fn on_button_event<D: DisplayIO, R: RandomIO>(
+        &mut self,
+        event: ButtonEvent,
+        display: &mut D,
+        random: &mut R,
+    ) {
+        let in_game = matches!(self.state, AppState::GameInProgress | AppState::GamePaused);
+        if in_game && let Some(color) = self.resign_prompt.take() {
+            match event {
+                ButtonEvent::Short(button) if button_side(button) == color => {
+                    self.resign(color, display);
+                }
+                _ => self.show_game(display),
+            }
+            return;
+        }
+        if in_game && let Some(color) = self.draw_offer.take() {
+            match event {
+                ButtonEvent::Short(button) if button_side(button) == color.opponent() => {
+                    self.agree_draw(display);
+                }
+                _ => self.show_game(display),
+            }
+            return;
+        }
+
+        match event {
+            ButtonEvent::Short(button) => self.on_button(button, display, random),
+            ButtonEvent::Double(Button::Blue) if self.state == AppState::SelectingTimeControl => {
+                self.on_blue_button(display);
+                self.on_blue_button(display);
+            }
+            ButtonEvent::Double(button) => self.on_button(button, display, random),
+            ButtonEvent::Long(button) if in_game => {
+                let color = button_side(button);
+                self.resign_prompt = Some(color);
+                display.show_resign_prompt(color == Color::White);
+            }
+            ButtonEvent::Long(button) => self.on_button(button, display, random),
+            ButtonEvent::Both if in_game => {
+                let color = self.game.current_turn();
+                self.draw_offer = Some(color);
+                display.show_draw_offer(color.opponent() == Color::White);
+            }
+            ButtonEvent::Both => {}
+        }
+    }
+
+    fn on_button<D: DisplayIO, R: RandomIO>(&mut self, button: Button, display: &mut D, random: &mut R) {
+        match button {
+            Button::Blue => self.on_blue_button(display),
+            Button::White => self.on_white_button(display, random),
+        }
+    }
+
+    /// Show the game again after a prompt is answered or cancelled
+    fn show_game<D: DisplayIO>(&self, display: &mut D) {
+        if self.state == AppState::GamePaused {
+            display.show_paused();
+        } else {
+            self.update_display(display);
+        }
+    }
+
+    fn on_blue_button<D: DisplayIO>(&mut self, display: &mut D) {
         match self.state {
             AppState::WaitingForCalibration | AppState::WaitingForSetup | AppState::GameEnded => {
                 self.state = AppState::Calibrating;
                 display.show_calibration_prompt();
             }
+            AppState::SelectingTimeControl => {
+                self.menu.next();
+                self.game.set_time_control(self.menu.time_control());
+                self.show_menu(display);
+            }
+            AppState::GameInProgress if self.takeback_request => {
+                // Blue is black's button
+                self.confirm_takeback(Color::Black, display);
+            }
+            AppState::GameInProgress => {
+                self.claim_draw(display);
+            }
             _ => {}
         }
     }
 
-    pub fn on_white_button<D: DisplayIO>(&mut self, display: &mut D) {
+    fn on_white_button<D: DisplayIO, R: RandomIO>(&mut self, display: &mut D, random: &mut R) {
         match self.state {
+            AppState::GameInProgress if self.takeback_request => {
+                self.confirm_takeback(Color::White, display);
+            }
             AppState::GameInProgress | AppState::GamePaused => {
                 self.game.toggle_pause();
                 match self.game.status() {
@@ -231,15 +605,52 @@ impl App {
                     _ => {}
                 }
             }
-            AppState::GameEnded => {
+            AppState::SelectingTimeControl => match self.menu.confirm() {
+                Some(time_control) => {
+                    self.time_control = time_control;
+                    self.start_game(display);
+                }
+                None => self.show_menu(display),
+            },
+            AppState::WaitingForSetup if self.chess960.is_some() => {
+                // On to the study game if there is one, else back to a standard game
+                let loaded = self.study_game.is_some_and(|pgn| self.load_replay(pgn, display));
+                if !loaded {
+                    self.game.reset();
+                    self.chess960 = None;
+                    display.show_waiting_for_setup();
+                }
+            }
+            AppState::WaitingForSetup if self.replay.is_some() => {
+                // Back to a standard game
+                self.game.reset();
+                self.replay = None;
+                display.show_waiting_for_setup();
+            }
+            AppState::WaitingForSetup if self.computer.is_none() => {
+                self.setup_vs_computer(Some(ComputerOpponent::default()), display);
+            }
+            AppState::WaitingForSetup => {
+                let index = random.next_u32() % CHESS960_POSITIONS as u32;
+                self.setup_chess960(index as u16, display);
+            }
+            AppState::GameEnded | AppState::Replaying => {
+                // A game against the computer is followed by another one
                 self.game.reset();
+                self.replay = None;
+                self.chess960 = None;
+                self.computer_move = None;
+                self.last_move = None;
                 self.state = AppState::WaitingForSetup;
+                self.led_state.clear_all();
+                self.led_dirty = true;
                 display.show_waiting_for_setup();
             }
             _ => {}
         }
     }
 
+    /// Act on a board reading as it is, without stabilizing it
     pub fn on_board_reading<D: DisplayIO, Z: BuzzerIO, L: DelayIO>(
         &mut self,
         reading: RfidReading,
@@ -254,8 +665,12 @@ impl App {
             AppState::WaitingForSetup => {
                 self.check_starting_position(&reading, display, buzzer);
             }
-            AppState::GameInProgress => {
+            AppState::GameInProgress | AppState::Replaying => {
                 self.process_game_move(&reading, display, buzzer);
+                self.light_expected_move();
+            }
+            AppState::Recovering => {
+                self.recover(&reading, display, buzzer);
             }
             _ => {}
         }
@@ -263,6 +678,35 @@ impl App {
         self.last_reading = Some(reading);
     }
 
+    /// Handle a board reading that arrived at `received_ms`.
+    ///
+    /// The game clock is first run up to the arrival time, so the move is
+    /// timed from when the pieces were placed rather than from when the main
+    /// loop got round to it. Time after that goes to the next player.
+    ///
+    /// The reading is stabilized first, so a square only changes once the
+    /// reading filter has accepted it.
+    pub fn on_board_reading_at<D: DisplayIO, Z: BuzzerIO, L: DelayIO>(
+        &mut self,
+        reading: RfidReading,
+        received_ms: u32,
+        display: &mut D,
+        buzzer: &mut Z,
+        delay: &mut L,
+    ) {
+        let elapsed_ms = self.advance_clock(received_ms);
+        self.run_clock(elapsed_ms, display, buzzer);
+        self.check_mismatch(elapsed_ms, display);
+
+        // The flag fell before the reading arrived
+        if self.state == AppState::GameEnded {
+            return;
+        }
+
+        let reading = self.filter.push(&reading, received_ms);
+        self.on_board_reading(reading, display, buzzer, delay);
+    }
+
     fn do_calibration<D: DisplayIO, Z: BuzzerIO, L: DelayIO>(
         &mut self,
         reading: &RfidReading,
@@ -270,7 +714,13 @@ impl App {
         buzzer: &mut Z,
         delay: &mut L,
     ) {
-        let count = self.calibration.calibrate_from_starting_position(reading);
+        let count = match self.chess960.and_then(chess960_back_rank) {
+            Some(back_rank) => {
+                let layout = layout_with_back_rank(&back_rank.map(white_calibration_piece));
+                self.calibration.calibrate_from_layout(reading, &layout)
+            }
+            None => self.calibration.calibrate_from_starting_position(reading),
+        };
         display.show_calibration_progress(count as u8);
 
         if self.calibration.is_complete() {
@@ -292,21 +742,63 @@ impl App {
     ) {
         let board = self.calibration.reading_to_board(reading);
 
-        let mut correct = 0;
-        for square in 0..64u8 {
-            if board[square as usize].is_some() {
-                correct += 1;
+        // Every square must match the start position: the standard layout, the
+        // Chess960 back rank or the replay's initial position
+        if let Some(problem) = setup_problem(self.game.board().position(), &board) {
+            let (leds, _) = self.mismatched_squares(&board);
+            if self.setup_problem != Some(problem) || self.led_state != leds {
+                self.setup_problem = Some(problem);
+                self.led_state = leds;
+                self.led_dirty = true;
+                display.show_setup_problem(problem.text(&mut [0u8; 16]));
             }
+            return;
         }
 
-        if correct >= 32 {
-            self.state = AppState::GameInProgress;
-            self.game.set_status(GameStatus::InProgress);
-            buzzer.move_sound();
-            self.update_display(display);
+        self.setup_problem = None;
+        self.led_state.clear_all();
+        self.led_dirty = true;
+        buzzer.move_sound();
+        if self.replay.is_some() {
+            self.start_game(display);
+            return;
+        }
+
+        // Choose the time control, previewing it on the clocks
+        self.menu = TimeControlMenu::new(self.time_control);
+        self.game.set_time_control(self.menu.time_control());
+        self.state = AppState::SelectingTimeControl;
+        self.show_menu(display);
+    }
+
+    fn show_menu<D: DisplayIO>(&self, display: &mut D) {
+        match self.menu.view() {
+            MenuView::Item { minutes, increment, custom } => {
+                display.show_time_control_menu(minutes, increment, custom);
+            }
+            MenuView::Editing { minutes, increment, field } => {
+                let editing_increment = field == CustomField::Increment;
+                display.show_custom_time_control(minutes, increment, editing_increment);
+            }
         }
     }
 
+    fn start_game<D: DisplayIO>(&mut self, display: &mut D) {
+        self.takeback_request = false;
+        self.resign_prompt = None;
+        self.draw_offer = None;
+        self.game.set_time_control(self.time_control);
+        self.state = if self.replay.is_some() {
+            AppState::Replaying
+        } else {
+            AppState::GameInProgress
+        };
+        self.game.set_status(GameStatus::InProgress);
+        self.find_computer_move();
+        self.light_expected_move();
+        self.update_display(display);
+    }
+
     fn process_game_move<D: DisplayIO, Z: BuzzerIO>(
         &mut self,
         reading: &RfidReading,
@@ -315,6 +807,10 @@ impl App {
     ) {
         let current_board = self.calibration.reading_to_board(reading);
 
+        if self.handle_takeback_request(&current_board, display) {
+            return;
+        }
+
         if self.handle_pending_takeback(&current_board, display, buzzer) {
             return;
         }
@@ -327,6 +823,9 @@ impl App {
             return;
         };
         let previous_board = self.calibration.reading_to_board(last_reading);
+        if previous_board == current_board {
+            return;
+        }
 
         let mut lifted_from: Option<u8> = None;
         let mut placed_to: Option<u8> = None;
@@ -337,78 +836,383 @@ impl App {
 
             if prev.is_some() && curr.is_none() {
                 lifted_from = Some(square);
-            } else if prev.is_none() && curr.is_some() {
-                placed_to = Some(square);
-            } else if prev.is_some() && curr.is_some() && prev != curr {
+            } else if curr.is_some() && prev != curr {
                 placed_to = Some(square);
             }
         }
 
+        let recognition = recognize(self.game.board().position(), &current_board);
+        match recognition {
+            Recognition::Move(mv) => {
+                if self.allows_move(mv.from, mv.to, mv.promotion) {
+                    self.mismatch_ms = None;
+                    self.complete_move(mv.from, mv.to, mv.promotion, display, buzzer);
+                } else if self.expected_move_started(&current_board) {
+                    self.mismatch_ms = None;
+                } else {
+                    self.mismatch_ms.get_or_insert(0);
+                    buzzer.error_sound();
+                    display.show_invalid_move();
+                }
+                return;
+            }
+            Recognition::Promotion { from, to } => {
+                if !self.allows_promotion(from, to) {
+                    self.mismatch_ms.get_or_insert(0);
+                    buzzer.error_sound();
+                    display.show_invalid_move();
+                    return;
+                }
+
+                self.mismatch_ms = None;
+                self.pending_promotion = Some(PendingPromotion { from, to });
+                display.show_promotion_prompt();
+                self.led_state.clear_all();
+                self.led_state.set(to);
+                self.led_dirty = true;
+                return;
+            }
+            Recognition::Unchanged => {
+                self.mismatch_ms = None;
+                // The lifted piece went back where it was
+                if self.game.lifted_piece().is_some() {
+                    self.game.piece_replaced();
+                    self.led_state.clear_all();
+                    self.led_dirty = true;
+                }
+                return;
+            }
+            Recognition::InProgress => self.mismatch_ms = None,
+            Recognition::Unrecognized => {}
+        }
+
+        if self.detect_rook_first_castling(&current_board, display, buzzer) {
+            return;
+        }
+
         if self.detect_takeback_start(lifted_from, placed_to, &current_board, display, buzzer) {
             return;
         }
 
-        if let Some(from) = lifted_from {
-            if placed_to.is_none() {
-                self.game.piece_lifted(from);
-                let destinations = self.game.legal_destinations(from);
-                self.led_state.clear_all();
-                self.led_state.set(from);
-                for dest in destinations {
-                    self.led_state.set(dest);
-                }
-                self.led_dirty = true;
+        if recognition == Recognition::Unrecognized {
+            self.mismatch_ms.get_or_insert(0);
+        }
+
+        // A piece placed where no legal move can put it
+        if recognition == Recognition::Unrecognized && placed_to.is_some() {
+            buzzer.error_sound();
+            display.show_invalid_move();
+            return;
+        }
+
+        let Some(from) = lifted_from else {
+            return;
+        };
+        let mover = match self.game.current_turn() {
+            Color::White => PieceColor::White,
+            Color::Black => PieceColor::Black,
+        };
+        if self.game.board().get(from).is_none_or(|piece| piece.color != mover) {
+            return;
+        }
+
+        self.game.piece_lifted(from);
+        // During replay or the computer's turn the expected move stays lit instead
+        if self.expected_move().is_none() {
+            let destinations = self.game.legal_destinations(from);
+            self.led_state.clear_all();
+            self.led_state.set(from);
+            for dest in destinations {
+                self.led_state.set(dest);
             }
+            self.led_dirty = true;
         }
+    }
 
-        if let (Some(from), Some(to)) = (self.game.lifted_piece(), placed_to) {
-            if self.game.is_legal_move(from, to) {
-                if let Some(piece) = previous_board[from as usize] {
-                    let is_pawn = matches!(
-                        piece,
-                        CalibrationPiece::WhitePawn | CalibrationPiece::BlackPawn
-                    );
-                    let is_white = piece.is_white();
-
-                    if is_pawn && is_promotion_rank(to, is_white) {
-                        if let Some(new_piece) = current_board[to as usize] {
-                            let piece_type = calibration_piece_to_type(new_piece);
-
-                            if matches!(
-                                piece_type,
-                                PieceType::Queen | PieceType::Rook | PieceType::Bishop | PieceType::Knight
-                            ) {
-                                self.game.make_move_with_promotion(from, to, Some(piece_type));
-                                self.last_move = Some((from, to));
-                                buzzer.move_sound();
-                                self.led_state.clear_all();
-                                self.led_dirty = true;
-                                self.check_game_end(display, buzzer);
-                                self.update_display(display);
-                                return;
-                            }
-                        }
+    /// Whether the board is part way through the expected move, such as a
+    /// rook placed first when castling
+    fn expected_move_started(&self, current_board: &[Option<CalibrationPiece>; 64]) -> bool {
+        let Some(expected) = self.expected_move() else {
+            return false;
+        };
+        let position = self.game.board().position();
+        position
+            .legal_moves()
+            .find(expected.from, expected.to, expected.promotion)
+            .is_some_and(|mv| is_partial(position, current_board, &mv))
+    }
 
-                        self.pending_promotion = Some(PendingPromotion { from, to });
-                        display.show_promotion_prompt();
-                        self.led_state.clear_all();
-                        self.led_state.set(to);
-                        self.led_dirty = true;
-                        return;
-                    }
-                }
+    /// Castling started with the rook: the rook move was played on its own,
+    /// then the king crossed over it. The rook move is replaced by castling,
+    /// with the clock running on as if it had been one move.
+    fn detect_rook_first_castling<D: DisplayIO, Z: BuzzerIO>(
+        &mut self,
+        current_board: &[Option<CalibrationPiece>; 64],
+        display: &mut D,
+        buzzer: &mut Z,
+    ) -> bool {
+        if self.state != AppState::GameInProgress {
+            return false;
+        }
+        let Some(&last) = self.game.last_undo_info() else {
+            return false;
+        };
+        // The computer's moves are made exactly as it asked
+        let last_mover = match self.game.current_turn() {
+            Color::White => Color::Black,
+            Color::Black => Color::White,
+        };
+        if self.computer.is_some_and(|computer| computer.color == last_mover) {
+            return false;
+        }
 
-                self.game.make_move(from, to);
-                self.last_move = Some((from, to));
-                buzzer.move_sound();
-                self.led_state.clear_all();
-                self.led_dirty = true;
-                self.check_game_end(display, buzzer);
-                self.update_display(display);
-            } else {
-                buzzer.error_sound();
-                display.show_invalid_move();
+        let mut before = *self.game.board().position();
+        before.unmake_move(&last);
+        let Recognition::Move(mv) = recognize(&before, current_board) else {
+            return false;
+        };
+        let Some(castle) = before.castling_move(mv.from, mv.to) else {
+            return false;
+        };
+        if (castle.rook_from, castle.rook_to) != (last.from, last.to) {
+            return false;
+        }
+
+        self.game.retract_move();
+        self.complete_move(mv.from, mv.to, None, display, buzzer);
+        true
+    }
+
+    /// Count down the grace period while the board shows something no move
+    /// explains, then point out the squares that differ from the game
+    fn check_mismatch<D: DisplayIO>(&mut self, elapsed_ms: u32, display: &mut D) {
+        if !matches!(self.state, AppState::GameInProgress | AppState::Replaying)
+            || self.pending_takeback.is_some()
+            || self.takeback_request
+        {
+            self.mismatch_ms = None;
+            return;
+        }
+        let Some(mismatch_ms) = self.mismatch_ms else {
+            return;
+        };
+
+        let mismatch_ms = mismatch_ms.saturating_add(elapsed_ms);
+        if mismatch_ms < MISMATCH_GRACE_MS {
+            self.mismatch_ms = Some(mismatch_ms);
+            return;
+        }
+
+        self.mismatch_ms = None;
+        self.recovering_from = self.state;
+        self.state = AppState::Recovering;
+        self.game.piece_replaced();
+        if let Some(ref reading) = self.last_reading {
+            let current_board = self.calibration.reading_to_board(reading);
+            self.show_mismatch(&current_board, display);
+        }
+    }
+
+    /// While recovering, light the squares that still differ from the game
+    /// and resume once there are none
+    fn recover<D: DisplayIO, Z: BuzzerIO>(
+        &mut self,
+        reading: &RfidReading,
+        display: &mut D,
+        buzzer: &mut Z,
+    ) {
+        let current_board = self.calibration.reading_to_board(reading);
+        if !self.board_matches_game(&current_board) {
+            if self.mismatched_squares(&current_board).0 != self.led_state {
+                self.show_mismatch(&current_board, display);
             }
+            return;
+        }
+
+        self.state = self.recovering_from;
+        buzzer.move_sound();
+        self.led_state.clear_all();
+        self.led_dirty = true;
+        self.light_expected_move();
+        self.update_display(display);
+    }
+
+    fn show_mismatch<D: DisplayIO>(
+        &mut self,
+        current_board: &[Option<CalibrationPiece>; 64],
+        display: &mut D,
+    ) {
+        let (leds, count) = self.mismatched_squares(current_board);
+        self.led_state = leds;
+        self.led_dirty = true;
+        display.show_board_mismatch(count);
+    }
+
+    /// Squares whose piece differs from the game position, and their number
+    fn mismatched_squares(&self, current_board: &[Option<CalibrationPiece>; 64]) -> (LedState, u8) {
+        let mut leds = LedState::new();
+        let mut count = 0;
+        for square in 0..64u8 {
+            if !square_matches(self.game.board().position(), current_board, square) {
+                leds.set(square);
+                count += 1;
+            }
+        }
+        (leds, count)
+    }
+
+    /// Apply a detected physical move to the game
+    fn complete_move<D: DisplayIO, Z: BuzzerIO>(
+        &mut self,
+        from: u8,
+        to: u8,
+        promotion: Option<PieceType>,
+        display: &mut D,
+        buzzer: &mut Z,
+    ) {
+        let san = San::from_move(self.game.board(), from, to, promotion);
+        let mover = self.game.current_turn();
+        let stage = self.game.timer().stage(mover);
+        self.game.make_move_with_promotion(from, to, promotion);
+        self.last_move = Some(san);
+        self.resign_prompt = None;
+        self.draw_offer = None;
+        if self.game.timer().stage(mover) != stage {
+            self.stage_notice = Some((mover, STAGE_NOTICE_TICKS));
+        }
+        self.computer_move = None;
+        buzzer.move_sound();
+        self.led_state.clear_all();
+        self.led_dirty = true;
+        self.check_game_end(display, buzzer);
+        self.read_replay_move();
+        self.find_computer_move();
+        self.light_expected_move();
+
+        if self.state == AppState::Replaying && self.expected_replay_move().is_none() {
+            self.state = AppState::GameEnded;
+            buzzer.game_over_sound();
+            display.show_replay_complete();
+            return;
+        }
+
+        self.update_display(display);
+    }
+
+    /// Read the loaded game's move at the current ply
+    ///
+    /// The moves already made were checked when the game was loaded, so the
+    /// text is read again from the start and they are skipped. This also
+    /// follows takebacks.
+    fn read_replay_move(&mut self) {
+        let Some(replay) = self.replay.as_mut() else {
+            return;
+        };
+        let ply = self.game.move_count();
+        let board = self.game.board();
+        replay.ply = ply;
+        replay.next = PgnReader::new(replay.pgn).ok().and_then(|(mut reader, _)| {
+            while reader.ply() < ply {
+                if !reader.skip_move().ok()? {
+                    return None;
+                }
+            }
+            reader.next_move(board).ok().flatten()
+        });
+    }
+
+    /// Next move of the loaded game, if replaying
+    fn expected_replay_move(&self) -> Option<MoveRecord> {
+        if self.state != AppState::Replaying {
+            return None;
+        }
+        let replay = self.replay.as_ref()?;
+        replay.next.filter(|_| replay.ply == self.game.move_count())
+    }
+
+    /// Move that has to be made on the board next: the replayed move or the
+    /// computer's move
+    fn expected_move(&self) -> Option<MoveRecord> {
+        if let Some(mv) = self.computer_move {
+            return Some(MoveRecord {
+                from: mv.from,
+                to: mv.to,
+                promotion: mv.promotion,
+            });
+        }
+        self.expected_replay_move()
+    }
+
+    /// Whether a move may be played (any legal move unless a move is expected)
+    fn allows_move(&self, from: u8, to: u8, promotion: Option<PieceType>) -> bool {
+        match self.expected_move() {
+            Some(expected) => expected == MoveRecord { from, to, promotion },
+            None => self.state != AppState::Replaying && !self.engine.is_searching(),
+        }
+    }
+
+    /// Whether a pawn reaching the last rank matches the expected move
+    fn allows_promotion(&self, from: u8, to: u8) -> bool {
+        match self.expected_move() {
+            Some(expected) => expected.from == from && expected.to == to,
+            None => self.state != AppState::Replaying && !self.engine.is_searching(),
+        }
+    }
+
+    /// Start the engine on a move if it is the computer's turn
+    ///
+    /// The search runs a slice per tick, see `continue_search`.
+    fn find_computer_move(&mut self) {
+        self.computer_move = None;
+        self.engine.stop();
+        if self.state != AppState::GameInProgress {
+            return;
+        }
+        let Some(computer) = self.computer else {
+            return;
+        };
+        if computer.color != self.game.current_turn() {
+            return;
+        }
+
+        let limits = SearchLimits::level(computer.level);
+        self.engine.start(self.game.board().position(), limits);
+    }
+
+    /// Search on for the computer's move, lighting it once found
+    fn continue_search<D: DisplayIO>(&mut self, display: &mut D) {
+        if self.state != AppState::GameInProgress || !self.engine.is_searching() {
+            return;
+        }
+        let Some(result) = self.engine.step(SEARCH_SLICE_NODES) else {
+            return;
+        };
+        self.computer_move = result.best_move;
+        self.light_expected_move();
+        self.update_display(display);
+    }
+
+    /// Whether the computer is to move: still thinking, or waiting for its
+    /// move to be made on the board
+    fn computer_to_move(&self) -> bool {
+        self.computer_move.is_some() || self.engine.is_searching()
+    }
+
+    /// Light the from/to squares of the replayed or computer move
+    ///
+    /// Leaves the LEDs alone while a takeback or promotion is pending.
+    fn light_expected_move(&mut self) {
+        if self.pending_takeback.is_some() || self.pending_promotion.is_some() {
+            return;
+        }
+        let Some(expected) = self.expected_move() else {
+            return;
+        };
+
+        let leds = LedState::from_move(expected.from, expected.to);
+        if leds != self.led_state {
+            self.led_state = leds;
+            self.led_dirty = true;
         }
     }
 
@@ -423,14 +1227,8 @@ impl App {
         };
 
         if takeback.is_complete(current_board) {
-            self.game.undo_move();
-            self.last_move = None;
             self.pending_takeback = None;
-            buzzer.move_sound();
-            self.led_state.clear_all();
-            self.led_dirty = true;
-            display.show_takeback_complete();
-            self.update_display(display);
+            self.takeback_ready(display, buzzer);
             return true;
         }
 
@@ -444,6 +1242,67 @@ impl App {
         true
     }
 
+    /// The pieces are back in place for a takeback: undo the move, unless the
+    /// opponent has to agree first
+    fn takeback_ready<D: DisplayIO, Z: BuzzerIO>(&mut self, display: &mut D, buzzer: &mut Z) {
+        buzzer.move_sound();
+        self.led_state.clear_all();
+        self.led_dirty = true;
+
+        // The computer never objects
+        let opponent = self.game.current_turn();
+        let computer_opponent = self.computer.is_some_and(|computer| computer.color == opponent);
+        if self.takeback_policy == TakebackPolicy::OpponentConfirms && !computer_opponent {
+            self.takeback_request = true;
+            self.update_display(display);
+            return;
+        }
+
+        self.undo_takeback(display);
+    }
+
+    fn undo_takeback<D: DisplayIO>(&mut self, display: &mut D) {
+        self.game.undo_move();
+        self.last_move = None;
+        self.takeback_request = false;
+        display.show_takeback_complete();
+        self.read_replay_move();
+        self.find_computer_move();
+        self.light_expected_move();
+        self.update_display(display);
+    }
+
+    /// While the opponent is asked to agree to a takeback, putting the
+    /// pieces back as they were withdraws the request
+    fn handle_takeback_request<D: DisplayIO>(
+        &mut self,
+        current_board: &[Option<CalibrationPiece>; 64],
+        display: &mut D,
+    ) -> bool {
+        if !self.takeback_request {
+            return false;
+        }
+
+        if self.board_matches_game(current_board) {
+            self.takeback_request = false;
+            self.light_expected_move();
+            self.update_display(display);
+        }
+        true
+    }
+
+    /// Confirm a takeback with the button of the player to move
+    fn confirm_takeback<D: DisplayIO>(&mut self, button: Color, display: &mut D) {
+        if button == self.game.current_turn() {
+            self.undo_takeback(display);
+        }
+    }
+
+    /// Whether `board` has the pieces of the game position on their squares
+    fn board_matches_game(&self, board: &[Option<CalibrationPiece>; 64]) -> bool {
+        matches_position(self.game.board().position(), board)
+    }
+
     fn handle_pending_promotion<D: DisplayIO, Z: BuzzerIO>(
         &mut self,
         current_board: &[Option<CalibrationPiece>; 64],
@@ -460,18 +1319,14 @@ impl App {
         if let Some(new_piece) = current_board[to as usize] {
             let piece_type = calibration_piece_to_type(new_piece);
 
+            // During replay, wait until the promotion piece from the game is placed
             if matches!(
                 piece_type,
                 PieceType::Queen | PieceType::Rook | PieceType::Bishop | PieceType::Knight
-            ) {
-                self.game.make_move_with_promotion(from, to, Some(piece_type));
-                self.last_move = Some((from, to));
+            ) && self.allows_move(from, to, Some(piece_type))
+            {
                 self.pending_promotion = None;
-                buzzer.move_sound();
-                self.led_state.clear_all();
-                self.led_dirty = true;
-                self.check_game_end(display, buzzer);
-                self.update_display(display);
+                self.complete_move(from, to, Some(piece_type), display, buzzer);
                 return true;
             }
         }
@@ -490,8 +1345,12 @@ impl App {
             return false;
         };
 
-        let undo_from = undo_info.from;
-        let undo_to = undo_info.to;
+        let (undo_from, undo_to) = match undo_info.castling {
+            // In Chess960 the king may not move when castling; the rook always does
+            Some(castle) if castle.king_from == castle.king_to => (castle.rook_from, castle.rook_to),
+            Some(castle) => (castle.king_from, castle.king_to),
+            None => (undo_info.from, undo_info.to),
+        };
         let has_capture = undo_info.captured.is_some();
         let is_castling = undo_info.castling.is_some();
         let is_promotion = undo_info.promotion.is_some();
@@ -506,25 +1365,16 @@ impl App {
 
         if let (Some(from), Some(to)) = (lifted_from, placed_to) {
             if from == undo_to && to == undo_from {
-                if is_simple_takeback {
-                    self.game.undo_move();
-                    self.last_move = None;
-                    buzzer.move_sound();
-                    self.led_state.clear_all();
-                    self.led_dirty = true;
-                    self.update_display(display);
+                if self.takeback_policy == TakebackPolicy::Disabled {
+                    buzzer.error_sound();
+                    display.show_invalid_move();
                     return true;
                 }
 
                 let pending = PendingTakeback::from_undo_info(undo_info, side_is_white);
 
-                if pending.is_complete(current_board) {
-                    self.game.undo_move();
-                    self.last_move = None;
-                    buzzer.move_sound();
-                    self.led_state.clear_all();
-                    self.led_dirty = true;
-                    self.update_display(display);
+                if is_simple_takeback || pending.is_complete(current_board) {
+                    self.takeback_ready(display, buzzer);
                     return true;
                 }
 
@@ -586,20 +1436,81 @@ impl App {
                 buzzer.game_over_sound();
                 display.show_draw("Stalemate");
             }
-            BoardStatus::FiftyMoveRule => {
+            BoardStatus::FivefoldRepetition => {
                 self.state = AppState::GameEnded;
                 buzzer.game_over_sound();
-                display.show_draw("50-move");
+                display.show_draw("5-fold");
+            }
+            BoardStatus::SeventyFiveMoveRule => {
+                self.state = AppState::GameEnded;
+                buzzer.game_over_sound();
+                display.show_draw("75-move");
             }
             BoardStatus::InsufficientMaterial => {
                 self.state = AppState::GameEnded;
                 buzzer.game_over_sound();
                 display.show_draw("No material");
             }
-            BoardStatus::Ongoing => {}
+            // Only reported by `claimable_draw`; the player decides
+            BoardStatus::FiftyMoveRule | BoardStatus::ThreefoldRepetition | BoardStatus::Ongoing => {}
         }
     }
 
+    /// Claim a draw by threefold repetition or the 50-move rule
+    ///
+    /// Returns `false` if no draw can currently be claimed.
+    pub fn claim_draw<D: DisplayIO>(&mut self, display: &mut D) -> bool {
+        if self.state != AppState::GameInProgress {
+            return false;
+        }
+        let Some(claim) = self.game.claimable_draw() else {
+            return false;
+        };
+
+        self.state = AppState::GameEnded;
+        self.game.set_status(GameStatus::Draw);
+        self.led_state.clear_all();
+        self.led_dirty = true;
+        display.show_draw(claimable_draw_reason(claim));
+        true
+    }
+
+    /// Resign the game for `color`
+    ///
+    /// Returns `false` if no game is in progress.
+    pub fn resign<D: DisplayIO>(&mut self, color: Color, display: &mut D) -> bool {
+        if !matches!(self.state, AppState::GameInProgress | AppState::GamePaused) {
+            return false;
+        }
+
+        let (status, winner) = match color {
+            Color::White => (GameStatus::BlackWins, "Black"),
+            Color::Black => (GameStatus::WhiteWins, "White"),
+        };
+        self.state = AppState::GameEnded;
+        self.game.set_status(status);
+        self.led_state.clear_all();
+        self.led_dirty = true;
+        display.show_game_over(winner, "Resignation");
+        true
+    }
+
+    /// End the game in a draw both players agreed to
+    ///
+    /// Returns `false` if no game is in progress.
+    pub fn agree_draw<D: DisplayIO>(&mut self, display: &mut D) -> bool {
+        if !matches!(self.state, AppState::GameInProgress | AppState::GamePaused) {
+            return false;
+        }
+
+        self.state = AppState::GameEnded;
+        self.game.set_status(GameStatus::Draw);
+        self.led_state.clear_all();
+        self.led_dirty = true;
+        display.show_draw("Agreed");
+        true
+    }
+
     pub fn tick<D: DisplayIO, Z: BuzzerIO, C: ClockDisplayIO>(
         &mut self,
         elapsed_ms: u32,
@@ -628,19 +1539,79 @@ impl App {
             return;
         }
 
-        if self.state != AppState::GameInProgress {
+        if let Some((color, ticks)) = self.stage_notice {
+            self.stage_notice = ticks.checked_sub(1).filter(|&t| t > 0).map(|t| (color, t));
+        }
+
+        self.run_clock(elapsed_ms, display, buzzer);
+        self.check_mismatch(elapsed_ms, display);
+        self.continue_search(display);
+    }
+
+    /// Tick the app at time `now_ms` from a [`TimeSource`].
+    ///
+    /// The game clock is charged with the full time since the previous
+    /// timestamp, so delayed or coalesced ticks lose no time.
+    pub fn tick_at<D: DisplayIO, Z: BuzzerIO, C: ClockDisplayIO>(
+        &mut self,
+        now_ms: u32,
+        display: &mut D,
+        buzzer: &mut Z,
+        clock: &mut C,
+    ) {
+        let elapsed_ms = self.advance_clock(now_ms);
+        self.tick(elapsed_ms, display, buzzer, clock);
+    }
+
+    /// Move the clock timestamp forward to `now_ms`, returning the time passed
+    fn advance_clock(&mut self, now_ms: u32) -> u32 {
+        let elapsed_ms = match self.clock_ms {
+            Some(last_ms) => now_ms.wrapping_sub(last_ms),
+            None => 0,
+        };
+        // A timestamp older than the last one has already been accounted for
+        if elapsed_ms > i32::MAX as u32 {
+            return 0;
+        }
+        self.clock_ms = Some(now_ms);
+        elapsed_ms
+    }
+
+    /// Charge `elapsed_ms` to the player to move, ending the game on time
+    fn run_clock<D: DisplayIO, Z: BuzzerIO>(
+        &mut self,
+        elapsed_ms: u32,
+        display: &mut D,
+        buzzer: &mut Z,
+    ) {
+        // The clocks wait until the computer's move is made on the board
+        if self.state != AppState::GameInProgress || self.computer_to_move() {
             return;
         }
 
         let current_turn = self.game.current_turn();
 
-        if self.game.timer().is_low_time(current_turn) && elapsed_ms % 1000 < 50 {
-            buzzer.low_time_warning();
+        let overruns = self.game.timer().overruns(current_turn);
+        let expired = self.game.tick_timer(elapsed_ms);
+        if !expired && self.game.timer().overruns(current_turn) != overruns {
+            // Out of move time, but the penalty allows finishing the move
+            buzzer.error_sound();
         }
 
-        if self.game.tick_timer(elapsed_ms) {
+        match self.low_time.check(self.game.timer(), current_turn) {
+            Some(TimeWarning::Threshold(level)) => buzzer.low_time_warning(level),
+            Some(TimeWarning::Countdown) => buzzer.countdown_beep(),
+            None => {}
+        }
+
+        if expired {
             self.state = AppState::GameEnded;
             buzzer.time_expired();
+            if self.game.status() == GameStatus::Draw {
+                // The opponent could never have checkmated
+                display.show_draw("Time, no mate");
+                return;
+            }
             let winner = match current_turn {
                 Color::White => "Black",
                 Color::Black => "White",
@@ -652,34 +1623,62 @@ impl App {
     fn update_clock_displays<C: ClockDisplayIO>(&self, clock: &mut C) {
         let timer = self.game.timer();
         let current_turn = self.game.current_turn();
-        let game_active = self.state == AppState::GameInProgress;
+        let game_active = self.state == AppState::GameInProgress && !self.computer_to_move();
+        let in_delay = game_active && timer.in_delay();
 
-        let (white_min, white_sec) = timer.formatted_time(Color::White);
-        let (black_min, black_sec) = timer.formatted_time(Color::Black);
+        let white_active = game_active && matches!(current_turn, Color::White);
+        let black_active = game_active && matches!(current_turn, Color::Black);
 
-        clock.update_white(
-            white_min,
-            white_sec,
-            game_active && matches!(current_turn, Color::White),
-        );
-        clock.update_black(
-            black_min,
-            black_sec,
-            game_active && matches!(current_turn, Color::Black),
-        );
+        let white_time = timer.display_time(Color::White);
+        let black_time = timer.display_time(Color::Black);
+        clock.update_white(white_time, white_active, white_active && in_delay);
+        clock.update_black(black_time, black_active, black_active && in_delay);
     }
 
     pub fn update_display<D: DisplayIO>(&self, display: &mut D) {
+        if let Some(expected) = self.expected_replay_move() {
+            let board = self.game.board();
+            let san = San::from_move(board, expected.from, expected.to, expected.promotion);
+            let is_white = board.side_to_move() == PieceColor::White;
+            display.show_replay_move(board.fullmove_number(), is_white, san.as_str());
+            return;
+        }
+
         if self.state != AppState::GameInProgress {
             return;
         }
 
+        if self.takeback_request {
+            display.show_takeback_request(self.game.current_turn() == Color::White);
+            return;
+        }
+        if let Some(color) = self.resign_prompt {
+            display.show_resign_prompt(color == Color::White);
+            return;
+        }
+        if let Some(color) = self.draw_offer {
+            display.show_draw_offer(color.opponent() == Color::White);
+            return;
+        }
+
+        if let Some(mv) = self.computer_move {
+            let san = San::from_move(self.game.board(), mv.from, mv.to, mv.promotion);
+            display.show_computer_move(san.as_str());
+            return;
+        }
+
         let turn = self.game.current_turn();
         let is_white = matches!(turn, Color::White);
 
         display.show_turn(is_white);
-        if let Some((from, to)) = self.last_move {
-            display.show_last_move(from, to);
+        if let Some((color, _)) = self.stage_notice {
+            // Stages are shown counting the base time as stage 1
+            let stage = self.game.timer().stage(color) + 1;
+            display.show_time_stage(color == Color::White, stage);
+        } else if let Some(claim) = self.game.claimable_draw() {
+            display.show_draw_claimable(claimable_draw_reason(claim));
+        } else if let Some(ref san) = self.last_move {
+            display.show_last_move(san.as_str());
         }
     }
 
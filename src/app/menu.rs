@@ -0,0 +1,242 @@
+//! On-device time control selection.
+//!
+//! The blue button scrolls through the presets and a custom entry, the white
+//! button confirms. A custom time control is then set up in two steps, first
+//! the minutes and then the increment, with blue stepping through the values.
+
+use crate::game::timer::TimeControl;
+
+/// Preset time controls as (minutes, increment seconds)
+pub const TIME_CONTROL_PRESETS: [(u8, u8); 6] =
+    [(1, 0), (3, 2), (5, 0), (10, 0), (15, 10), (30, 0)];
+
+/// Minutes offered for a custom time control
+const CUSTOM_MINUTES: [u8; 13] = [1, 2, 3, 4, 5, 10, 15, 20, 25, 30, 45, 60, 90];
+
+/// Increments in seconds offered for a custom time control
+const CUSTOM_INCREMENTS: [u8; 9] = [0, 1, 2, 3, 5, 10, 15, 20, 30];
+
+/// Custom entry: minutes 10, increment 0
+const DEFAULT_CUSTOM: (usize, usize) = (5, 0);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MenuItem {
+    /// The time control already configured, if it is not one of the presets
+    Configured(TimeControl),
+    Preset(usize),
+    Custom,
+}
+
+/// Value being edited for a custom time control
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CustomField {
+    Minutes,
+    Increment,
+}
+
+/// What the display should show for the menu
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MenuView {
+    /// Browsing; `custom` is set on the custom entry and on a configured
+    /// time control that is not one of the presets
+    Item { minutes: u8, increment: u8, custom: bool },
+    /// Editing one value of the custom time control
+    Editing { minutes: u8, increment: u8, field: CustomField },
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TimeControlMenu {
+    item: MenuItem,
+    editing: Option<CustomField>,
+    /// Indices into CUSTOM_MINUTES and CUSTOM_INCREMENTS
+    custom: (usize, usize),
+}
+
+impl TimeControlMenu {
+    /// Open the menu on `current`, the preset it matches if any
+    pub fn new(current: TimeControl) -> Self {
+        let item = TIME_CONTROL_PRESETS
+            .iter()
+            .position(|&(minutes, increment)| preset(minutes, increment) == current)
+            .map_or(MenuItem::Configured(current), MenuItem::Preset);
+
+        Self {
+            item,
+            editing: None,
+            custom: DEFAULT_CUSTOM,
+        }
+    }
+
+    /// Blue button: next entry, or next value while editing
+    pub fn next(&mut self) {
+        match self.editing {
+            Some(CustomField::Minutes) => {
+                self.custom.0 = (self.custom.0 + 1) % CUSTOM_MINUTES.len();
+            }
+            Some(CustomField::Increment) => {
+                self.custom.1 = (self.custom.1 + 1) % CUSTOM_INCREMENTS.len();
+            }
+            None => {
+                self.item = match self.item {
+                    MenuItem::Configured(_) => MenuItem::Preset(0),
+                    MenuItem::Preset(index) if index + 1 < TIME_CONTROL_PRESETS.len() => {
+                        MenuItem::Preset(index + 1)
+                    }
+                    MenuItem::Preset(_) => MenuItem::Custom,
+                    MenuItem::Custom => MenuItem::Preset(0),
+                };
+            }
+        }
+    }
+
+    /// White button: returns the chosen time control once selection is done
+    pub fn confirm(&mut self) -> Option<TimeControl> {
+        match (self.item, self.editing) {
+            (MenuItem::Custom, None) => {
+                self.editing = Some(CustomField::Minutes);
+                None
+            }
+            (MenuItem::Custom, Some(CustomField::Minutes)) => {
+                self.editing = Some(CustomField::Increment);
+                None
+            }
+            _ => Some(self.time_control()),
+        }
+    }
+
+    /// Time control currently shown, for previewing on the clocks
+    pub fn time_control(&self) -> TimeControl {
+        match self.item {
+            MenuItem::Configured(time_control) => time_control,
+            MenuItem::Preset(index) => {
+                let (minutes, increment) = TIME_CONTROL_PRESETS[index];
+                preset(minutes, increment)
+            }
+            MenuItem::Custom => {
+                let (minutes, increment) = self.custom_values();
+                preset(minutes, increment)
+            }
+        }
+    }
+
+    pub fn view(&self) -> MenuView {
+        let (minutes, increment) = match self.item {
+            MenuItem::Configured(time_control) => (
+                (time_control.white_base_ms / 60_000) as u8,
+                (time_control.increment_ms / 1000) as u8,
+            ),
+            MenuItem::Preset(index) => TIME_CONTROL_PRESETS[index],
+            MenuItem::Custom => self.custom_values(),
+        };
+
+        match self.editing {
+            Some(field) => MenuView::Editing { minutes, increment, field },
+            None => MenuView::Item {
+                minutes,
+                increment,
+                custom: !matches!(self.item, MenuItem::Preset(_)),
+            },
+        }
+    }
+
+    fn custom_values(&self) -> (u8, u8) {
+        (CUSTOM_MINUTES[self.custom.0], CUSTOM_INCREMENTS[self.custom.1])
+    }
+}
+
+fn preset(minutes: u8, increment: u8) -> TimeControl {
+    TimeControl::minutes(minutes as u32, increment as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(minutes: u8, increment: u8, custom: bool) -> MenuView {
+        MenuView::Item {
+            minutes,
+            increment,
+            custom,
+        }
+    }
+
+    #[test]
+    fn test_next_wraps_around() {
+        let mut menu = TimeControlMenu::new(TimeControl::minutes(15, 10));
+        assert_eq!(menu.view(), item(15, 10, false));
+
+        menu.next();
+        assert_eq!(menu.view(), item(30, 0, false));
+        menu.next();
+        assert_eq!(menu.view(), item(10, 0, true));
+        menu.next();
+        assert_eq!(menu.view(), item(1, 0, false));
+        assert_eq!(menu.time_control(), TimeControl::minutes(1, 0));
+    }
+
+    #[test]
+    fn test_configured_time_control() {
+        let configured = TimeControl::minutes(7, 4);
+        let mut menu = TimeControlMenu::new(configured);
+        assert_eq!(menu.view(), item(7, 4, true));
+        assert_eq!(menu.confirm(), Some(configured));
+
+        // It is left for the presets and not offered again
+        menu.next();
+        assert_eq!(menu.view(), item(1, 0, false));
+        for _ in 0..TIME_CONTROL_PRESETS.len() {
+            menu.next();
+        }
+        assert_eq!(menu.view(), item(10, 0, true));
+    }
+
+    #[test]
+    fn test_custom_minutes_then_increment() {
+        let mut menu = TimeControlMenu::new(TimeControl::minutes(30, 0));
+        menu.next();
+        assert_eq!(menu.view(), item(10, 0, true));
+
+        assert_eq!(menu.confirm(), None);
+        assert_eq!(
+            menu.view(),
+            MenuView::Editing {
+                minutes: 10,
+                increment: 0,
+                field: CustomField::Minutes,
+            }
+        );
+        menu.next();
+        assert_eq!(menu.time_control(), TimeControl::minutes(15, 0));
+
+        assert_eq!(menu.confirm(), None);
+        menu.next();
+        menu.next();
+        assert_eq!(
+            menu.view(),
+            MenuView::Editing {
+                minutes: 15,
+                increment: 2,
+                field: CustomField::Increment,
+            }
+        );
+        assert_eq!(menu.confirm(), Some(TimeControl::minutes(15, 2)));
+    }
+
+    #[test]
+    fn test_custom_values_wrap_around() {
+        let mut menu = TimeControlMenu::new(TimeControl::minutes(30, 0));
+        menu.next();
+        menu.confirm();
+
+        for _ in 0..CUSTOM_MINUTES.len() - DEFAULT_CUSTOM.0 {
+            menu.next();
+        }
+        assert_eq!(menu.time_control(), TimeControl::minutes(1, 0));
+
+        menu.confirm();
+        for _ in 0..CUSTOM_INCREMENTS.len() {
+            menu.next();
+        }
+        assert_eq!(menu.time_control(), TimeControl::minutes(1, 0));
+    }
+}
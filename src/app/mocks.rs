@@ -1,6 +1,9 @@
-use crate::app::traits::{BoardIO, BuzzerIO, ClockDisplayIO, DelayIO, DisplayIO};
+use crate::app::traits::{
+    BoardIO, BuzzerIO, ClockDisplayIO, DelayIO, DisplayIO, RandomIO, TimeSource,
+};
 use crate::certabo::board::BoardError;
 use crate::certabo::simulator::SimulatedBoard;
+use crate::game::timer::TimeDisplay;
 
 impl BoardIO for SimulatedBoard {
     fn poll(&mut self) {
@@ -37,17 +40,31 @@ pub enum DisplayMessage {
     CalibrationComplete,
     WaitingForSetup,
     Turn { is_white: bool },
-    LastMove { from: u8, to: u8 },
+    LastMove { san: String },
     Paused,
     PromotionPrompt,
     InvalidMove,
     GameOver { winner: String, reason: String },
     Draw { reason: String },
+    DrawClaimable { reason: String },
     TakebackComplete,
     TakebackCastling,
     TakebackEnPassant,
     TakebackPromotion,
     TakebackCapture,
+    TakebackRequest { opponent_is_white: bool },
+    ResignPrompt { is_white: bool },
+    DrawOffer { opponent_is_white: bool },
+    ReplayMove { move_number: u16, is_white: bool, san: String },
+    ReplayComplete,
+    Chess960Setup { index: u16, back_rank: String },
+    VsComputerSetup { level: u8 },
+    ComputerMove { san: String },
+    TimeStage { is_white: bool, stage: u8 },
+    TimeControlMenu { minutes: u8, increment: u8, custom: bool },
+    CustomTimeControl { minutes: u8, increment: u8, editing_increment: bool },
+    BoardMismatch { squares: u8 },
+    SetupProblem { problem: String },
 }
 
 impl MockDisplay {
@@ -82,8 +99,8 @@ impl DisplayIO for MockDisplay {
         self.record(DisplayMessage::Turn { is_white });
     }
 
-    fn show_last_move(&mut self, from: u8, to: u8) {
-        self.record(DisplayMessage::LastMove { from, to });
+    fn show_last_move(&mut self, san: &str) {
+        self.record(DisplayMessage::LastMove { san: san.into() });
     }
 
     fn show_paused(&mut self) {
@@ -111,6 +128,12 @@ impl DisplayIO for MockDisplay {
         });
     }
 
+    fn show_draw_claimable(&mut self, reason: &str) {
+        self.record(DisplayMessage::DrawClaimable {
+            reason: reason.into(),
+        });
+    }
+
     fn show_takeback_complete(&mut self) {
         self.record(DisplayMessage::TakebackComplete);
     }
@@ -130,6 +153,75 @@ impl DisplayIO for MockDisplay {
     fn show_takeback_capture(&mut self) {
         self.record(DisplayMessage::TakebackCapture);
     }
+
+    fn show_takeback_request(&mut self, opponent_is_white: bool) {
+        self.record(DisplayMessage::TakebackRequest { opponent_is_white });
+    }
+
+    fn show_resign_prompt(&mut self, is_white: bool) {
+        self.record(DisplayMessage::ResignPrompt { is_white });
+    }
+
+    fn show_draw_offer(&mut self, opponent_is_white: bool) {
+        self.record(DisplayMessage::DrawOffer { opponent_is_white });
+    }
+
+    fn show_replay_move(&mut self, move_number: u16, is_white: bool, san: &str) {
+        self.record(DisplayMessage::ReplayMove {
+            move_number,
+            is_white,
+            san: san.into(),
+        });
+    }
+
+    fn show_replay_complete(&mut self) {
+        self.record(DisplayMessage::ReplayComplete);
+    }
+
+    fn show_chess960_setup(&mut self, index: u16, back_rank: &str) {
+        self.record(DisplayMessage::Chess960Setup {
+            index,
+            back_rank: back_rank.into(),
+        });
+    }
+
+    fn show_vs_computer_setup(&mut self, level: u8) {
+        self.record(DisplayMessage::VsComputerSetup { level });
+    }
+
+    fn show_computer_move(&mut self, san: &str) {
+        self.record(DisplayMessage::ComputerMove { san: san.into() });
+    }
+
+    fn show_time_stage(&mut self, is_white: bool, stage: u8) {
+        self.record(DisplayMessage::TimeStage { is_white, stage });
+    }
+
+    fn show_time_control_menu(&mut self, minutes: u8, increment: u8, custom: bool) {
+        self.record(DisplayMessage::TimeControlMenu {
+            minutes,
+            increment,
+            custom,
+        });
+    }
+
+    fn show_custom_time_control(&mut self, minutes: u8, increment: u8, editing_increment: bool) {
+        self.record(DisplayMessage::CustomTimeControl {
+            minutes,
+            increment,
+            editing_increment,
+        });
+    }
+
+    fn show_board_mismatch(&mut self, squares: u8) {
+        self.record(DisplayMessage::BoardMismatch { squares });
+    }
+
+    fn show_setup_problem(&mut self, problem: &str) {
+        self.record(DisplayMessage::SetupProblem {
+            problem: problem.to_string(),
+        });
+    }
 }
 
 #[derive(Default)]
@@ -143,7 +235,8 @@ pub enum BuzzerSound {
     Move,
     Error,
     GameOver,
-    LowTime,
+    LowTime(u8),
+    Countdown,
     TimeExpired,
 }
 
@@ -170,8 +263,12 @@ impl BuzzerIO for MockBuzzer {
         self.sounds.push(BuzzerSound::GameOver);
     }
 
-    fn low_time_warning(&mut self) {
-        self.sounds.push(BuzzerSound::LowTime);
+    fn low_time_warning(&mut self, level: u8) {
+        self.sounds.push(BuzzerSound::LowTime(level));
+    }
+
+    fn countdown_beep(&mut self) {
+        self.sounds.push(BuzzerSound::Countdown);
     }
 
     fn time_expired(&mut self) {
@@ -181,10 +278,12 @@ impl BuzzerIO for MockBuzzer {
 
 #[derive(Default)]
 pub struct MockClockDisplay {
-    pub white_time: (u8, u8),
-    pub black_time: (u8, u8),
+    pub white_time: TimeDisplay,
+    pub black_time: TimeDisplay,
     pub white_active: bool,
     pub black_active: bool,
+    pub white_in_delay: bool,
+    pub black_in_delay: bool,
     pub colon_on: bool,
 }
 
@@ -199,14 +298,16 @@ impl ClockDisplayIO for MockClockDisplay {
         self.colon_on = !self.colon_on;
     }
 
-    fn update_white(&mut self, minutes: u8, seconds: u8, active: bool) {
-        self.white_time = (minutes, seconds);
+    fn update_white(&mut self, time: TimeDisplay, active: bool, in_delay: bool) {
+        self.white_time = time;
         self.white_active = active;
+        self.white_in_delay = in_delay;
     }
 
-    fn update_black(&mut self, minutes: u8, seconds: u8, active: bool) {
-        self.black_time = (minutes, seconds);
+    fn update_black(&mut self, time: TimeDisplay, active: bool, in_delay: bool) {
+        self.black_time = time;
         self.black_active = active;
+        self.black_in_delay = in_delay;
     }
 }
 
@@ -226,3 +327,42 @@ impl DelayIO for MockDelay {
         self.total_delay_ms += ms;
     }
 }
+
+#[derive(Default)]
+pub struct MockTimeSource {
+    pub time_ms: u32,
+}
+
+impl MockTimeSource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&mut self, ms: u32) {
+        self.time_ms = self.time_ms.wrapping_add(ms);
+    }
+}
+
+impl TimeSource for MockTimeSource {
+    fn now_ms(&self) -> u32 {
+        self.time_ms
+    }
+}
+
+/// Returns `value` every time
+#[derive(Default)]
+pub struct MockRandom {
+    pub value: u32,
+}
+
+impl MockRandom {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl RandomIO for MockRandom {
+    fn next_u32(&mut self) -> u32 {
+        self.value
+    }
+}
@@ -1,11 +1,20 @@
+pub mod buttons;
 pub mod logic;
+pub mod menu;
+pub mod recognizer;
 pub mod traits;
 
 #[cfg(feature = "simulator")]
 pub mod mocks;
 
-pub use logic::{App, AppState};
-pub use traits::{BoardIO, BuzzerIO, ClockDisplayIO, DelayIO, DisplayIO, Hardware};
+pub use buttons::{Button, ButtonEvent};
+pub use logic::{App, AppState, ComputerOpponent, TakebackPolicy};
+pub use traits::{
+    BoardIO, BuzzerIO, ClockDisplayIO, DelayIO, DisplayIO, Hardware, RandomIO, TimeSource,
+};
 
 #[cfg(feature = "simulator")]
-pub use mocks::{BuzzerSound, DisplayMessage, MockBuzzer, MockClockDisplay, MockDelay, MockDisplay};
+pub use mocks::{
+    BuzzerSound, DisplayMessage, MockBuzzer, MockClockDisplay, MockDelay, MockDisplay,
+    MockRandom, MockTimeSource,
+};
@@ -0,0 +1,332 @@
+//! Move recognition from the physical board.
+//!
+//! The pieces read from the board are compared with the game position and
+//! with the position after each legal move, rather than with the previous
+//! reading. The order in which pieces are lifted and placed therefore does
+//! not matter: a capture may land before the captured piece is taken off,
+//! castling may start with the rook, and a missed reading loses nothing.
+
+use crate::certabo::calibration::Piece as CalibrationPiece;
+use crate::game::chess::{square_name, Move, Piece, PieceColor, PieceType, Position};
+
+/// Pieces read from the board, by square
+pub type PhysicalBoard = [Option<CalibrationPiece>; 64];
+
+/// What the physical board shows relative to the game position
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Recognition {
+    /// The board shows the game position
+    Unchanged,
+    /// The board shows the position after this legal move
+    Move(Move),
+    /// A pawn stands on its promotion square, waiting to be exchanged
+    Promotion { from: u8, to: u8 },
+    /// Part way through a legal move: pieces in hand, or only some moved
+    InProgress,
+    /// No legal move explains the board
+    Unrecognized,
+}
+
+pub(crate) fn calibration_piece_to_type(piece: CalibrationPiece) -> PieceType {
+    match piece {
+        CalibrationPiece::WhitePawn | CalibrationPiece::BlackPawn => PieceType::Pawn,
+        CalibrationPiece::WhiteKnight | CalibrationPiece::BlackKnight => PieceType::Knight,
+        CalibrationPiece::WhiteBishop | CalibrationPiece::BlackBishop => PieceType::Bishop,
+        CalibrationPiece::WhiteRook | CalibrationPiece::BlackRook => PieceType::Rook,
+        CalibrationPiece::WhiteQueen | CalibrationPiece::BlackQueen => PieceType::Queen,
+        CalibrationPiece::WhiteKing | CalibrationPiece::BlackKing => PieceType::King,
+    }
+}
+
+fn board_piece(piece: CalibrationPiece) -> Piece {
+    let color = if piece.is_white() {
+        PieceColor::White
+    } else {
+        PieceColor::Black
+    };
+    Piece::new(calibration_piece_to_type(piece), color)
+}
+
+/// Whether the board has the pieces of `position` on their squares
+pub fn matches_position(position: &Position, physical: &PhysicalBoard) -> bool {
+    (0..64u8).all(|square| square_matches(position, physical, square))
+}
+
+/// Whether `square` holds the same piece on the board as in `position`
+pub fn square_matches(position: &Position, physical: &PhysicalBoard, square: u8) -> bool {
+    physical[square as usize].map(board_piece) == position.get(square)
+}
+
+/// What keeps the board from showing a start position
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SetupProblem {
+    /// Two squares hold each other's pieces
+    Swap(u8, u8),
+    /// A square that should hold a piece is empty
+    Missing(u8),
+    /// A square that should be empty holds a piece
+    Extra(u8),
+    /// A square holds the wrong piece
+    Wrong(u8),
+    /// This many squares differ
+    Squares(u8),
+}
+
+impl SetupProblem {
+    /// Text for the display, at most 16 characters
+    pub fn text<'a>(&self, buf: &'a mut [u8; 16]) -> &'a str {
+        let mut len = 0;
+        let mut push = |bytes: &[u8]| {
+            buf[len..len + bytes.len()].copy_from_slice(bytes);
+            len += bytes.len();
+        };
+        match *self {
+            SetupProblem::Swap(a, b) => {
+                push(b"Swap ");
+                push(&square_name(a));
+                push(b"/");
+                push(&square_name(b));
+            }
+            SetupProblem::Missing(square) => {
+                push(b"Missing piece ");
+                push(&square_name(square));
+            }
+            SetupProblem::Extra(square) => {
+                push(b"Remove piece ");
+                push(&square_name(square));
+            }
+            SetupProblem::Wrong(square) => {
+                push(b"Wrong piece ");
+                push(&square_name(square));
+            }
+            SetupProblem::Squares(count) => {
+                if count >= 10 {
+                    push(&[b'0' + count / 10]);
+                }
+                push(&[b'0' + count % 10]);
+                push(b" squares wrong");
+            }
+        }
+        core::str::from_utf8(&buf[..len]).unwrap_or("")
+    }
+}
+
+/// How the board differs from the start position `position`, if it does
+///
+/// A single wrong square, or two squares holding each other's pieces, are
+/// named; otherwise only the number of wrong squares is given.
+pub fn setup_problem(position: &Position, physical: &PhysicalBoard) -> Option<SetupProblem> {
+    let mut wrong = (0..64u8).filter(|&square| !square_matches(position, physical, square));
+    let first = wrong.next()?;
+    let Some(second) = wrong.next() else {
+        let problem = match (physical[first as usize], position.get(first)) {
+            (None, _) => SetupProblem::Missing(first),
+            (Some(_), None) => SetupProblem::Extra(first),
+            (Some(_), Some(_)) => SetupProblem::Wrong(first),
+        };
+        return Some(problem);
+    };
+
+    let rest = wrong.count() as u8;
+    let swapped = |a: u8, b: u8| {
+        physical[a as usize].is_some() && physical[a as usize].map(board_piece) == position.get(b)
+    };
+    if rest == 0 && swapped(first, second) && swapped(second, first) {
+        Some(SetupProblem::Swap(first, second))
+    } else {
+        Some(SetupProblem::Squares(rest + 2))
+    }
+}
+
+/// Find the legal move the physical board shows
+///
+/// Every legal move leaves different pieces on the board. This holds in
+/// Chess960 too: castling with the king already on its castling square moves
+/// only the rook, but past its king, where no rook move can go. All matching
+/// moves are still looked at, and should a plain move and castling both
+/// match, the plain move is played.
+pub fn recognize(before: &Position, physical: &PhysicalBoard) -> Recognition {
+    if matches_position(before, physical) {
+        return Recognition::Unchanged;
+    }
+
+    let mut found: Option<Move> = None;
+    let mut in_progress = false;
+    for mv in before.legal_moves().iter() {
+        let Some(after) = position_after(before, mv) else {
+            continue;
+        };
+
+        match compare(before, &after, physical, mv) {
+            Comparison::Complete => {
+                if found.is_none_or(|found| found.is_castle()) {
+                    found = Some(*mv);
+                }
+            }
+            Comparison::PawnOnPromotionSquare => {
+                return Recognition::Promotion {
+                    from: mv.from,
+                    to: mv.to,
+                };
+            }
+            Comparison::Partial => in_progress = true,
+            Comparison::Different => {}
+        }
+    }
+
+    match found {
+        Some(mv) => Recognition::Move(mv),
+        None if in_progress => Recognition::InProgress,
+        None => Recognition::Unrecognized,
+    }
+}
+
+/// Whether the board is part way through `mv`
+///
+/// A rook moved next to its king is both a complete rook move and the start
+/// of castling, so this tells whether castling may still follow.
+pub fn is_partial(before: &Position, physical: &PhysicalBoard, mv: &Move) -> bool {
+    position_after(before, mv)
+        .is_some_and(|after| matches!(compare(before, &after, physical, mv), Comparison::Partial))
+}
+
+fn position_after(before: &Position, mv: &Move) -> Option<Position> {
+    let mut after = *before;
+    after.make_move(mv.from, mv.to, mv.promotion)?;
+    Some(after)
+}
+
+enum Comparison {
+    /// The board shows the position after the move
+    Complete,
+    /// As complete, but the pawn itself stands on the promotion square
+    PawnOnPromotionSquare,
+    /// Every square the move changes is as before, as after, or empty, and
+    /// every other square is untouched
+    Partial,
+    Different,
+}
+
+fn compare(before: &Position, after: &Position, physical: &PhysicalBoard, mv: &Move) -> Comparison {
+    let mut complete = true;
+    let mut pawn_on_promotion_square = mv.promotion.is_some();
+
+    for square in 0..64u8 {
+        let actual = physical[square as usize].map(board_piece);
+        let (was, will_be) = (before.get(square), after.get(square));
+        if actual == will_be {
+            continue;
+        }
+        complete = false;
+
+        let pawn_placed = square == mv.to && actual == before.get(mv.from);
+        if !pawn_placed {
+            pawn_on_promotion_square = false;
+        }
+        if was == will_be && actual != was {
+            return Comparison::Different;
+        }
+        if actual.is_some() && actual != was && !pawn_placed {
+            return Comparison::Different;
+        }
+    }
+
+    if complete {
+        Comparison::Complete
+    } else if pawn_on_promotion_square {
+        Comparison::PawnOnPromotionSquare
+    } else {
+        Comparison::Partial
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The board with the pieces of `position` on their squares
+    fn physical(position: &Position) -> PhysicalBoard {
+        core::array::from_fn(|square| {
+            let piece = position.get(square as u8)?;
+            let white = piece.color == PieceColor::White;
+            Some(match (piece.piece_type, white) {
+                (PieceType::Pawn, true) => CalibrationPiece::WhitePawn,
+                (PieceType::Knight, true) => CalibrationPiece::WhiteKnight,
+                (PieceType::Bishop, true) => CalibrationPiece::WhiteBishop,
+                (PieceType::Rook, true) => CalibrationPiece::WhiteRook,
+                (PieceType::Queen, true) => CalibrationPiece::WhiteQueen,
+                (PieceType::King, true) => CalibrationPiece::WhiteKing,
+                (PieceType::Pawn, false) => CalibrationPiece::BlackPawn,
+                (PieceType::Knight, false) => CalibrationPiece::BlackKnight,
+                (PieceType::Bishop, false) => CalibrationPiece::BlackBishop,
+                (PieceType::Rook, false) => CalibrationPiece::BlackRook,
+                (PieceType::Queen, false) => CalibrationPiece::BlackQueen,
+                (PieceType::King, false) => CalibrationPiece::BlackKing,
+            })
+        })
+    }
+
+    fn board_after(position: &Position, from: u8, to: u8) -> PhysicalBoard {
+        physical(&position_after(position, &Move::new(from, to)).unwrap())
+    }
+
+    #[test]
+    fn test_recognize_move_in_any_order() {
+        let position = Position::starting_position();
+        let mut board = physical(&position);
+        assert_eq!(recognize(&position, &board), Recognition::Unchanged);
+
+        board[12] = None;
+        assert_eq!(recognize(&position, &board), Recognition::InProgress);
+        board[28] = Some(CalibrationPiece::WhitePawn);
+        assert!(matches!(
+            recognize(&position, &board),
+            Recognition::Move(Move { from: 12, to: 28, .. })
+        ));
+
+        board[20] = Some(CalibrationPiece::WhiteQueen);
+        assert_eq!(recognize(&position, &board), Recognition::Unrecognized);
+    }
+
+    #[test]
+    fn test_castling_with_king_on_its_square() {
+        let position = Position::from_fen("1r4kr/8/8/8/8/8/8/1R4KR w HBhb - 0 1").unwrap();
+
+        // With the king staying on g1, the h1 rook to f1 can only be castling:
+        // as a rook move it would pass the king
+        let Recognition::Move(castling) = recognize(&position, &board_after(&position, 6, 7)) else {
+            panic!("expected castling");
+        };
+        assert_eq!((castling.from, castling.to), (6, 7));
+        assert!(castling.is_castle());
+        assert!(position.legal_moves().find(7, 5, None).is_none());
+
+        // Rf1 is played with the b1 rook, keeping the right to castle kingside
+        let Recognition::Move(rook_move) = recognize(&position, &board_after(&position, 1, 5)) else {
+            panic!("expected a rook move");
+        };
+        assert_eq!((rook_move.from, rook_move.to), (1, 5));
+        assert!(!rook_move.is_castle());
+        let mut after = position;
+        after.make_move(rook_move.from, rook_move.to, None);
+        assert!(after.castling_rights(PieceColor::White).kingside);
+        assert!(!after.castling_rights(PieceColor::White).queenside);
+    }
+
+    #[test]
+    fn test_setup_problem() {
+        let position = Position::starting_position();
+        let mut board = physical(&position);
+        assert_eq!(setup_problem(&position, &board), None);
+
+        board.swap(3, 4);
+        assert_eq!(setup_problem(&position, &board), Some(SetupProblem::Swap(3, 4)));
+        assert_eq!(setup_problem(&position, &board).unwrap().text(&mut [0u8; 16]), "Swap d1/e1");
+
+        board.swap(3, 4);
+        board[12] = None;
+        board[28] = Some(CalibrationPiece::WhitePawn);
+        assert_eq!(setup_problem(&position, &board), Some(SetupProblem::Squares(2)));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::certabo::board::BoardError;
+use crate::game::timer::TimeDisplay;
 
 pub trait BoardIO {
     fn poll(&mut self);
@@ -14,17 +15,40 @@ pub trait DisplayIO {
     fn show_calibration_complete(&mut self);
     fn show_waiting_for_setup(&mut self);
     fn show_turn(&mut self, is_white: bool);
-    fn show_last_move(&mut self, from: u8, to: u8);
+    fn show_last_move(&mut self, san: &str);
     fn show_paused(&mut self);
     fn show_promotion_prompt(&mut self);
     fn show_invalid_move(&mut self);
     fn show_game_over(&mut self, winner: &str, reason: &str);
     fn show_draw(&mut self, reason: &str);
+    fn show_draw_claimable(&mut self, reason: &str);
     fn show_takeback_complete(&mut self);
     fn show_takeback_castling(&mut self);
     fn show_takeback_en_passant(&mut self);
     fn show_takeback_promotion(&mut self);
     fn show_takeback_capture(&mut self);
+    /// A takeback is waiting for the player to move to agree with their button
+    fn show_takeback_request(&mut self, opponent_is_white: bool);
+    /// A player is asked to confirm resigning with another press of their button
+    fn show_resign_prompt(&mut self, is_white: bool);
+    /// A draw is offered, waiting for the opponent to accept with their button
+    fn show_draw_offer(&mut self, opponent_is_white: bool);
+    fn show_replay_move(&mut self, move_number: u16, is_white: bool, san: &str);
+    fn show_replay_complete(&mut self);
+    fn show_chess960_setup(&mut self, index: u16, back_rank: &str);
+    fn show_vs_computer_setup(&mut self, level: u8);
+    fn show_computer_move(&mut self, san: &str);
+    /// A player entered time control stage `stage` (1 = base time)
+    fn show_time_stage(&mut self, is_white: bool, stage: u8);
+    /// Time control menu entry; `custom` for the custom entry and a configured
+    /// time control that is not a preset
+    fn show_time_control_menu(&mut self, minutes: u8, increment: u8, custom: bool);
+    /// Setting up a custom time control, the minutes or the increment
+    fn show_custom_time_control(&mut self, minutes: u8, increment: u8, editing_increment: bool);
+    /// The board differs from the game on `squares` squares
+    fn show_board_mismatch(&mut self, squares: u8);
+    /// The pieces are not set up in the start position, e.g. "Swap d1/e1"
+    fn show_setup_problem(&mut self, problem: &str);
 }
 
 pub trait BuzzerIO {
@@ -32,31 +56,52 @@ pub trait BuzzerIO {
     fn move_sound(&mut self);
     fn error_sound(&mut self);
     fn game_over_sound(&mut self);
-    fn low_time_warning(&mut self);
+    /// Low time threshold `level` crossed; higher levels are more urgent
+    fn low_time_warning(&mut self, level: u8);
+    /// One second passed in the final countdown
+    fn countdown_beep(&mut self);
     fn time_expired(&mut self);
 }
 
 pub trait ClockDisplayIO {
     fn toggle_colon(&mut self);
-    fn update_white(&mut self, minutes: u8, seconds: u8, active: bool);
-    fn update_black(&mut self, minutes: u8, seconds: u8, active: bool);
+    /// `in_delay` is set while the active clock is within its per-move delay
+    fn update_white(&mut self, time: TimeDisplay, active: bool, in_delay: bool);
+    fn update_black(&mut self, time: TimeDisplay, active: bool, in_delay: bool);
 }
 
 pub trait DelayIO {
     fn delay_ms(&mut self, ms: u32);
 }
 
-pub struct Hardware<B, D, Z, C, L>
+/// Monotonic millisecond timebase for the game clock.
+///
+/// The value wraps around at `u32::MAX`; callers only look at differences
+/// between timestamps, computed with `wrapping_sub`.
+pub trait TimeSource {
+    fn now_ms(&self) -> u32;
+}
+
+/// Random numbers, e.g. to draw a Chess960 start position.
+pub trait RandomIO {
+    fn next_u32(&mut self) -> u32;
+}
+
+pub struct Hardware<B, D, Z, C, L, T, R>
 where
     B: BoardIO,
     D: DisplayIO,
     Z: BuzzerIO,
     C: ClockDisplayIO,
     L: DelayIO,
+    T: TimeSource,
+    R: RandomIO,
 {
     pub board: B,
     pub display: D,
     pub buzzer: Z,
     pub clock: C,
     pub delay: L,
+    pub time: T,
+    pub random: R,
 }
@@ -1,11 +1,17 @@
-use crate::app::traits::{BoardIO, BuzzerIO, ClockDisplayIO, DelayIO, DisplayIO};
+use crate::app::traits::{
+    BoardIO, BuzzerIO, ClockDisplayIO, DelayIO, DisplayIO, RandomIO, TimeSource,
+};
 use crate::buzzer::Buzzer;
 use crate::certabo::board::BoardError;
 use crate::display::Display;
+use crate::game::timer::TimeDisplay;
 use crate::peripherals::Peripherals;
 use crate::tm1637::ChessClockDisplays;
 use crate::usb::{CP210xDevice, USB};
+use core::cell::RefCell;
+use critical_section::Mutex;
 use embedded_hal::delay::DelayNs;
+use sam3x8e_hal::pac::RTT;
 
 pub struct ArmBoard;
 
@@ -54,8 +60,8 @@ impl DisplayIO for ArmDisplay {
         Display::with(|d| d.show_turn(is_white));
     }
 
-    fn show_last_move(&mut self, from: u8, to: u8) {
-        Display::with(|d| d.show_last_move(from, to));
+    fn show_last_move(&mut self, san: &str) {
+        Display::with(|d| d.show_last_move(san));
     }
 
     fn show_paused(&mut self) {
@@ -78,6 +84,10 @@ impl DisplayIO for ArmDisplay {
         Display::with(|d| d.show_draw(reason));
     }
 
+    fn show_draw_claimable(&mut self, reason: &str) {
+        Display::with(|d| d.show_draw_claimable(reason));
+    }
+
     fn show_takeback_complete(&mut self) {
         Display::with(|d| d.show_takeback_complete());
     }
@@ -97,6 +107,58 @@ impl DisplayIO for ArmDisplay {
     fn show_takeback_capture(&mut self) {
         Display::with(|d| d.show_takeback_capture());
     }
+
+    fn show_takeback_request(&mut self, opponent_is_white: bool) {
+        Display::with(|d| d.show_takeback_request(opponent_is_white));
+    }
+
+    fn show_resign_prompt(&mut self, is_white: bool) {
+        Display::with(|d| d.show_resign_prompt(is_white));
+    }
+
+    fn show_draw_offer(&mut self, opponent_is_white: bool) {
+        Display::with(|d| d.show_draw_offer(opponent_is_white));
+    }
+
+    fn show_replay_move(&mut self, move_number: u16, is_white: bool, san: &str) {
+        Display::with(|d| d.show_replay_move(move_number, is_white, san));
+    }
+
+    fn show_replay_complete(&mut self) {
+        Display::with(|d| d.show_replay_complete());
+    }
+
+    fn show_chess960_setup(&mut self, index: u16, back_rank: &str) {
+        Display::with(|d| d.show_chess960_setup(index, back_rank));
+    }
+
+    fn show_vs_computer_setup(&mut self, level: u8) {
+        Display::with(|d| d.show_vs_computer_setup(level));
+    }
+
+    fn show_computer_move(&mut self, san: &str) {
+        Display::with(|d| d.show_computer_move(san));
+    }
+
+    fn show_time_stage(&mut self, is_white: bool, stage: u8) {
+        Display::with(|d| d.show_time_stage(is_white, stage));
+    }
+
+    fn show_time_control_menu(&mut self, minutes: u8, increment: u8, custom: bool) {
+        Display::with(|d| d.show_time_control_menu(minutes, increment, custom));
+    }
+
+    fn show_custom_time_control(&mut self, minutes: u8, increment: u8, editing_increment: bool) {
+        Display::with(|d| d.show_custom_time_control(minutes, increment, editing_increment));
+    }
+
+    fn show_board_mismatch(&mut self, squares: u8) {
+        Display::with(|d| d.show_board_mismatch(squares));
+    }
+
+    fn show_setup_problem(&mut self, problem: &str) {
+        Display::with(|d| d.show_setup_problem(problem));
+    }
 }
 
 pub struct ArmBuzzer;
@@ -118,8 +180,12 @@ impl BuzzerIO for ArmBuzzer {
         Buzzer::with(|b| b.game_over_sound());
     }
 
-    fn low_time_warning(&mut self) {
-        Buzzer::with(|b| b.low_time_warning());
+    fn low_time_warning(&mut self, level: u8) {
+        Buzzer::with(|b| b.low_time_warning(level));
+    }
+
+    fn countdown_beep(&mut self) {
+        Buzzer::with(|b| b.countdown_beep());
     }
 
     fn time_expired(&mut self) {
@@ -134,12 +200,12 @@ impl ClockDisplayIO for ArmClockDisplay {
         ChessClockDisplays::with(|d| d.toggle_colon());
     }
 
-    fn update_white(&mut self, minutes: u8, seconds: u8, active: bool) {
-        ChessClockDisplays::with(|d| d.update_white(minutes, seconds, active));
+    fn update_white(&mut self, time: TimeDisplay, active: bool, in_delay: bool) {
+        ChessClockDisplays::with(|d| d.update_white(time, active, in_delay));
     }
 
-    fn update_black(&mut self, minutes: u8, seconds: u8, active: bool) {
-        ChessClockDisplays::with(|d| d.update_black(minutes, seconds, active));
+    fn update_black(&mut self, time: TimeDisplay, active: bool, in_delay: bool) {
+        ChessClockDisplays::with(|d| d.update_black(time, active, in_delay));
     }
 }
 
@@ -150,3 +216,71 @@ impl DelayIO for ArmDelay {
         Peripherals::with(|p| p.delay.delay_ms(ms));
     }
 }
+
+/// Game timebase from the real-time timer.
+///
+/// The RTT counts the 32.768 kHz slow clock, prescaled to 1024 Hz, so it does
+/// not depend on the master clock setup and keeps counting while the main loop
+/// is blocked. Its 32-bit count wraps every 48.5 days, which is not a whole
+/// number of `u32` milliseconds, so the milliseconds are counted up from the
+/// ticks between readings instead of scaled from the count. That needs a
+/// reading at least once per RTT period, which the main loop easily makes.
+pub struct ArmTimeSource;
+
+/// RTT count at the last reading, and the milliseconds counted up to it
+static RTT_CLOCK: Mutex<RefCell<RttClock>> = Mutex::new(RefCell::new(RttClock {
+    ticks: 0,
+    ms: 0,
+    remainder: 0,
+}));
+
+struct RttClock {
+    ticks: u32,
+    ms: u32,
+    /// Ticks × 1000 not yet making up a whole millisecond
+    remainder: u32,
+}
+
+impl RttClock {
+    /// Count the ticks since the last reading (the RTT may have wrapped once
+    /// in between), returning the milliseconds, which wrap at `u32::MAX`
+    fn advance(&mut self, ticks: u32) -> u32 {
+        let elapsed = ticks.wrapping_sub(self.ticks) as u64 * 1000 + self.remainder as u64;
+        self.ticks = ticks;
+        self.ms = self.ms.wrapping_add((elapsed >> 10) as u32);
+        self.remainder = (elapsed & 1023) as u32;
+        self.ms
+    }
+}
+
+impl TimeSource for ArmTimeSource {
+    fn now_ms(&self) -> u32 {
+        // The count is shared, so read the RTT and count in one go
+        critical_section::with(|cs| {
+            let rtt = unsafe { &*RTT::ptr() };
+            // The value register is clocked asynchronously, so read it until
+            // two consecutive reads agree
+            let mut ticks = rtt.vr().read().bits();
+            loop {
+                let again = rtt.vr().read().bits();
+                if again == ticks {
+                    break;
+                }
+                ticks = again;
+            }
+            RTT_CLOCK.borrow(cs).borrow_mut().advance(ticks)
+        })
+    }
+}
+
+/// Random numbers from the true random number generator.
+pub struct ArmRandom;
+
+impl RandomIO for ArmRandom {
+    fn next_u32(&mut self) -> u32 {
+        Peripherals::with(|p| {
+            p.rng.wait();
+            p.rng.take_result()
+        })
+    }
+}
@@ -79,11 +79,18 @@ impl Buzzer {
     self.beep_cycles(540);
   }
 
-  /// Alarm sound for low time warning
-  pub fn low_time_warning(&self) {
-    self.beep_cycles(80);
-    Peripherals::with(|p| p.delay.delay_ms(100));
+  /// Alarm sound for low time warning, one more beep for each more urgent level
+  pub fn low_time_warning(&self, level: u8) {
     self.beep_cycles(80);
+    for _ in 0..=level {
+      Peripherals::with(|p| p.delay.delay_ms(100));
+      self.beep_cycles(80);
+    }
+  }
+
+  /// Short tick for each second of the final countdown
+  pub fn countdown_beep(&self) {
+    self.beep_cycles(40);
   }
 
   /// Continuous alarm for time expired
@@ -44,6 +44,18 @@ pub const STARTING_LAYOUT: [(u8, Piece); 32] = [
   (63, Piece::BlackRook),
 ];
 
+/// Starting layout with the given white back rank, mirrored for black
+///
+/// Used for Chess960, where the back rank pieces are shuffled.
+pub fn layout_with_back_rank(back_rank: &[Piece; 8]) -> [(u8, Piece); 32] {
+  let mut layout = STARTING_LAYOUT;
+  for (file, &piece) in back_rank.iter().enumerate() {
+    layout[file] = (file as u8, piece);
+    layout[24 + file] = (56 + file as u8, piece.opposite_color());
+  }
+  layout
+}
+
 /// Chess piece types
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Piece {
@@ -75,6 +87,24 @@ impl Piece {
     )
   }
 
+  /// Same piece type in the other color
+  pub fn opposite_color(self) -> Piece {
+    match self {
+      Piece::WhitePawn => Piece::BlackPawn,
+      Piece::WhiteKnight => Piece::BlackKnight,
+      Piece::WhiteBishop => Piece::BlackBishop,
+      Piece::WhiteRook => Piece::BlackRook,
+      Piece::WhiteQueen => Piece::BlackQueen,
+      Piece::WhiteKing => Piece::BlackKing,
+      Piece::BlackPawn => Piece::WhitePawn,
+      Piece::BlackKnight => Piece::WhiteKnight,
+      Piece::BlackBishop => Piece::WhiteBishop,
+      Piece::BlackRook => Piece::WhiteRook,
+      Piece::BlackQueen => Piece::WhiteQueen,
+      Piece::BlackKing => Piece::WhiteKing,
+    }
+  }
+
   /// Get the piece character for display (uppercase = white, lowercase = black)
   pub fn to_char(self) -> char {
     match self {
@@ -174,10 +204,17 @@ impl CalibrationData {
   ///
   /// Returns the number of pieces successfully calibrated.
   pub fn calibrate_from_starting_position(&mut self, reading: &RfidReading) -> usize {
+    self.calibrate_from_layout(reading, &STARTING_LAYOUT)
+  }
+
+  /// Calibrate from an RFID reading assuming pieces are set up as in `layout`
+  ///
+  /// Returns the number of pieces successfully calibrated.
+  pub fn calibrate_from_layout(&mut self, reading: &RfidReading, layout: &[(u8, Piece); 32]) -> usize {
     self.clear();
     let mut calibrated = 0;
 
-    for &(square, piece) in &STARTING_LAYOUT {
+    for &(square, piece) in layout {
       let chip_id = reading.chip_id(square);
 
       // Skip empty squares (no RFID detected)
@@ -222,6 +259,26 @@ mod tests {
     assert!(!Piece::BlackKing.is_white());
   }
 
+  #[test]
+  fn test_layout_with_back_rank() {
+    let back_rank = [
+      Piece::WhiteBishop,
+      Piece::WhiteBishop,
+      Piece::WhiteQueen,
+      Piece::WhiteKnight,
+      Piece::WhiteKnight,
+      Piece::WhiteRook,
+      Piece::WhiteKing,
+      Piece::WhiteRook,
+    ];
+    let layout = layout_with_back_rank(&back_rank);
+
+    assert_eq!(layout[2], (2, Piece::WhiteQueen));
+    assert_eq!(layout[30], (62, Piece::BlackKing));
+    // Pawns are unchanged
+    assert_eq!(layout[8..24], STARTING_LAYOUT[8..24]);
+  }
+
   #[test]
   fn test_piece_to_char() {
     assert_eq!(Piece::WhiteKing.to_char(), 'K');
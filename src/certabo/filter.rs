@@ -0,0 +1,209 @@
+//! RFID reading stabilization
+//!
+//! Sensors flicker and pieces slid across the board pass over other squares,
+//! so a single reading is not trusted. Each square keeps its stable chip ID
+//! until a different one has been read in enough consecutive readings, or
+//! for long enough. A reading that goes back to the stable chip ID in the
+//! meantime cancels the change.
+
+use crate::certabo::protocol::{RfidReading, NUM_SQUARES, RFID_BYTES};
+
+/// How much agreement a square needs before it changes state
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FilterConfig {
+  /// Consecutive readings that must agree on the new chip ID (1 = no filtering)
+  pub confirm_readings: u8,
+  /// Time after which an unchanged new chip ID is accepted, in milliseconds
+  pub settle_ms: u32,
+}
+
+impl FilterConfig {
+  /// Act on every reading as it arrives
+  pub const OFF: Self = Self {
+    confirm_readings: 1,
+    settle_ms: 0,
+  };
+
+  /// Three agreeing readings, or 200 ms
+  pub const STANDARD: Self = Self {
+    confirm_readings: 3,
+    settle_ms: 200,
+  };
+}
+
+impl Default for FilterConfig {
+  fn default() -> Self {
+    Self::STANDARD
+  }
+}
+
+/// Change seen on one square that is not yet accepted
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Candidate {
+  chip_id: [u8; RFID_BYTES],
+  count: u8,
+  first_seen_ms: u32,
+}
+
+/// Stabilizes board readings square by square
+#[derive(Clone, Copy, Debug)]
+pub struct ReadingFilter {
+  config: FilterConfig,
+  /// Last accepted reading, `None` until the first reading
+  stable: Option<RfidReading>,
+  candidates: [Option<Candidate>; NUM_SQUARES],
+}
+
+impl ReadingFilter {
+  pub fn new(config: FilterConfig) -> Self {
+    Self {
+      config,
+      stable: None,
+      candidates: [None; NUM_SQUARES],
+    }
+  }
+
+  pub fn config(&self) -> FilterConfig {
+    self.config
+  }
+
+  /// Change the configuration, dropping changes that are not yet accepted
+  pub fn set_config(&mut self, config: FilterConfig) {
+    self.config = config;
+    self.candidates = [None; NUM_SQUARES];
+  }
+
+  /// Feed a reading received at `now_ms` and get the stabilized reading
+  ///
+  /// The first reading is accepted as it is, since there is nothing to
+  /// compare it with.
+  pub fn push(&mut self, reading: &RfidReading, now_ms: u32) -> RfidReading {
+    let Some(mut stable) = self.stable else {
+      self.stable = Some(*reading);
+      return *reading;
+    };
+
+    for square in 0..NUM_SQUARES {
+      let chip_id = reading.chip_ids[square];
+      if chip_id == stable.chip_ids[square] {
+        self.candidates[square] = None;
+        continue;
+      }
+
+      let candidate = match self.candidates[square] {
+        Some(candidate) if candidate.chip_id == chip_id => Candidate {
+          count: candidate.count.saturating_add(1),
+          ..candidate
+        },
+        _ => Candidate {
+          chip_id,
+          count: 1,
+          first_seen_ms: now_ms,
+        },
+      };
+
+      let settled =
+        candidate.count > 1 && now_ms.wrapping_sub(candidate.first_seen_ms) >= self.config.settle_ms;
+      if candidate.count >= self.config.confirm_readings || settled {
+        stable.chip_ids[square] = chip_id;
+        self.candidates[square] = None;
+      } else {
+        self.candidates[square] = Some(candidate);
+      }
+    }
+
+    self.stable = Some(stable);
+    stable
+  }
+}
+
+impl Default for ReadingFilter {
+  fn default() -> Self {
+    Self::new(FilterConfig::default())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn reading_with(square: usize, chip_id: [u8; RFID_BYTES]) -> RfidReading {
+    let mut reading = RfidReading::new();
+    reading.chip_ids[square] = chip_id;
+    reading
+  }
+
+  #[test]
+  fn test_first_reading_accepted() {
+    let mut filter = ReadingFilter::default();
+    let reading = reading_with(12, [1, 2, 3, 4, 5]);
+
+    assert_eq!(filter.push(&reading, 0), reading);
+  }
+
+  #[test]
+  fn test_change_needs_agreeing_readings() {
+    let mut filter = ReadingFilter::default();
+    let empty = RfidReading::new();
+    let placed = reading_with(28, [1, 2, 3, 4, 5]);
+    filter.push(&empty, 0);
+
+    assert_eq!(filter.push(&placed, 10), empty);
+    assert_eq!(filter.push(&placed, 20), empty);
+    assert_eq!(filter.push(&placed, 30), placed);
+  }
+
+  #[test]
+  fn test_flicker_back_cancels_change() {
+    let mut filter = ReadingFilter::default();
+    let occupied = reading_with(12, [1, 2, 3, 4, 5]);
+    let empty = RfidReading::new();
+    filter.push(&occupied, 0);
+
+    filter.push(&empty, 10);
+    filter.push(&empty, 20);
+    filter.push(&occupied, 30);
+    filter.push(&empty, 40);
+    assert_eq!(filter.push(&empty, 50), occupied);
+    assert_eq!(filter.push(&empty, 60), empty);
+  }
+
+  #[test]
+  fn test_change_accepted_after_settle_time() {
+    let mut filter = ReadingFilter::new(FilterConfig {
+      confirm_readings: 10,
+      settle_ms: 200,
+    });
+    let empty = RfidReading::new();
+    let placed = reading_with(28, [1, 2, 3, 4, 5]);
+    filter.push(&empty, 0);
+
+    // A single reading is never enough, however late it arrives
+    assert_eq!(filter.push(&placed, 1000), empty);
+    assert_eq!(filter.push(&placed, 1100), empty);
+    assert_eq!(filter.push(&placed, 1200), placed);
+  }
+
+  #[test]
+  fn test_squares_filtered_independently() {
+    let mut filter = ReadingFilter::default();
+    let mut both = reading_with(12, [1, 2, 3, 4, 5]);
+    both.chip_ids[28] = [6, 7, 8, 9, 10];
+    let first = reading_with(12, [1, 2, 3, 4, 5]);
+    filter.push(&RfidReading::new(), 0);
+
+    filter.push(&first, 10);
+    filter.push(&first, 20);
+    // Square 12 is confirmed, square 28 has only just appeared
+    assert_eq!(filter.push(&both, 30), first);
+  }
+
+  #[test]
+  fn test_off_passes_readings_through() {
+    let mut filter = ReadingFilter::new(FilterConfig::OFF);
+    let placed = reading_with(28, [1, 2, 3, 4, 5]);
+    filter.push(&RfidReading::new(), 0);
+
+    assert_eq!(filter.push(&placed, 0), placed);
+  }
+}
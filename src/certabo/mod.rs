@@ -6,6 +6,7 @@
 pub mod board;
 pub mod buffer;
 pub mod calibration;
+pub mod filter;
 pub mod leds;
 pub mod protocol;
 
@@ -65,6 +65,14 @@ impl SimulatedBoard {
         self.encode_reading(&reading);
     }
 
+    /// Generate a reading in which `square` momentarily reads empty, as a
+    /// flickering sensor does
+    pub fn generate_flicker(&mut self, square: u8) {
+        let mut reading = self.virtual_board.to_rfid_reading();
+        reading.chip_ids[square as usize] = [0; RFID_BYTES];
+        self.encode_reading(&reading);
+    }
+
     pub fn inject_reading(&mut self, reading: &RfidReading) {
         self.encode_reading(reading);
     }
@@ -280,6 +288,20 @@ mod tests {
         assert_eq!(parsed.chip_id(0), [0xDE, 0xAD, 0xBE, 0xEF, 0x00]);
     }
 
+    #[test]
+    fn test_generate_flicker() {
+        let mut board = SimulatedBoard::with_starting_position();
+        board.generate_flicker(12);
+
+        let mut buf = [0u8; 2048];
+        let len = board.read_line(&mut buf).unwrap();
+        let reading = RfidReading::parse(&buf[1..len - 1]).unwrap();
+
+        assert!(!reading.has_piece(12));
+        assert!(reading.has_piece(11));
+        assert!(board.virtual_board().get(12).is_some());
+    }
+
     #[test]
     fn test_calibration_compatible() {
         use crate::certabo::calibration::CalibrationData;
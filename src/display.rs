@@ -42,7 +42,7 @@ impl Write for Display {
 impl Display {
   /// Display game status with turn and last move
   /// Line 1: "White to move" or "Black to move"
-  /// Line 2: Last move (e.g., "Last: e2-e4")
+  /// Line 2: Last move (e.g., "Last: e4")
   pub fn show_turn(&self, is_white_turn: bool) {
     JHD1802::with(|jhd1802| {
       jhd1802.clear();
@@ -55,36 +55,15 @@ impl Display {
     });
   }
 
-  /// Display last move on line 2
-  /// Format: "Last: e2-e4" or "Last: Nf3xg5"
-  pub fn show_last_move(&self, from_sq: u8, to_sq: u8) {
+  /// Display last move on line 2 in SAN
+  /// Format: "Last: e4" or "Last: Nbxd7+"
+  pub fn show_last_move(&self, san: &str) {
     JHD1802::with(|jhd1802| {
       jhd1802.set_cursor(0, 1);
       jhd1802.send_str("Last: ");
-      jhd1802.send_str(Self::square_name(from_sq));
-      jhd1802.send_str("-");
-      jhd1802.send_str(Self::square_name(to_sq));
-      jhd1802.send_str("        "); // Clear rest of line
-    });
-  }
-
-  /// Convert square index (0-63) to algebraic notation
-  fn square_name(sq: u8) -> &'static str {
-    const SQUARES: [&str; 64] = [
-      "a1", "b1", "c1", "d1", "e1", "f1", "g1", "h1",
-      "a2", "b2", "c2", "d2", "e2", "f2", "g2", "h2",
-      "a3", "b3", "c3", "d3", "e3", "f3", "g3", "h3",
-      "a4", "b4", "c4", "d4", "e4", "f4", "g4", "h4",
-      "a5", "b5", "c5", "d5", "e5", "f5", "g5", "h5",
-      "a6", "b6", "c6", "d6", "e6", "f6", "g6", "h6",
-      "a7", "b7", "c7", "d7", "e7", "f7", "g7", "h7",
-      "a8", "b8", "c8", "d8", "e8", "f8", "g8", "h8",
-    ];
-    if sq < 64 {
-      SQUARES[sq as usize]
-    } else {
-      "??"
-    }
+      jhd1802.send_str(san);
+      jhd1802.send_str("          "); // Clear rest of line
+    });
   }
 
   /// Display calibration prompt
@@ -170,6 +149,17 @@ impl Display {
     });
   }
 
+  /// Display a claimable draw on line 2 (blue button claims it)
+  /// Format: "Draw? 3-fold"
+  pub fn show_draw_claimable(&self, reason: &str) {
+    JHD1802::with(|jhd1802| {
+      jhd1802.set_cursor(0, 1);
+      jhd1802.send_str("Draw? ");
+      jhd1802.send_str(reason);
+      jhd1802.send_str("          "); // Clear rest of line
+    });
+  }
+
   /// Display invalid move message
   pub fn show_invalid_move(&self) {
     JHD1802::with(|jhd1802| {
@@ -196,6 +186,46 @@ impl Display {
     });
   }
 
+  /// Ask the player to move to agree to a takeback with their button
+  pub fn show_takeback_request(&self, opponent_is_white: bool) {
+    JHD1802::with(|jhd1802| {
+      jhd1802.set_cursor(0, 0);
+      jhd1802.send_str("Takeback?       ");
+      jhd1802.set_cursor(0, 1);
+      jhd1802.send_str(if opponent_is_white {
+        "WHITE: press btn"
+      } else {
+        "BLACK: press btn"
+      });
+    });
+  }
+
+  pub fn show_resign_prompt(&self, is_white: bool) {
+    JHD1802::with(|jhd1802| {
+      jhd1802.set_cursor(0, 0);
+      jhd1802.send_str(if is_white {
+        "WHITE resigns?  "
+      } else {
+        "BLACK resigns?  "
+      });
+      jhd1802.set_cursor(0, 1);
+      jhd1802.send_str("Press to confirm");
+    });
+  }
+
+  pub fn show_draw_offer(&self, opponent_is_white: bool) {
+    JHD1802::with(|jhd1802| {
+      jhd1802.set_cursor(0, 0);
+      jhd1802.send_str("Draw?           ");
+      jhd1802.set_cursor(0, 1);
+      jhd1802.send_str(if opponent_is_white {
+        "WHITE: press btn"
+      } else {
+        "BLACK: press btn"
+      });
+    });
+  }
+
   pub fn show_takeback_capture(&self) {
     JHD1802::with(|jhd1802| {
       jhd1802.clear();
@@ -235,4 +265,164 @@ impl Display {
       jhd1802.send_str("Place pawn back");
     });
   }
+
+  /// Display the next move of a replayed game
+  /// Format: "Replay" / "12. Nf3" or "12... Nc6"
+  pub fn show_replay_move(&self, move_number: u16, is_white: bool, san: &str) {
+    let mut number = [0u8; 5];
+    let number = format_number(move_number, &mut number);
+
+    JHD1802::with(|jhd1802| {
+      jhd1802.clear();
+      jhd1802.set_cursor(0, 0);
+      jhd1802.send_str("Replay");
+      jhd1802.set_cursor(0, 1);
+      jhd1802.send_str(number);
+      jhd1802.send_str(if is_white { ". " } else { "... " });
+      jhd1802.send_str(san);
+    });
+  }
+
+  /// Display end of a replayed game
+  pub fn show_replay_complete(&self) {
+    JHD1802::with(|jhd1802| {
+      jhd1802.clear();
+      jhd1802.set_cursor(0, 0);
+      jhd1802.send_str("Replay finished");
+      jhd1802.set_cursor(0, 1);
+      jhd1802.send_str("Press to exit");
+    });
+  }
+
+  /// Display the Chess960 start position to set up
+  pub fn show_chess960_setup(&self, index: u16, back_rank: &str) {
+    let mut number = [0u8; 5];
+    let number = format_number(index, &mut number);
+
+    JHD1802::with(|jhd1802| {
+      jhd1802.clear();
+      jhd1802.set_cursor(0, 0);
+      jhd1802.send_str("Chess960 #");
+      jhd1802.send_str(number);
+      jhd1802.set_cursor(0, 1);
+      jhd1802.send_str(back_rank);
+    });
+  }
+
+  /// Display the setup prompt for a game against the computer
+  /// Format: "vs Computer L3" / "Set up pieces"
+  pub fn show_vs_computer_setup(&self, level: u8) {
+    let mut number = [0u8; 5];
+    let number = format_number(level as u16, &mut number);
+
+    JHD1802::with(|jhd1802| {
+      jhd1802.clear();
+      jhd1802.set_cursor(0, 0);
+      jhd1802.send_str("vs Computer L");
+      jhd1802.send_str(number);
+      jhd1802.set_cursor(0, 1);
+      jhd1802.send_str("Set up pieces");
+    });
+  }
+
+  /// Display the computer's move for the player to make on the board
+  /// Format: "Computer plays" / "Nf3"
+  pub fn show_computer_move(&self, san: &str) {
+    JHD1802::with(|jhd1802| {
+      jhd1802.set_cursor(0, 0);
+      jhd1802.send_str("Computer plays  ");
+      jhd1802.set_cursor(0, 1);
+      jhd1802.send_str(san);
+      jhd1802.send_str("          "); // Clear rest of line
+    });
+  }
+
+  /// Display the start of a new time control stage on line 2
+  /// Format: "White: stage 2"
+  pub fn show_time_stage(&self, is_white: bool, stage: u8) {
+    let mut number = [0u8; 5];
+    let number = format_number(stage as u16, &mut number);
+
+    JHD1802::with(|jhd1802| {
+      jhd1802.set_cursor(0, 1);
+      jhd1802.send_str(if is_white { "White" } else { "Black" });
+      jhd1802.send_str(": stage ");
+      jhd1802.send_str(number);
+      jhd1802.send_str("          "); // Clear rest of line
+    });
+  }
+
+  /// Display a time control menu entry, e.g. "15+10"
+  pub fn show_time_control_menu(&self, minutes: u8, increment: u8, custom: bool) {
+    JHD1802::with(|jhd1802| {
+      jhd1802.clear();
+      jhd1802.set_cursor(0, 0);
+      jhd1802.send_str("Time control");
+      jhd1802.set_cursor(0, 1);
+      if custom {
+        jhd1802.send_str("Custom ");
+      }
+      send_time_control(jhd1802, minutes, increment);
+    });
+  }
+
+  /// Display the custom time control being set up
+  pub fn show_custom_time_control(&self, minutes: u8, increment: u8, editing_increment: bool) {
+    JHD1802::with(|jhd1802| {
+      jhd1802.clear();
+      jhd1802.set_cursor(0, 0);
+      jhd1802.send_str(if editing_increment { "Set increment" } else { "Set minutes" });
+      jhd1802.set_cursor(0, 1);
+      send_time_control(jhd1802, minutes, increment);
+    });
+  }
+
+  /// Ask for the pieces to be put back as in the game
+  /// Format: "Fix board" / "3 squares differ"
+  pub fn show_board_mismatch(&self, squares: u8) {
+    JHD1802::with(|jhd1802| {
+      jhd1802.set_cursor(0, 0);
+      jhd1802.send_str("Fix board       ");
+      jhd1802.set_cursor(0, 1);
+      let mut number = [0u8; 5];
+      jhd1802.send_str(format_number(squares as u16, &mut number));
+      jhd1802.send_str(if squares == 1 { " square differs " } else { " squares differ " });
+    });
+  }
+
+  /// Say what is wrong with the start position
+  /// Format: "Check setup" / "Swap d1/e1"
+  pub fn show_setup_problem(&self, problem: &str) {
+    JHD1802::with(|jhd1802| {
+      jhd1802.set_cursor(0, 0);
+      jhd1802.send_str("Check setup     ");
+      jhd1802.set_cursor(0, 1);
+      jhd1802.send_str(problem);
+      jhd1802.send_str(&"                "[problem.len().min(16)..]);
+    });
+  }
+}
+
+/// Send a time control as "minutes+increment"
+fn send_time_control(jhd1802: &JHD1802, minutes: u8, increment: u8) {
+  let mut number = [0u8; 5];
+  jhd1802.send_str(format_number(minutes as u16, &mut number));
+  jhd1802.send_str("+");
+  jhd1802.send_str(format_number(increment as u16, &mut number));
+}
+
+/// Format a number as decimal text into `buf`
+fn format_number(n: u16, buf: &mut [u8; 5]) -> &str {
+  let mut len = 0;
+  let mut n = n;
+  loop {
+    buf[len] = b'0' + (n % 10) as u8;
+    len += 1;
+    n /= 10;
+    if n == 0 {
+      break;
+    }
+  }
+  buf[..len].reverse();
+  core::str::from_utf8(&buf[..len]).unwrap_or("?")
 }
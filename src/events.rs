@@ -1,20 +1,66 @@
-//! Atomic event flags for ISR → main loop communication.
+//! ISR → main loop communication.
 //!
-//! Interrupt handlers set these flags; the main loop consumes them.
+//! Interrupt handlers set flags and queue button edges; the main loop
+//! consumes them.
 
-use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use crate::app::buttons::ButtonEdge;
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicBool, Ordering};
+use critical_section::Mutex;
 
-/// Blue button (calibrate) was pressed.
-pub static BLUE_BUTTON_PRESSED: AtomicBool = AtomicBool::new(false);
+/// Timer tick occurred (about 100ms interval, paces display updates).
+pub static TIMER_TICK: AtomicBool = AtomicBool::new(false);
 
-/// White button (pause/resume) was pressed.
-pub static WHITE_BUTTON_PRESSED: AtomicBool = AtomicBool::new(false);
+/// Button edges not yet taken by the main loop.
+static BUTTON_EDGES: Mutex<RefCell<ButtonQueue>> = Mutex::new(RefCell::new(ButtonQueue::new()));
 
-/// Timer tick occurred (100ms interval).
-pub static TIMER_TICK: AtomicBool = AtomicBool::new(false);
+/// Capacity of the button edge queue.
+const BUTTON_QUEUE_LEN: usize = 16;
+
+/// Fixed-size FIFO of button edges; when full, the newest edge is dropped.
+struct ButtonQueue {
+    edges: [Option<ButtonEdge>; BUTTON_QUEUE_LEN],
+    head: usize,
+    len: usize,
+}
+
+impl ButtonQueue {
+    const fn new() -> Self {
+        Self {
+            edges: [None; BUTTON_QUEUE_LEN],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, edge: ButtonEdge) {
+        if self.len == BUTTON_QUEUE_LEN {
+            return;
+        }
+        self.edges[(self.head + self.len) % BUTTON_QUEUE_LEN] = Some(edge);
+        self.len += 1;
+    }
 
-/// Monotonic millisecond counter, incremented by timer ISR.
-pub static MILLIS: AtomicU32 = AtomicU32::new(0);
+    fn pop(&mut self) -> Option<ButtonEdge> {
+        if self.len == 0 {
+            return None;
+        }
+        let edge = self.edges[self.head].take();
+        self.head = (self.head + 1) % BUTTON_QUEUE_LEN;
+        self.len -= 1;
+        edge
+    }
+}
+
+/// Queue a button press or release (called from the button ISRs).
+pub fn push_button_edge(edge: ButtonEdge) {
+    critical_section::with(|cs| BUTTON_EDGES.borrow(cs).borrow_mut().push(edge));
+}
+
+/// Take the oldest queued button edge.
+pub fn pop_button_edge() -> Option<ButtonEdge> {
+    critical_section::with(|cs| BUTTON_EDGES.borrow(cs).borrow_mut().pop())
+}
 
 /// Consume a flag atomically, returning true if it was set.
 #[inline]
@@ -1,7 +1,9 @@
 //! Chess board representation and move validation
 //!
 //! Implements full chess rules including castling, en passant, promotion,
-//! and draw conditions (50-move rule, insufficient material).
+//! and draw conditions (50/75-move rules, repetition, insufficient material).
+
+use crate::game::zobrist;
 
 /// Chess piece types
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -14,6 +16,33 @@ pub enum PieceType {
   King,
 }
 
+impl PieceType {
+  /// Get the uppercase letter for this piece type (as used in FEN and SAN)
+  pub fn to_char(self) -> char {
+    match self {
+      PieceType::Pawn => 'P',
+      PieceType::Knight => 'N',
+      PieceType::Bishop => 'B',
+      PieceType::Rook => 'R',
+      PieceType::Queen => 'Q',
+      PieceType::King => 'K',
+    }
+  }
+
+  /// Parse an uppercase piece letter
+  pub fn from_char(c: char) -> Option<Self> {
+    match c {
+      'P' => Some(PieceType::Pawn),
+      'N' => Some(PieceType::Knight),
+      'B' => Some(PieceType::Bishop),
+      'R' => Some(PieceType::Rook),
+      'Q' => Some(PieceType::Queen),
+      'K' => Some(PieceType::King),
+      _ => None,
+    }
+  }
+}
+
 /// Castling rights for one side
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct CastlingRights {
@@ -39,6 +68,93 @@ impl CastlingRights {
   }
 }
 
+/// Start files of the king and the castling rooks (the same for both sides)
+///
+/// Standard chess uses e, a and h; Chess960 start positions shuffle them.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CastlingFiles {
+  pub king: u8,
+  pub queenside_rook: u8,
+  pub kingside_rook: u8,
+}
+
+impl CastlingFiles {
+  pub const STANDARD: Self = Self {
+    king: 4,
+    queenside_rook: 0,
+    kingside_rook: 7,
+  };
+
+  fn rook(self, kingside: bool) -> u8 {
+    if kingside { self.kingside_rook } else { self.queenside_rook }
+  }
+}
+
+/// Squares involved in a castling move
+///
+/// The king always lands on the g- or c-file and the rook next to it on the
+/// f- or d-file. In Chess960 either piece may already stand on its target.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CastlingMove {
+  pub kingside: bool,
+  pub king_from: u8,
+  pub king_to: u8,
+  pub rook_from: u8,
+  pub rook_to: u8,
+}
+
+/// Number of Chess960 start positions
+pub const CHESS960_POSITIONS: u16 = 960;
+
+/// Back rank of Chess960 start position `index` in standard (Scharnagl) numbering
+///
+/// Index 518 is the standard starting position. Returns `None` for indices
+/// of 960 and above.
+pub fn chess960_back_rank(index: u16) -> Option<[PieceType; 8]> {
+  if index >= CHESS960_POSITIONS {
+    return None;
+  }
+
+  // Placements of the two knights among the five squares left after the
+  // bishops and queen
+  const KNIGHTS: [(usize, usize); 10] = [
+    (0, 1), (0, 2), (0, 3), (0, 4), (1, 2), (1, 3), (1, 4), (2, 3), (2, 4), (3, 4),
+  ];
+
+  let mut rank: [Option<PieceType>; 8] = [None; 8];
+  let mut n = index as usize;
+
+  // Light-squared bishop on b/d/f/h, dark-squared bishop on a/c/e/g
+  rank[(n % 4) * 2 + 1] = Some(PieceType::Bishop);
+  n /= 4;
+  rank[(n % 4) * 2] = Some(PieceType::Bishop);
+  n /= 4;
+
+  let queen = n % 6;
+  n /= 6;
+  let (knight_a, knight_b) = KNIGHTS[n];
+
+  if let Some(square) = rank.iter_mut().filter(|sq| sq.is_none()).nth(queen) {
+    *square = Some(PieceType::Queen);
+  }
+  for (i, square) in rank.iter_mut().filter(|sq| sq.is_none()).enumerate() {
+    if i == knight_a || i == knight_b {
+      *square = Some(PieceType::Knight);
+    }
+  }
+
+  // Rook, king, rook on the remaining three squares
+  let mut remaining = [PieceType::Rook, PieceType::King, PieceType::Rook].into_iter();
+  let mut back_rank = [PieceType::Pawn; 8];
+  for (piece_type, square) in back_rank.iter_mut().zip(rank) {
+    *piece_type = match square {
+      Some(placed) => placed,
+      None => remaining.next()?,
+    };
+  }
+  Some(back_rank)
+}
+
 /// Piece color
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum PieceColor {
@@ -66,6 +182,22 @@ impl Piece {
   pub const fn new(piece_type: PieceType, color: PieceColor) -> Self {
     Self { piece_type, color }
   }
+
+  /// Get the FEN character for this piece (uppercase = white, lowercase = black)
+  pub fn to_char(self) -> char {
+    let c = self.piece_type.to_char();
+    match self.color {
+      PieceColor::White => c,
+      PieceColor::Black => c.to_ascii_lowercase(),
+    }
+  }
+
+  /// Parse a FEN piece character (uppercase = white, lowercase = black)
+  pub fn from_char(c: char) -> Option<Self> {
+    let piece_type = PieceType::from_char(c.to_ascii_uppercase())?;
+    let color = if c.is_ascii_uppercase() { PieceColor::White } else { PieceColor::Black };
+    Some(Self::new(piece_type, color))
+  }
 }
 
 /// Maximum destinations for any piece
@@ -135,6 +267,134 @@ impl Iterator for DestinationsIter {
   }
 }
 
+/// Maximum number of legal moves in any position (the known maximum is 218)
+pub const MAX_MOVES: usize = 256;
+
+/// Move classification flags
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MoveFlags(u8);
+
+impl MoveFlags {
+  pub const NONE: Self = Self(0);
+  /// Captures a piece (including en passant)
+  pub const CAPTURE: Self = Self(1 << 0);
+  /// King moves two squares and the rook jumps over it
+  pub const CASTLE: Self = Self(1 << 1);
+  /// Pawn captures a pawn that just made a double push
+  pub const EN_PASSANT: Self = Self(1 << 2);
+
+  pub const fn contains(self, other: Self) -> bool {
+    self.0 & other.0 == other.0
+  }
+}
+
+impl core::ops::BitOr for MoveFlags {
+  type Output = Self;
+
+  fn bitor(self, rhs: Self) -> Self {
+    Self(self.0 | rhs.0)
+  }
+}
+
+/// A fully specified move
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Move {
+  pub from: u8,
+  pub to: u8,
+  /// Piece a pawn promotes to (always set for promotions)
+  pub promotion: Option<PieceType>,
+  pub flags: MoveFlags,
+}
+
+impl Move {
+  pub const fn new(from: u8, to: u8) -> Self {
+    Self {
+      from,
+      to,
+      promotion: None,
+      flags: MoveFlags::NONE,
+    }
+  }
+
+  pub fn is_capture(&self) -> bool {
+    self.flags.contains(MoveFlags::CAPTURE)
+  }
+
+  pub fn is_castle(&self) -> bool {
+    self.flags.contains(MoveFlags::CASTLE)
+  }
+
+  pub fn is_en_passant(&self) -> bool {
+    self.flags.contains(MoveFlags::EN_PASSANT)
+  }
+}
+
+/// Fixed-capacity list of moves
+pub struct MoveList {
+  moves: [Move; MAX_MOVES],
+  count: usize,
+}
+
+impl MoveList {
+  pub const fn new() -> Self {
+    Self {
+      moves: [Move::new(0, 0); MAX_MOVES],
+      count: 0,
+    }
+  }
+
+  pub fn push(&mut self, mv: Move) {
+    if self.count < MAX_MOVES {
+      self.moves[self.count] = mv;
+      self.count += 1;
+    }
+  }
+
+  pub fn len(&self) -> usize {
+    self.count
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.count == 0
+  }
+
+  pub fn as_slice(&self) -> &[Move] {
+    &self.moves[..self.count]
+  }
+
+  /// Mutable view of the moves, e.g. for sorting by move ordering score
+  pub fn as_mut_slice(&mut self) -> &mut [Move] {
+    &mut self.moves[..self.count]
+  }
+
+  pub fn iter(&self) -> core::slice::Iter<'_, Move> {
+    self.as_slice().iter()
+  }
+
+  /// Find the move with the given squares and promotion piece
+  pub fn find(&self, from: u8, to: u8, promotion: Option<PieceType>) -> Option<Move> {
+    self
+      .iter()
+      .find(|m| m.from == from && m.to == to && m.promotion == promotion)
+      .copied()
+  }
+}
+
+impl Default for MoveList {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<'a> IntoIterator for &'a MoveList {
+  type Item = &'a Move;
+  type IntoIter = core::slice::Iter<'a, Move>;
+
+  fn into_iter(self) -> Self::IntoIter {
+    self.iter()
+  }
+}
+
 /// Game status from board perspective
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum BoardStatus {
@@ -144,15 +404,54 @@ pub enum BoardStatus {
   Checkmate,
   /// Stalemate - draw (no legal moves, not in check)
   Stalemate,
-  /// Draw by fifty-move rule
+  /// Fifty-move rule reached - draw may be claimed
   FiftyMoveRule,
+  /// Threefold repetition - draw may be claimed
+  ThreefoldRepetition,
+  /// Draw by fivefold repetition (automatic)
+  FivefoldRepetition,
+  /// Draw by seventy-five-move rule (automatic)
+  SeventyFiveMoveRule,
   /// Draw by insufficient material
   InsufficientMaterial,
 }
 
+/// FEN string for the standard starting position
+pub const STARTING_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/// Maximum length of a FEN string produced by `ChessBoard::to_fen`
+pub const MAX_FEN_LEN: usize = 92;
+
+/// Errors that can occur while parsing a FEN string
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FenError {
+  /// Piece placement field is malformed, or a side does not have exactly one king
+  InvalidPlacement,
+  /// Side to move is missing or not `w`/`b`
+  InvalidSideToMove,
+  /// Castling field is missing, malformed, or names a king/rook that is not on its home square
+  ///
+  /// Accepts `KQkq` letters and Shredder-FEN rook files (`HAha`).
+  InvalidCastling,
+  /// En passant field is missing, malformed, or on the wrong rank for the side to move
+  InvalidEnPassant,
+  /// Halfmove clock is not a number in range
+  InvalidHalfmoveClock,
+  /// Fullmove number is not a positive number in range
+  InvalidFullmoveNumber,
+  /// Extra fields after the fullmove number
+  TooManyFields,
+}
+
 /// Maximum number of moves that can be undone
 pub const MAX_UNDO_HISTORY: usize = 100;
 
+/// Number of previous position hashes kept for repetition detection
+///
+/// Covers the 75-move rule window; positions before the last pawn move
+/// or capture can never repeat.
+const POSITION_HISTORY_LEN: usize = 152;
+
 /// Information needed to undo a move
 #[derive(Clone, Copy, Debug)]
 pub struct UndoInfo {
@@ -171,33 +470,53 @@ pub struct UndoInfo {
   pub prev_black_castling: CastlingRights,
   /// Halfmove clock before the move
   pub prev_halfmove_clock: u8,
-  /// Was this a castling move (kingside = true, queenside = false)
-  pub castling: Option<bool>,
+  /// Zobrist hash before the move
+  pub prev_hash: u64,
+  /// King and rook squares if this was a castling move
+  pub castling: Option<CastlingMove>,
   /// Promotion piece (if pawn promoted)
   pub promotion: Option<PieceType>,
   /// Original piece type (pawn before promotion)
   pub original_piece: PieceType,
 }
 
-/// Chess board state
-#[derive(Clone)]
-pub struct ChessBoard {
+/// Position state: piece placement, side to move, castling rights,
+/// en passant square and move clocks
+///
+/// Small and `Copy`, so legality checks and searches can make and unmake
+/// moves on a scratch copy. Move history is kept by `ChessBoard`.
+#[derive(Clone, Copy)]
+pub struct Position {
   squares: [Option<Piece>; 64],
   side_to_move: PieceColor,
   /// Castling rights for white
   white_castling: CastlingRights,
   /// Castling rights for black
   black_castling: CastlingRights,
+  /// Start files of the king and castling rooks
+  castling_files: CastlingFiles,
   /// En passant target square (the square where the capturing pawn lands)
   en_passant: Option<u8>,
   /// Halfmove clock for 50-move rule (resets on pawn move or capture)
   halfmove_clock: u8,
   /// Full move number (increments after black's move)
   fullmove_number: u16,
-  /// Undo history for takebacks
+  /// Zobrist hash of the current position
+  hash: u64,
+}
+
+/// Chess board: the current position plus its move history
+#[derive(Clone)]
+pub struct ChessBoard {
+  position: Position,
+  /// Undo history for takebacks (ring buffer indexed by `position_count`)
   undo_history: [Option<UndoInfo>; MAX_UNDO_HISTORY],
-  /// Number of moves in undo history
+  /// Number of moves that can be undone
   undo_count: usize,
+  /// Hashes of previous positions (ring buffer indexed by `position_count`)
+  position_history: [u64; POSITION_HISTORY_LEN],
+  /// Number of moves made since the board was set up
+  position_count: usize,
 }
 
 impl Default for ChessBoard {
@@ -206,30 +525,233 @@ impl Default for ChessBoard {
   }
 }
 
-/// Square indices for castling
-const WHITE_KING_START: u8 = 4;   // e1
-const WHITE_ROOK_KINGSIDE: u8 = 7;  // h1
-const WHITE_ROOK_QUEENSIDE: u8 = 0; // a1
-const BLACK_KING_START: u8 = 60;  // e8
-const BLACK_ROOK_KINGSIDE: u8 = 63; // h8
-const BLACK_ROOK_QUEENSIDE: u8 = 56; // a8
-
 impl ChessBoard {
+  #[cfg(test)]
+  pub(crate) fn empty() -> Self {
+    Self::with_position(Position::empty())
+  }
+
+  /// Create a board starting from the given position, with no move history
+  pub fn with_position(position: Position) -> Self {
+    Self {
+      position,
+      undo_history: [None; MAX_UNDO_HISTORY],
+      undo_count: 0,
+      position_history: [0; POSITION_HISTORY_LEN],
+      position_count: 0,
+    }
+  }
+
+  /// Create board with starting position
+  pub fn starting_position() -> Self {
+    Self::with_position(Position::starting_position())
+  }
+
+  /// Create board with Chess960 start position `index` (see `chess960_back_rank`)
+  pub fn chess960(index: u16) -> Option<Self> {
+    Some(Self::with_position(Position::chess960(index)?))
+  }
+
+  /// Create board from a FEN string
+  ///
+  /// The halfmove clock and fullmove number fields are optional and
+  /// default to 0 and 1 when omitted.
+  pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+    Ok(Self::with_position(Position::from_fen(fen)?))
+  }
+
+  /// The current position
+  pub fn position(&self) -> &Position {
+    &self.position
+  }
+
+  /// Write the current position as FEN into `buf`, returning the written text
+  pub fn to_fen<'a>(&self, buf: &'a mut [u8; MAX_FEN_LEN]) -> &'a str {
+    self.position.to_fen(buf)
+  }
+
+  pub fn get(&self, sq: u8) -> Option<Piece> {
+    self.position.get(sq)
+  }
+
+  /// Side to move
+  pub fn side_to_move(&self) -> PieceColor {
+    self.position.side_to_move
+  }
+
+  /// Full move number (starts at 1, increments after black's move)
+  pub fn fullmove_number(&self) -> u16 {
+    self.position.fullmove_number
+  }
+
+  pub fn castling_rights(&self, color: PieceColor) -> CastlingRights {
+    self.position.castling_rights(color)
+  }
+
+  /// Castling squares if `from`-`to` is a castling move (see `Position::castling_move`)
+  pub fn castling_move(&self, from: u8, to: u8) -> Option<CastlingMove> {
+    self.position.castling_move(from, to)
+  }
+
+  /// Legal castling move whose king lands on `king_to` (see `Position::castling_to`)
+  pub fn castling_to(&self, king_to: u8) -> Option<Move> {
+    self.position.castling_to(king_to)
+  }
+
+  /// Zobrist hash of the current position
+  pub fn hash(&self) -> u64 {
+    self.position.hash
+  }
+
+  /// Get information about the last move (for takeback detection)
+  pub fn last_undo_info(&self) -> Option<&UndoInfo> {
+    if self.undo_count > 0 {
+      self.undo_history[(self.position_count - 1) % MAX_UNDO_HISTORY].as_ref()
+    } else {
+      None
+    }
+  }
+
+  /// Make a move with optional promotion piece (updates board state)
+  /// For pawn promotion, if `promotion` is None, auto-promotes to Queen.
+  pub fn make_move(&mut self, from: u8, to: u8) {
+    self.make_move_with_promotion(from, to, None);
+  }
+
+  /// Make a move with explicit promotion piece choice
+  ///
+  /// The oldest moves drop out of the undo history once it holds
+  /// `MAX_UNDO_HISTORY` moves.
+  pub fn make_move_with_promotion(&mut self, from: u8, to: u8, promotion: Option<PieceType>) {
+    let Some(undo) = self.position.make_move(from, to, promotion) else {
+      return;
+    };
+
+    self.undo_history[self.position_count % MAX_UNDO_HISTORY] = Some(undo);
+    self.undo_count = (self.undo_count + 1).min(MAX_UNDO_HISTORY);
+    // Remember the previous position for repetition detection
+    self.position_history[self.position_count % POSITION_HISTORY_LEN] = undo.prev_hash;
+    self.position_count += 1;
+  }
+
+  /// Undo the last move (takeback)
+  /// Returns true if a move was undone, false if no move to undo
+  pub fn undo_move(&mut self) -> bool {
+    if self.undo_count == 0 {
+      return false;
+    }
+
+    let Some(undo) = self.undo_history[(self.position_count - 1) % MAX_UNDO_HISTORY].take() else {
+      return false;
+    };
+    self.position.unmake_move(&undo);
+    self.undo_count -= 1;
+    self.position_count -= 1;
+    true
+  }
+
+  /// Number of times the current position has occurred (including now)
+  ///
+  /// Only positions since the last pawn move or capture are compared, as
+  /// earlier ones can never recur.
+  pub fn repetition_count(&self) -> u8 {
+    let lookback = (self.position.halfmove_clock as usize)
+      .min(self.position_count)
+      .min(POSITION_HISTORY_LEN);
+
+    let mut count = 1;
+    // Same side to move only: step back two plies at a time
+    let mut back = 2;
+    while back <= lookback {
+      let index = (self.position_count - back) % POSITION_HISTORY_LEN;
+      if self.position_history[index] == self.position.hash {
+        count += 1;
+      }
+      back += 2;
+    }
+    count
+  }
+
+  pub fn legal_destinations(&self, from: u8) -> Destinations {
+    self.position.legal_destinations(from)
+  }
+
+  pub fn is_in_check(&self, color: PieceColor) -> bool {
+    self.position.is_in_check(color)
+  }
+
+  pub fn is_legal(&self, from: u8, to: u8) -> bool {
+    self.position.is_legal(from, to)
+  }
+
+  /// Get all legal moves for the side to move
+  pub fn legal_moves(&self) -> MoveList {
+    self.position.legal_moves()
+  }
+
+  /// Check if the side to move has any legal moves
+  pub fn has_legal_moves(&self) -> bool {
+    self.position.has_legal_moves()
+  }
+
+  /// Get the current board status (checkmate, stalemate, automatic draws, or ongoing)
+  ///
+  /// Draws that must be claimed are reported by `claimable_draw` instead.
+  pub fn status(&self) -> BoardStatus {
+    match self.position.status() {
+      // A repeated position cannot be checkmate or stalemate (it had legal moves before)
+      BoardStatus::Ongoing if self.repetition_count() >= 5 => BoardStatus::FivefoldRepetition,
+      status => status,
+    }
+  }
+
+  /// Check if `color` could never checkmate, whatever moves either side plays
+  ///
+  /// A flag fall against such a side is a draw rather than a loss.
+  pub fn has_insufficient_material(&self, color: PieceColor) -> bool {
+    self.position.has_insufficient_material(color)
+  }
+
+  /// Get a draw the side to move may claim (threefold repetition or fifty-move rule)
+  pub fn claimable_draw(&self) -> Option<BoardStatus> {
+    if self.repetition_count() >= 3 {
+      Some(BoardStatus::ThreefoldRepetition)
+    } else if self.position.halfmove_clock >= 100 {
+      Some(BoardStatus::FiftyMoveRule)
+    } else {
+      None
+    }
+  }
+
+  /// Count leaf nodes of the legal move tree to `depth` plies
+  ///
+  /// Used to verify move generation against published node counts.
+  pub fn perft(&self, depth: u32) -> u64 {
+    self.position.perft(depth)
+  }
+
+  /// Perft split by root move (see `Position::divide`)
+  pub fn divide<F: FnMut(Move, u64)>(&self, depth: u32, report: F) -> u64 {
+    self.position.divide(depth, report)
+  }
+}
+
+impl Position {
   pub(crate) fn empty() -> Self {
     Self {
       squares: [None; 64],
       side_to_move: PieceColor::White,
       white_castling: CastlingRights::none(),
       black_castling: CastlingRights::none(),
+      castling_files: CastlingFiles::STANDARD,
       en_passant: None,
       halfmove_clock: 0,
       fullmove_number: 1,
-      undo_history: [None; MAX_UNDO_HISTORY],
-      undo_count: 0,
+      hash: 0,
     }
   }
 
-  /// Create board with starting position
+  /// Create the standard starting position
   pub fn starting_position() -> Self {
     let mut board = Self::empty();
 
@@ -263,13 +785,293 @@ impl ChessBoard {
     board.white_castling = CastlingRights::new();
     board.black_castling = CastlingRights::new();
 
+    board.hash = board.compute_hash();
     board
   }
 
+  /// Create Chess960 start position `index` (see `chess960_back_rank`)
+  pub fn chess960(index: u16) -> Option<Self> {
+    let back_rank = chess960_back_rank(index)?;
+    let mut board = Self::empty();
+
+    let mut rook_files = [0u8; 2];
+    let mut rooks = 0;
+    for (file, &piece_type) in back_rank.iter().enumerate() {
+      board.squares[file] = Some(Piece::new(piece_type, PieceColor::White));
+      board.squares[8 + file] = Some(Piece::new(PieceType::Pawn, PieceColor::White));
+      board.squares[48 + file] = Some(Piece::new(PieceType::Pawn, PieceColor::Black));
+      board.squares[56 + file] = Some(Piece::new(piece_type, PieceColor::Black));
+
+      match piece_type {
+        PieceType::King => board.castling_files.king = file as u8,
+        PieceType::Rook => {
+          rook_files[rooks] = file as u8;
+          rooks += 1;
+        }
+        _ => {}
+      }
+    }
+    board.castling_files.queenside_rook = rook_files[0];
+    board.castling_files.kingside_rook = rook_files[1];
+
+    board.white_castling = CastlingRights::new();
+    board.black_castling = CastlingRights::new();
+
+    board.hash = board.compute_hash();
+    Some(board)
+  }
+
+  /// Create a position from a FEN string
+  ///
+  /// The halfmove clock and fullmove number fields are optional and
+  /// default to 0 and 1 when omitted.
+  pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+    let mut board = Self::empty();
+    let mut fields = fen.split_ascii_whitespace();
+
+    board.parse_placement(fields.next().ok_or(FenError::InvalidPlacement)?)?;
+
+    board.side_to_move = match fields.next() {
+      Some("w") => PieceColor::White,
+      Some("b") => PieceColor::Black,
+      _ => return Err(FenError::InvalidSideToMove),
+    };
+
+    board.parse_castling(fields.next().ok_or(FenError::InvalidCastling)?)?;
+
+    board.en_passant = match fields.next() {
+      Some("-") => None,
+      Some(name) => {
+        let sq = parse_square(name).ok_or(FenError::InvalidEnPassant)?;
+        // The target square is behind a pawn that just made a double push
+        let expected_rank = match board.side_to_move {
+          PieceColor::White => 5,
+          PieceColor::Black => 2,
+        };
+        if sq / 8 != expected_rank {
+          return Err(FenError::InvalidEnPassant);
+        }
+        Some(sq)
+      }
+      None => return Err(FenError::InvalidEnPassant),
+    };
+
+    if let Some(halfmove) = fields.next() {
+      board.halfmove_clock = halfmove.parse().map_err(|_| FenError::InvalidHalfmoveClock)?;
+    }
+
+    if let Some(fullmove) = fields.next() {
+      board.fullmove_number = match fullmove.parse() {
+        Ok(n) if n >= 1 => n,
+        _ => return Err(FenError::InvalidFullmoveNumber),
+      };
+    }
+
+    if fields.next().is_some() {
+      return Err(FenError::TooManyFields);
+    }
+
+    board.hash = board.compute_hash();
+    Ok(board)
+  }
+
+  fn parse_placement(&mut self, placement: &str) -> Result<(), FenError> {
+    let mut rank = 7u8;
+    let mut file = 0u8;
+
+    for c in placement.chars() {
+      match c {
+        '/' => {
+          if file != 8 || rank == 0 {
+            return Err(FenError::InvalidPlacement);
+          }
+          rank -= 1;
+          file = 0;
+        }
+        '1'..='8' => {
+          file += c as u8 - b'0';
+          if file > 8 {
+            return Err(FenError::InvalidPlacement);
+          }
+        }
+        _ => {
+          let piece = Piece::from_char(c).ok_or(FenError::InvalidPlacement)?;
+          if file >= 8 {
+            return Err(FenError::InvalidPlacement);
+          }
+          self.squares[(rank * 8 + file) as usize] = Some(piece);
+          file += 1;
+        }
+      }
+    }
+
+    if rank != 0 || file != 8 {
+      return Err(FenError::InvalidPlacement);
+    }
+
+    // Move generation and check detection rely on exactly one king per side
+    for color in [PieceColor::White, PieceColor::Black] {
+      let kings = self
+        .squares
+        .iter()
+        .filter(|sq| **sq == Some(Piece::new(PieceType::King, color)))
+        .count();
+      if kings != 1 {
+        return Err(FenError::InvalidPlacement);
+      }
+    }
+
+    Ok(())
+  }
+
+  fn parse_castling(&mut self, castling: &str) -> Result<(), FenError> {
+    if castling == "-" {
+      return Ok(());
+    }
+
+    // Both sides must castle with the same files
+    let mut king_file: Option<u8> = None;
+    let mut rook_files: [Option<u8>; 2] = [None; 2];
+
+    for c in castling.chars() {
+      let color = if c.is_ascii_uppercase() { PieceColor::White } else { PieceColor::Black };
+      let rank = back_rank(color);
+      let rook = Some(Piece::new(PieceType::Rook, color));
+
+      let king_sq = self.find_king(color).ok_or(FenError::InvalidCastling)?;
+      if king_sq / 8 != rank {
+        return Err(FenError::InvalidCastling);
+      }
+      let king = king_sq % 8;
+
+      // K/Q name the outermost rook on that side of the king
+      let rook_file = match c.to_ascii_lowercase() {
+        'k' => (king + 1..8).rev().find(|&f| self.get(rank * 8 + f) == rook),
+        'q' => (0..king).find(|&f| self.get(rank * 8 + f) == rook),
+        f @ 'a'..='h' => Some(f as u8 - b'a').filter(|&f| self.get(rank * 8 + f) == rook),
+        _ => None,
+      }
+      .ok_or(FenError::InvalidCastling)?;
+      let kingside = rook_file > king;
+
+      if *king_file.get_or_insert(king) != king
+        || *rook_files[kingside as usize].get_or_insert(rook_file) != rook_file
+      {
+        return Err(FenError::InvalidCastling);
+      }
+
+      let rights = match color {
+        PieceColor::White => &mut self.white_castling,
+        PieceColor::Black => &mut self.black_castling,
+      };
+      let right = if kingside { &mut rights.kingside } else { &mut rights.queenside };
+      if *right {
+        return Err(FenError::InvalidCastling); // Duplicate letter
+      }
+      *right = true;
+    }
+
+    let standard = CastlingFiles::STANDARD;
+    self.castling_files = CastlingFiles {
+      king: king_file.unwrap_or(standard.king),
+      queenside_rook: rook_files[0].unwrap_or(standard.queenside_rook),
+      kingside_rook: rook_files[1].unwrap_or(standard.kingside_rook),
+    };
+
+    Ok(())
+  }
+
+  /// Write the position as a FEN string into `buf`
+  pub fn to_fen<'a>(&self, buf: &'a mut [u8; MAX_FEN_LEN]) -> &'a str {
+    let mut pos = 0;
+
+    for rank in (0..8u8).rev() {
+      let mut empty = 0u8;
+      for file in 0..8u8 {
+        match self.get(rank * 8 + file) {
+          Some(piece) => {
+            if empty > 0 {
+              push_byte(buf, &mut pos, b'0' + empty);
+              empty = 0;
+            }
+            push_byte(buf, &mut pos, piece.to_char() as u8);
+          }
+          None => empty += 1,
+        }
+      }
+      if empty > 0 {
+        push_byte(buf, &mut pos, b'0' + empty);
+      }
+      if rank > 0 {
+        push_byte(buf, &mut pos, b'/');
+      }
+    }
+
+    push_byte(buf, &mut pos, b' ');
+    push_byte(buf, &mut pos, match self.side_to_move {
+      PieceColor::White => b'w',
+      PieceColor::Black => b'b',
+    });
+
+    push_byte(buf, &mut pos, b' ');
+    let castling_start = pos;
+    // Chess960 rights are written as rook files (Shredder-FEN)
+    let (kingside, queenside) = if self.is_chess960() {
+      (b'A' + self.castling_files.kingside_rook, b'A' + self.castling_files.queenside_rook)
+    } else {
+      (b'K', b'Q')
+    };
+    for (enabled, c) in [
+      (self.white_castling.kingside, kingside),
+      (self.white_castling.queenside, queenside),
+      (self.black_castling.kingside, kingside.to_ascii_lowercase()),
+      (self.black_castling.queenside, queenside.to_ascii_lowercase()),
+    ] {
+      if enabled {
+        push_byte(buf, &mut pos, c);
+      }
+    }
+    if pos == castling_start {
+      push_byte(buf, &mut pos, b'-');
+    }
+
+    push_byte(buf, &mut pos, b' ');
+    match self.en_passant {
+      Some(sq) => {
+        for c in square_name(sq) {
+          push_byte(buf, &mut pos, c);
+        }
+      }
+      None => push_byte(buf, &mut pos, b'-'),
+    }
+
+    push_byte(buf, &mut pos, b' ');
+    push_number(buf, &mut pos, self.halfmove_clock as u16);
+    push_byte(buf, &mut pos, b' ');
+    push_number(buf, &mut pos, self.fullmove_number);
+
+    core::str::from_utf8(&buf[..pos]).unwrap_or("")
+  }
+
   pub fn get(&self, sq: u8) -> Option<Piece> {
     self.squares[sq as usize]
   }
 
+  /// Side to move
+  pub fn side_to_move(&self) -> PieceColor {
+    self.side_to_move
+  }
+
+  /// Full move number (starts at 1, increments after black's move)
+  pub fn fullmove_number(&self) -> u16 {
+    self.fullmove_number
+  }
+
+  /// Halfmove clock (half-moves since the last pawn move or capture)
+  pub fn halfmove_clock(&self) -> u8 {
+    self.halfmove_clock
+  }
+
   pub fn castling_rights(&self, color: PieceColor) -> CastlingRights {
     match color {
       PieceColor::White => self.white_castling,
@@ -277,40 +1079,113 @@ impl ChessBoard {
     }
   }
 
-  /// Get information about the last move (for takeback detection)
-  pub fn last_undo_info(&self) -> Option<&UndoInfo> {
-    if self.undo_count > 0 {
-      self.undo_history[self.undo_count - 1].as_ref()
-    } else {
-      None
+  /// Start files of the king and castling rooks
+  pub fn castling_files(&self) -> CastlingFiles {
+    self.castling_files
+  }
+
+  /// Whether castling uses Chess960 rules (king or rooks off their standard files)
+  ///
+  /// Chess960 castling moves are written as the king capturing its own
+  /// rook, since the king's target square may also be a normal king move.
+  pub fn is_chess960(&self) -> bool {
+    self.castling_files != CastlingFiles::STANDARD
+  }
+
+  /// Castling squares for `color` castling on the given side
+  fn castling_squares(&self, color: PieceColor, kingside: bool) -> CastlingMove {
+    let rank = back_rank(color) * 8;
+    CastlingMove {
+      kingside,
+      king_from: rank + self.castling_files.king,
+      king_to: rank + if kingside { 6 } else { 2 },
+      rook_from: rank + self.castling_files.rook(kingside),
+      rook_to: rank + if kingside { 5 } else { 3 },
     }
   }
 
-  /// Make a move with optional promotion piece (updates board state)
-  /// For pawn promotion, if `promotion` is None, auto-promotes to Queen.
-  pub fn make_move(&mut self, from: u8, to: u8) {
-    self.make_move_with_promotion(from, to, None);
+  /// Destination square used to encode a castling move
+  fn castling_target(&self, castle: &CastlingMove) -> u8 {
+    if self.is_chess960() { castle.rook_from } else { castle.king_to }
   }
 
-  /// Make a move with explicit promotion piece choice
-  pub fn make_move_with_promotion(&mut self, from: u8, to: u8, promotion: Option<PieceType>) {
-    let piece = match self.get(from) {
-      Some(p) => p,
-      None => return,
+  /// Castling squares if `from`-`to` is a castling move by the side to move
+  ///
+  /// Castling is encoded as the king moving two squares in standard chess,
+  /// and as the king moving onto its own rook in Chess960. Only checks the
+  /// encoding and castling rights, not whether castling is legal.
+  pub fn castling_move(&self, from: u8, to: u8) -> Option<CastlingMove> {
+    let color = self.side_to_move;
+    if self.get(from) != Some(Piece::new(PieceType::King, color)) {
+      return None;
+    }
+
+    let rights = self.castling_rights(color);
+    [(true, rights.kingside), (false, rights.queenside)]
+      .into_iter()
+      .filter(|&(_, allowed)| allowed)
+      .map(|(kingside, _)| self.castling_squares(color, kingside))
+      .find(|castle| castle.king_from == from && self.castling_target(castle) == to)
+  }
+
+  /// Legal castling move whose king lands on `king_to`, if any
+  ///
+  /// Lets a king placed on its castling square be recognized as castling
+  /// in Chess960, where the move is encoded by the rook square instead.
+  pub fn castling_to(&self, king_to: u8) -> Option<Move> {
+    let color = self.side_to_move;
+    let rights = self.castling_rights(color);
+    [(true, rights.kingside), (false, rights.queenside)]
+      .into_iter()
+      .filter(|&(_, allowed)| allowed)
+      .map(|(kingside, _)| self.castling_squares(color, kingside))
+      .filter(|castle| castle.king_to == king_to)
+      .map(|castle| (castle.king_from, self.castling_target(&castle)))
+      .find(|&(from, to)| self.is_legal(from, to))
+      .map(|(from, to)| Move { flags: MoveFlags::CASTLE, ..Move::new(from, to) })
+  }
+
+  /// Remove the castling right that uses the rook starting on `sq`
+  fn clear_castling_right(&mut self, color: PieceColor, sq: u8) {
+    let rank = back_rank(color);
+    if sq / 8 != rank {
+      return;
+    }
+    let rights = match color {
+      PieceColor::White => &mut self.white_castling,
+      PieceColor::Black => &mut self.black_castling,
     };
+    if sq % 8 == self.castling_files.kingside_rook {
+      rights.kingside = false;
+    }
+    if sq % 8 == self.castling_files.queenside_rook {
+      rights.queenside = false;
+    }
+  }
+
+  /// Make a move in place, returning the information needed to unmake it
+  ///
+  /// For pawn promotion, if `promotion` is None, auto-promotes to Queen.
+  /// Returns `None` (and leaves the position unchanged) if `from` is empty.
+  pub fn make_move(&mut self, from: u8, to: u8, promotion: Option<PieceType>) -> Option<UndoInfo> {
+    let piece = self.get(from)?;
 
     // Save state for undo
     let prev_en_passant = self.en_passant;
     let prev_white_castling = self.white_castling;
     let prev_black_castling = self.black_castling;
     let prev_halfmove_clock = self.halfmove_clock;
+    let prev_hash = self.hash;
+
+    // Remove side to move, castling and en passant keys; re-added after the move
+    let mut hash = self.hash ^ self.state_hash();
 
     let mut captured_square = to;
-    let mut castling_move: Option<bool> = None;
+    let castling = self.castling_move(from, to);
     let mut actual_promotion: Option<PieceType> = None;
 
-    // Determine if this is a capture
-    let mut captured = self.get(to);
+    // Determine if this is a capture (a Chess960 castling move lands on its own rook)
+    let mut captured = if castling.is_some() { None } else { self.get(to) };
 
     // Handle en passant capture
     if piece.piece_type == PieceType::Pawn && Some(to) == self.en_passant {
@@ -323,48 +1198,40 @@ impl ChessBoard {
       captured_square = captured_pawn_sq;
     }
 
-    // Handle castling
-    if piece.piece_type == PieceType::King {
-      let from_file = from % 8;
-      let to_file = to % 8;
-
-      // Kingside castling (king moves 2 squares right)
-      if from_file == 4 && to_file == 6 {
-        castling_move = Some(true);
-        // Move the rook
-        let rook_from = if piece.color == PieceColor::White { WHITE_ROOK_KINGSIDE } else { BLACK_ROOK_KINGSIDE };
-        let rook_to = to - 1; // f1 or f8
-        let rook = self.squares[rook_from as usize].take();
-        self.squares[rook_to as usize] = rook;
-      }
-      // Queenside castling (king moves 2 squares left)
-      else if from_file == 4 && to_file == 2 {
-        castling_move = Some(false);
-        // Move the rook
-        let rook_from = if piece.color == PieceColor::White { WHITE_ROOK_QUEENSIDE } else { BLACK_ROOK_QUEENSIDE };
-        let rook_to = to + 1; // d1 or d8
-        let rook = self.squares[rook_from as usize].take();
-        self.squares[rook_to as usize] = rook;
-      }
+    if let Some(captured) = captured {
+      hash ^= zobrist::piece(captured, captured_square);
     }
 
-    // Move the piece
-    let mut moving_piece = self.squares[from as usize].take().unwrap();
-
-    // Handle pawn promotion
-    if piece.piece_type == PieceType::Pawn {
-      let promo_rank = match piece.color {
-        PieceColor::White => 7,
-        PieceColor::Black => 0,
-      };
-      if to / 8 == promo_rank {
-        // Promote to the specified piece, or Queen by default
-        actual_promotion = Some(promotion.unwrap_or(PieceType::Queen));
-        moving_piece.piece_type = actual_promotion.unwrap();
+    if let Some(castle) = castling {
+      // Lift both pieces first: in Chess960 either may land on the other's square
+      let rook = self.squares[castle.rook_from as usize].take();
+      self.squares[castle.king_from as usize] = None;
+      if let Some(rook) = rook {
+        hash ^= zobrist::piece(rook, castle.rook_from) ^ zobrist::piece(rook, castle.rook_to);
+      }
+      hash ^= zobrist::piece(piece, castle.king_from) ^ zobrist::piece(piece, castle.king_to);
+      self.squares[castle.rook_to as usize] = rook;
+      self.squares[castle.king_to as usize] = Some(piece);
+    } else {
+      // Move the piece
+      let mut moving_piece = self.squares[from as usize].take().unwrap();
+
+      // Handle pawn promotion
+      if piece.piece_type == PieceType::Pawn {
+        let promo_rank = match piece.color {
+          PieceColor::White => 7,
+          PieceColor::Black => 0,
+        };
+        if to / 8 == promo_rank {
+          // Promote to the specified piece, or Queen by default
+          actual_promotion = Some(promotion.unwrap_or(PieceType::Queen));
+          moving_piece.piece_type = actual_promotion.unwrap();
+        }
       }
-    }
 
-    self.squares[to as usize] = Some(moving_piece);
+      hash ^= zobrist::piece(piece, from) ^ zobrist::piece(moving_piece, to);
+      self.squares[to as usize] = Some(moving_piece);
+    }
 
     // Update en passant square
     self.en_passant = None;
@@ -411,24 +1278,14 @@ impl ChessBoard {
 
     // If rook moves from starting square, lose that castling right
     if piece.piece_type == PieceType::Rook {
-      match (piece.color, from) {
-        (PieceColor::White, 0) => self.white_castling.queenside = false,
-        (PieceColor::White, 7) => self.white_castling.kingside = false,
-        (PieceColor::Black, 56) => self.black_castling.queenside = false,
-        (PieceColor::Black, 63) => self.black_castling.kingside = false,
-        _ => {}
-      }
+      self.clear_castling_right(piece.color, from);
     }
 
     // If rook is captured on starting square, lose that castling right
-    if captured.is_some() {
-      match captured_square {
-        0 => self.white_castling.queenside = false,
-        7 => self.white_castling.kingside = false,
-        56 => self.black_castling.queenside = false,
-        63 => self.black_castling.kingside = false,
-        _ => {}
-      }
+    if let Some(captured) = captured
+      && captured.piece_type == PieceType::Rook
+    {
+      self.clear_castling_right(captured.color, captured_square);
     }
 
     // Update halfmove clock
@@ -443,75 +1300,52 @@ impl ChessBoard {
       self.fullmove_number = self.fullmove_number.saturating_add(1);
     }
 
-    // Save undo info
-    if self.undo_count < MAX_UNDO_HISTORY {
-      self.undo_history[self.undo_count] = Some(UndoInfo {
-        from,
-        to,
-        captured,
-        captured_square,
-        prev_en_passant,
-        prev_white_castling,
-        prev_black_castling,
-        prev_halfmove_clock,
-        castling: castling_move,
-        promotion: actual_promotion,
-        original_piece: piece.piece_type,
-      });
-      self.undo_count += 1;
-    }
-
     // Switch sides
     self.side_to_move = self.side_to_move.opponent();
-  }
-
-  /// Undo the last move (takeback)
-  /// Returns true if a move was undone, false if no move to undo
-  pub fn undo_move(&mut self) -> bool {
-    if self.undo_count == 0 {
-      return false;
-    }
-
-    self.undo_count -= 1;
-    let undo = match self.undo_history[self.undo_count].take() {
-      Some(u) => u,
-      None => return false,
-    };
-
+    self.hash = hash ^ self.state_hash();
+
+    Some(UndoInfo {
+      from,
+      to,
+      captured,
+      captured_square,
+      prev_en_passant,
+      prev_white_castling,
+      prev_black_castling,
+      prev_halfmove_clock,
+      prev_hash,
+      castling,
+      promotion: actual_promotion,
+      original_piece: piece.piece_type,
+    })
+  }
+
+  /// Unmake a move made with `make_move`, restoring the previous position
+  pub fn unmake_move(&mut self, undo: &UndoInfo) {
     // Switch back side to move
     self.side_to_move = self.side_to_move.opponent();
 
-    // Get the piece that was moved (it's now on the 'to' square)
-    let mut piece = self.squares[undo.to as usize].take().unwrap();
-
-    // If it was a promotion, revert to pawn
-    if undo.promotion.is_some() {
-      piece.piece_type = PieceType::Pawn;
-    }
+    if let Some(castle) = undo.castling {
+      // Lift both pieces first, mirroring `make_move`
+      let rook = self.squares[castle.rook_to as usize].take();
+      let king = self.squares[castle.king_to as usize].take();
+      self.squares[castle.rook_from as usize] = rook;
+      self.squares[castle.king_from as usize] = king;
+    } else {
+      // Get the piece that was moved (it's now on the 'to' square)
+      let mut piece = self.squares[undo.to as usize].take().unwrap();
 
-    // Put the piece back on the 'from' square
-    self.squares[undo.from as usize] = Some(piece);
+      // If it was a promotion, revert to pawn
+      if undo.promotion.is_some() {
+        piece.piece_type = PieceType::Pawn;
+      }
 
-    // Restore captured piece (if any)
-    if let Some(captured) = undo.captured {
-      self.squares[undo.captured_square as usize] = Some(captured);
-    }
+      // Put the piece back on the 'from' square
+      self.squares[undo.from as usize] = Some(piece);
 
-    // If it was castling, move the rook back
-    if let Some(kingside) = undo.castling {
-      let color = piece.color;
-      if kingside {
-        // Kingside - rook went from h-file to f-file
-        let rook_from = undo.to - 1; // f1 or f8
-        let rook_to = if color == PieceColor::White { WHITE_ROOK_KINGSIDE } else { BLACK_ROOK_KINGSIDE };
-        let rook = self.squares[rook_from as usize].take();
-        self.squares[rook_to as usize] = rook;
-      } else {
-        // Queenside - rook went from a-file to d-file
-        let rook_from = undo.to + 1; // d1 or d8
-        let rook_to = if color == PieceColor::White { WHITE_ROOK_QUEENSIDE } else { BLACK_ROOK_QUEENSIDE };
-        let rook = self.squares[rook_from as usize].take();
-        self.squares[rook_to as usize] = rook;
+      // Restore captured piece (if any)
+      if let Some(captured) = undo.captured {
+        self.squares[undo.captured_square as usize] = Some(captured);
       }
     }
 
@@ -521,12 +1355,43 @@ impl ChessBoard {
     self.black_castling = undo.prev_black_castling;
     self.halfmove_clock = undo.prev_halfmove_clock;
 
-    // Update fullmove number if we're undoing black's move
+    // Update fullmove number if we're undoing black's move
+    if self.side_to_move == PieceColor::Black {
+      self.fullmove_number = self.fullmove_number.saturating_sub(1);
+    }
+
+    self.hash = undo.prev_hash;
+  }
+
+  /// Zobrist hash of the position
+  ///
+  /// Covers piece placement, side to move, castling rights and en passant
+  /// square, so equal hashes mean the same position for repetition rules.
+  pub fn hash(&self) -> u64 {
+    self.hash
+  }
+
+  /// Compute the Zobrist hash from scratch
+  fn compute_hash(&self) -> u64 {
+    let mut hash = self.state_hash();
+    for sq in 0..64u8 {
+      if let Some(piece) = self.get(sq) {
+        hash ^= zobrist::piece(piece, sq);
+      }
+    }
+    hash
+  }
+
+  /// Hash keys for side to move, castling rights and en passant square
+  fn state_hash(&self) -> u64 {
+    let mut hash = zobrist::castling(self.white_castling, self.black_castling);
+    if let Some(ep) = self.en_passant {
+      hash ^= zobrist::en_passant(ep);
+    }
     if self.side_to_move == PieceColor::Black {
-      self.fullmove_number = self.fullmove_number.saturating_sub(1);
+      hash ^= zobrist::black_to_move();
     }
-
-    true
+    hash
   }
 
   fn is_pseudo_legal(&self, from: u8, to: u8) -> bool {
@@ -540,9 +1405,9 @@ impl ChessBoard {
       return false;
     }
 
-    // Can't capture our own piece
+    // Can't capture our own piece (except the rook in Chess960 castling)
     if let Some(target) = self.get(to) {
-      if target.color == piece.color {
+      if target.color == piece.color && self.castling_move(from, to).is_none() {
         return false;
       }
     }
@@ -702,23 +1567,152 @@ impl ChessBoard {
       return false;
     }
 
-    // Make the move on a copy and check if king is in check
-    let mut test_board = self.clone();
-    test_board.make_move(from, to);
+    let mut scratch = *self;
+    scratch.keeps_king_safe(from, to)
+  }
+
+  /// Make a pseudo-legal move in place, test whether the mover's king is
+  /// left in check, then unmake it
+  fn keeps_king_safe(&mut self, from: u8, to: u8) -> bool {
+    let mover = self.side_to_move;
+    let Some(undo) = self.make_move(from, to, None) else {
+      return false;
+    };
+    let safe = !self.is_in_check(mover);
+    self.unmake_move(&undo);
+    safe
+  }
+
+  /// Get all legal moves for the side to move
+  ///
+  /// Promotions are listed once per promotion piece (queen, rook, bishop, knight).
+  pub fn legal_moves(&self) -> MoveList {
+    let mut moves = MoveList::new();
+    self.generate_moves(false, |mv| moves.push(mv));
+    moves
+  }
+
+  /// Pass each legal move for the side to move to `push`, in the order of
+  /// `legal_moves`, without building a `MoveList`
+  pub fn for_each_legal_move(&self, push: impl FnMut(Move)) {
+    self.generate_moves(false, push);
+  }
+
+  /// Pass each legal capture and queen promotion for the side to move to
+  /// `push`: the moves a quiescence search looks at
+  pub fn for_each_legal_capture(&self, push: impl FnMut(Move)) {
+    self.generate_moves(true, push);
+  }
+
+  fn generate_moves(&self, captures_only: bool, mut push: impl FnMut(Move)) {
+    let mut scratch = *self;
+
+    for from in 0..64u8 {
+      let Some(piece) = self.get(from) else {
+        continue;
+      };
+      if piece.color != self.side_to_move {
+        continue;
+      }
+
+      for to in self.legal_destinations(from) {
+        let is_promotion = piece.piece_type == PieceType::Pawn && (to / 8 == 7 || to / 8 == 0);
+        let mut flags = MoveFlags::NONE;
+        match piece.piece_type {
+          PieceType::King if self.castling_move(from, to).is_some() => {
+            flags = flags | MoveFlags::CASTLE;
+          }
+          PieceType::Pawn if Some(to) == self.en_passant => {
+            flags = flags | MoveFlags::CAPTURE | MoveFlags::EN_PASSANT;
+          }
+          _ if self.get(to).is_some() => {
+            flags = flags | MoveFlags::CAPTURE;
+          }
+          _ => {}
+        }
+
+        if captures_only && !is_promotion && !flags.contains(MoveFlags::CAPTURE) {
+          continue;
+        }
+        // Makes and unmakes the move, so it is tested last
+        if !scratch.keeps_king_safe(from, to) {
+          continue;
+        }
+
+        let mv = Move { flags, ..Move::new(from, to) };
+        if captures_only && is_promotion {
+          push(Move { promotion: Some(PieceType::Queen), ..mv });
+        } else if is_promotion {
+          for promotion in [PieceType::Queen, PieceType::Rook, PieceType::Bishop, PieceType::Knight] {
+            push(Move { promotion: Some(promotion), ..mv });
+          }
+        } else {
+          push(mv);
+        }
+      }
+    }
+  }
+
+  /// Count leaf nodes of the legal move tree to `depth` plies
+  ///
+  /// Used to verify move generation against published node counts.
+  pub fn perft(&self, depth: u32) -> u64 {
+    let mut scratch = *self;
+    scratch.perft_in_place(depth)
+  }
+
+  fn perft_in_place(&mut self, depth: u32) -> u64 {
+    if depth == 0 {
+      return 1;
+    }
+
+    let moves = self.legal_moves();
+    if depth == 1 {
+      return moves.len() as u64;
+    }
+
+    let mut nodes = 0;
+    for mv in &moves {
+      if let Some(undo) = self.make_move(mv.from, mv.to, mv.promotion) {
+        nodes += self.perft_in_place(depth - 1);
+        self.unmake_move(&undo);
+      }
+    }
+    nodes
+  }
+
+  /// Perft split by root move
+  ///
+  /// Calls `report` with each legal move and its subtree node count, and
+  /// returns the total. Handy for locating move generation bugs against a
+  /// reference engine.
+  pub fn divide<F: FnMut(Move, u64)>(&self, depth: u32, mut report: F) -> u64 {
+    if depth == 0 {
+      return 1;
+    }
 
-    // After the move, check if the moving side's king is in check
-    // Note: make_move switches side_to_move, so we check the opponent (original mover)
-    !test_board.is_in_check(self.side_to_move)
+    let mut scratch = *self;
+    let mut total = 0;
+    for mv in &self.legal_moves() {
+      if let Some(undo) = scratch.make_move(mv.from, mv.to, mv.promotion) {
+        let nodes = scratch.perft_in_place(depth - 1);
+        scratch.unmake_move(&undo);
+        report(*mv, nodes);
+        total += nodes;
+      }
+    }
+    total
   }
 
   /// Check if the side to move has any legal moves
   pub fn has_legal_moves(&self) -> bool {
+    let mut scratch = *self;
     for from in 0..64u8 {
       if let Some(piece) = self.get(from) {
         if piece.color == self.side_to_move {
           let dests = self.legal_destinations(from);
           for to in dests {
-            if self.is_legal(from, to) {
+            if scratch.keeps_king_safe(from, to) {
               return true;
             }
           }
@@ -728,16 +1722,18 @@ impl ChessBoard {
     false
   }
 
-  /// Get the current board status (checkmate, stalemate, draw conditions, or ongoing)
+  /// Get the position status (checkmate, stalemate, automatic draws, or ongoing)
+  ///
+  /// Repetition needs the move history and is checked by `ChessBoard::status`.
   pub fn status(&self) -> BoardStatus {
-    // Check 50-move rule first (100 half-moves = 50 full moves)
-    // But if checkmate is delivered on move 50, checkmate takes precedence
-    if self.halfmove_clock >= 100 {
+    // Check 75-move rule first (150 half-moves = 75 full moves)
+    // But if checkmate is delivered on the last move, checkmate takes precedence
+    if self.halfmove_clock >= 150 {
       // Still need to check for checkmate
       if !self.has_legal_moves() && self.is_in_check(self.side_to_move) {
         return BoardStatus::Checkmate;
       }
-      return BoardStatus::FiftyMoveRule;
+      return BoardStatus::SeventyFiveMoveRule;
     }
 
     // Check insufficient material
@@ -815,6 +1811,56 @@ impl ChessBoard {
     false
   }
 
+  /// Check if `color` has no series of legal moves that ends in checkmate
+  ///
+  /// Judged for one side only, as FIDE requires when the opponent's flag falls.
+  /// A single knight can mate if the opponent has any other piece to block its
+  /// own king, and bishops on one square color can mate only if the opponent has
+  /// a piece that can stand on the other color.
+  fn has_insufficient_material(&self, color: PieceColor) -> bool {
+    let mut knights = 0u8;
+    let mut bishops = [0u8; 2]; // indexed by square color: 0 = dark, 1 = light
+    let mut opponent_pieces = 0u8;
+    let mut opponent_blockers = [0u8; 2]; // opponent pieces able to stand on each square color
+
+    for sq in 0..64u8 {
+      let Some(piece) = self.get(sq) else {
+        continue;
+      };
+      let sq_color = ((sq % 8 + sq / 8) % 2) as usize;
+      if piece.color == color {
+        match piece.piece_type {
+          PieceType::Pawn | PieceType::Rook | PieceType::Queen => return false,
+          PieceType::Knight => knights += 1,
+          PieceType::Bishop => bishops[sq_color] += 1,
+          PieceType::King => {}
+        }
+      } else {
+        match piece.piece_type {
+          PieceType::King => {}
+          PieceType::Bishop => {
+            opponent_pieces += 1;
+            opponent_blockers[sq_color] += 1;
+          }
+          _ => {
+            opponent_pieces += 1;
+            opponent_blockers[0] += 1;
+            opponent_blockers[1] += 1;
+          }
+        }
+      }
+    }
+
+    match (knights, bishops) {
+      (0, [0, 0]) => true,
+      (1, [0, 0]) => opponent_pieces == 0,
+      // Bishops on one color need a blocker on the other color
+      (0, [0, _]) => opponent_blockers[0] == 0,
+      (0, [_, 0]) => opponent_blockers[1] == 0,
+      _ => false,
+    }
+  }
+
   fn pawn_moves(&self, from: u8, color: PieceColor, dests: &mut Destinations) {
     let (dir, start_rank, _promo_rank): (i8, u8, u8) = match color {
       PieceColor::White => (8, 1, 6),
@@ -908,55 +1954,31 @@ impl ChessBoard {
 
     // Castling moves
     let castling = self.castling_rights(color);
-    let (king_start, rank) = match color {
-      PieceColor::White => (WHITE_KING_START, 0u8),
-      PieceColor::Black => (BLACK_KING_START, 7u8),
-    };
-
-    // Only if king is on starting square
-    if from != king_start {
-      return;
-    }
-
     let opponent = color.opponent();
 
-    // Kingside castling (O-O)
-    if castling.kingside {
-      let f_sq = rank * 8 + 5; // f1 or f8
-      let g_sq = rank * 8 + 6; // g1 or g8
-
-      // Squares between king and rook must be empty
-      let path_clear = self.get(f_sq).is_none() && self.get(g_sq).is_none();
-
-      // King must not be in check, and must not pass through or land on attacked squares
-      let not_in_check = !self.is_square_attacked(king_start, opponent);
-      let f_safe = !self.is_square_attacked(f_sq, opponent);
-      let g_safe = !self.is_square_attacked(g_sq, opponent);
-
-      if path_clear && not_in_check && f_safe && g_safe {
-        dests.push(g_sq); // King lands on g1/g8
+    for (kingside, allowed) in [(true, castling.kingside), (false, castling.queenside)] {
+      if !allowed {
+        continue;
       }
-    }
 
-    // Queenside castling (O-O-O)
-    if castling.queenside {
-      let b_sq = rank * 8 + 1; // b1 or b8
-      let c_sq = rank * 8 + 2; // c1 or c8
-      let d_sq = rank * 8 + 3; // d1 or d8
+      let castle = self.castling_squares(color, kingside);
+      if from != castle.king_from || self.get(castle.rook_from) != Some(Piece::new(PieceType::Rook, color)) {
+        continue;
+      }
 
-      // Squares between king and rook must be empty (b, c, d)
-      let path_clear = self.get(b_sq).is_none()
-        && self.get(c_sq).is_none()
-        && self.get(d_sq).is_none();
+      // Every square the king or rook crosses must be empty, apart from the
+      // castling king and rook themselves
+      let lo = castle.king_from.min(castle.king_to).min(castle.rook_from).min(castle.rook_to);
+      let hi = castle.king_from.max(castle.king_to).max(castle.rook_from).max(castle.rook_to);
+      let path_clear = (lo..=hi)
+        .all(|sq| sq == castle.king_from || sq == castle.rook_from || self.get(sq).is_none());
 
       // King must not be in check, and must not pass through or land on attacked squares
-      // Note: b-square doesn't need to be safe (rook passes through it, not king)
-      let not_in_check = !self.is_square_attacked(king_start, opponent);
-      let c_safe = !self.is_square_attacked(c_sq, opponent);
-      let d_safe = !self.is_square_attacked(d_sq, opponent);
+      let (king_lo, king_hi) = (castle.king_from.min(castle.king_to), castle.king_from.max(castle.king_to));
+      let king_safe = (king_lo..=king_hi).all(|sq| !self.is_square_attacked(sq, opponent));
 
-      if path_clear && not_in_check && c_safe && d_safe {
-        dests.push(c_sq); // King lands on c1/c8
+      if path_clear && king_safe {
+        dests.push(self.castling_target(&castle));
       }
     }
   }
@@ -1004,6 +2026,49 @@ const ALL_DIRS: [(i8, i8); 8] = [
   (1, 1), (1, -1), (-1, 1), (-1, -1),
 ];
 
+/// Parse an algebraic square name (e.g. "e4") into a square index
+pub fn parse_square(name: &str) -> Option<u8> {
+  match name.as_bytes() {
+    &[file @ b'a'..=b'h', rank @ b'1'..=b'8'] => Some((rank - b'1') * 8 + (file - b'a')),
+    _ => None,
+  }
+}
+
+/// Get the algebraic name of a square index as ASCII bytes (e.g. `*b"e4"`)
+pub fn square_name(sq: u8) -> [u8; 2] {
+  [b'a' + sq % 8, b'1' + sq / 8]
+}
+
+fn push_byte(buf: &mut [u8], pos: &mut usize, byte: u8) {
+  buf[*pos] = byte;
+  *pos += 1;
+}
+
+fn push_number(buf: &mut [u8], pos: &mut usize, value: u16) {
+  let mut digits = [0u8; 5];
+  let mut n = value;
+  let mut count = 0;
+  loop {
+    digits[count] = b'0' + (n % 10) as u8;
+    count += 1;
+    n /= 10;
+    if n == 0 {
+      break;
+    }
+  }
+  for &digit in digits[..count].iter().rev() {
+    push_byte(buf, pos, digit);
+  }
+}
+
+/// Rank index of a side's back rank
+fn back_rank(color: PieceColor) -> u8 {
+  match color {
+    PieceColor::White => 0,
+    PieceColor::Black => 7,
+  }
+}
+
 /// Add offset to square, returning None if out of bounds
 fn add_offset(sq: u8, offset: i8) -> Option<u8> {
   let result = sq as i8 + offset;
@@ -1066,9 +2131,9 @@ mod tests {
   fn test_make_move() {
     let mut board = ChessBoard::starting_position();
 
-    assert_eq!(board.side_to_move, PieceColor::White);
+    assert_eq!(board.position.side_to_move, PieceColor::White);
     board.make_move(12, 28); // e2-e4
-    assert_eq!(board.side_to_move, PieceColor::Black);
+    assert_eq!(board.position.side_to_move, PieceColor::Black);
     assert_eq!(board.get(12), None);
     assert_eq!(board.get(28), Some(Piece::new(PieceType::Pawn, PieceColor::White)));
   }
@@ -1078,20 +2143,20 @@ mod tests {
     let board = ChessBoard::starting_position();
 
     // Legal pawn move
-    assert!(board.is_pseudo_legal(12, 28)); // e2-e4
+    assert!(board.position.is_pseudo_legal(12, 28)); // e2-e4
 
     // Illegal - not our piece
-    assert!(!board.is_pseudo_legal(52, 36)); // e7-e5 (black's turn)
+    assert!(!board.position.is_pseudo_legal(52, 36)); // e7-e5 (black's turn)
 
     // Illegal - no piece
-    assert!(!board.is_pseudo_legal(32, 40));
+    assert!(!board.position.is_pseudo_legal(32, 40));
   }
 
   #[test]
   fn test_find_king() {
     let board = ChessBoard::starting_position();
-    assert_eq!(board.find_king(PieceColor::White), Some(4)); // e1
-    assert_eq!(board.find_king(PieceColor::Black), Some(60)); // e8
+    assert_eq!(board.position.find_king(PieceColor::White), Some(4)); // e1
+    assert_eq!(board.position.find_king(PieceColor::Black), Some(60)); // e8
   }
 
   #[test]
@@ -1105,8 +2170,8 @@ mod tests {
   fn test_check_by_rook() {
     // Set up position: White King on e1, Black Rook on e8
     let mut board = ChessBoard::empty();
-    board.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
-    board.squares[60] = Some(Piece::new(PieceType::Rook, PieceColor::Black));
+    board.position.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
+    board.position.squares[60] = Some(Piece::new(PieceType::Rook, PieceColor::Black));
 
     assert!(board.is_in_check(PieceColor::White));
     assert!(!board.is_in_check(PieceColor::Black));
@@ -1116,8 +2181,8 @@ mod tests {
   fn test_check_by_bishop() {
     // White King on e1, Black Bishop on h4 (diagonal)
     let mut board = ChessBoard::empty();
-    board.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
-    board.squares[31] = Some(Piece::new(PieceType::Bishop, PieceColor::Black)); // h4
+    board.position.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
+    board.position.squares[31] = Some(Piece::new(PieceType::Bishop, PieceColor::Black)); // h4
 
     assert!(board.is_in_check(PieceColor::White));
   }
@@ -1126,8 +2191,8 @@ mod tests {
   fn test_check_by_knight() {
     // White King on e1 (4), Black Knight on f3 (21)
     let mut board = ChessBoard::empty();
-    board.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
-    board.squares[21] = Some(Piece::new(PieceType::Knight, PieceColor::Black)); // f3
+    board.position.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
+    board.position.squares[21] = Some(Piece::new(PieceType::Knight, PieceColor::Black)); // f3
 
     assert!(board.is_in_check(PieceColor::White));
   }
@@ -1136,8 +2201,8 @@ mod tests {
   fn test_check_by_pawn() {
     // White King on e4 (28), Black Pawn on d5 (35)
     let mut board = ChessBoard::empty();
-    board.squares[28] = Some(Piece::new(PieceType::King, PieceColor::White));
-    board.squares[35] = Some(Piece::new(PieceType::Pawn, PieceColor::Black)); // d5
+    board.position.squares[28] = Some(Piece::new(PieceType::King, PieceColor::White));
+    board.position.squares[35] = Some(Piece::new(PieceType::Pawn, PieceColor::Black)); // d5
 
     assert!(board.is_in_check(PieceColor::White));
   }
@@ -1146,9 +2211,9 @@ mod tests {
   fn test_blocked_check() {
     // White King on e1, Black Rook on e8, White Pawn on e2 (blocks)
     let mut board = ChessBoard::empty();
-    board.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
-    board.squares[60] = Some(Piece::new(PieceType::Rook, PieceColor::Black));
-    board.squares[12] = Some(Piece::new(PieceType::Pawn, PieceColor::White)); // e2
+    board.position.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
+    board.position.squares[60] = Some(Piece::new(PieceType::Rook, PieceColor::Black));
+    board.position.squares[12] = Some(Piece::new(PieceType::Pawn, PieceColor::White)); // e2
 
     assert!(!board.is_in_check(PieceColor::White)); // Pawn blocks
   }
@@ -1158,12 +2223,12 @@ mod tests {
     // White King on e1, Black Rook on a1, White Bishop on c1
     // Moving the bishop would expose king to check along the 1st rank
     let mut board = ChessBoard::empty();
-    board.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White)); // e1
-    board.squares[0] = Some(Piece::new(PieceType::Rook, PieceColor::Black)); // a1
-    board.squares[2] = Some(Piece::new(PieceType::Bishop, PieceColor::White)); // c1
+    board.position.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White)); // e1
+    board.position.squares[0] = Some(Piece::new(PieceType::Rook, PieceColor::Black)); // a1
+    board.position.squares[2] = Some(Piece::new(PieceType::Bishop, PieceColor::White)); // c1
 
     // Bishop moving diagonally is pseudo-legal but would expose king to check
-    assert!(board.is_pseudo_legal(2, 11)); // c1-d2
+    assert!(board.position.is_pseudo_legal(2, 11)); // c1-d2
     assert!(!board.is_legal(2, 11)); // Can't move - would expose king to rook on a1
   }
 
@@ -1172,9 +2237,9 @@ mod tests {
     // White King on e1, Black Rook on e8, White's turn
     // King must move, other pieces can't move
     let mut board = ChessBoard::empty();
-    board.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
-    board.squares[60] = Some(Piece::new(PieceType::Rook, PieceColor::Black));
-    board.squares[0] = Some(Piece::new(PieceType::Rook, PieceColor::White)); // a1
+    board.position.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
+    board.position.squares[60] = Some(Piece::new(PieceType::Rook, PieceColor::Black));
+    board.position.squares[0] = Some(Piece::new(PieceType::Rook, PieceColor::White)); // a1
 
     assert!(board.is_in_check(PieceColor::White));
 
@@ -1192,11 +2257,11 @@ mod tests {
     // King on a1, pawns on a2 and b2, black queen on d1 gives check
     // Black rook on d8 prevents queen capture
     let mut board = ChessBoard::empty();
-    board.squares[0] = Some(Piece::new(PieceType::King, PieceColor::White)); // a1
-    board.squares[8] = Some(Piece::new(PieceType::Pawn, PieceColor::White)); // a2
-    board.squares[9] = Some(Piece::new(PieceType::Pawn, PieceColor::White)); // b2
-    board.squares[3] = Some(Piece::new(PieceType::Queen, PieceColor::Black)); // d1 - gives check
-    board.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black)); // e8 - black king (needed for valid position)
+    board.position.squares[0] = Some(Piece::new(PieceType::King, PieceColor::White)); // a1
+    board.position.squares[8] = Some(Piece::new(PieceType::Pawn, PieceColor::White)); // a2
+    board.position.squares[9] = Some(Piece::new(PieceType::Pawn, PieceColor::White)); // b2
+    board.position.squares[3] = Some(Piece::new(PieceType::Queen, PieceColor::Black)); // d1 - gives check
+    board.position.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black)); // e8 - black king (needed for valid position)
 
     assert!(board.is_in_check(PieceColor::White));
     assert!(!board.has_legal_moves());
@@ -1207,9 +2272,9 @@ mod tests {
   fn test_stalemate() {
     // King on a1 with no legal moves, not in check
     let mut board = ChessBoard::empty();
-    board.squares[0] = Some(Piece::new(PieceType::King, PieceColor::White)); // a1
-    board.squares[17] = Some(Piece::new(PieceType::Queen, PieceColor::Black)); // b3
-    board.squares[10] = Some(Piece::new(PieceType::King, PieceColor::Black)); // c2
+    board.position.squares[0] = Some(Piece::new(PieceType::King, PieceColor::White)); // a1
+    board.position.squares[17] = Some(Piece::new(PieceType::Queen, PieceColor::Black)); // b3
+    board.position.squares[10] = Some(Piece::new(PieceType::King, PieceColor::Black)); // c2
 
     assert!(!board.is_in_check(PieceColor::White));
     assert!(!board.has_legal_moves());
@@ -1241,8 +2306,8 @@ mod tests {
   fn test_castling_kingside() {
     let mut board = ChessBoard::starting_position();
     // Clear squares between king and rook
-    board.squares[5] = None; // f1
-    board.squares[6] = None; // g1
+    board.position.squares[5] = None; // f1
+    board.position.squares[6] = None; // g1
 
     // King should be able to castle kingside
     let dests = board.legal_destinations(4); // e1
@@ -1262,9 +2327,9 @@ mod tests {
   fn test_castling_queenside() {
     let mut board = ChessBoard::starting_position();
     // Clear squares between king and rook
-    board.squares[1] = None; // b1
-    board.squares[2] = None; // c1
-    board.squares[3] = None; // d1
+    board.position.squares[1] = None; // b1
+    board.position.squares[2] = None; // c1
+    board.position.squares[3] = None; // d1
 
     // King should be able to castle queenside
     let dests = board.legal_destinations(4); // e1
@@ -1284,10 +2349,10 @@ mod tests {
   fn test_castling_blocked_by_check() {
     // Set up position where king is in check and can't castle
     let mut board = ChessBoard::empty();
-    board.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White)); // e1
-    board.squares[7] = Some(Piece::new(PieceType::Rook, PieceColor::White)); // h1
-    board.squares[60] = Some(Piece::new(PieceType::Rook, PieceColor::Black)); // e8 attacks e1
-    board.white_castling = CastlingRights::new(); // Can castle
+    board.position.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White)); // e1
+    board.position.squares[7] = Some(Piece::new(PieceType::Rook, PieceColor::White)); // h1
+    board.position.squares[60] = Some(Piece::new(PieceType::Rook, PieceColor::Black)); // e8 attacks e1
+    board.position.white_castling = CastlingRights::new(); // Can castle
 
     // King is in check
     assert!(board.is_in_check(PieceColor::White));
@@ -1301,12 +2366,12 @@ mod tests {
   fn test_castling_rights_lost_after_king_move() {
     let mut board = ChessBoard::starting_position();
     // Clear f1
-    board.squares[5] = None;
+    board.position.squares[5] = None;
 
     // Move king to f1
     board.make_move(4, 5);
     // Move king back (black moves first, let's just set up the position)
-    board.side_to_move = PieceColor::White;
+    board.position.side_to_move = PieceColor::White;
     board.make_move(5, 4);
 
     // Castling rights should be lost
@@ -1318,19 +2383,19 @@ mod tests {
   #[test]
   fn test_en_passant_capture() {
     let mut board = ChessBoard::empty();
-    board.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
-    board.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black));
+    board.position.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
+    board.position.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black));
     // White pawn on e5
-    board.squares[36] = Some(Piece::new(PieceType::Pawn, PieceColor::White));
+    board.position.squares[36] = Some(Piece::new(PieceType::Pawn, PieceColor::White));
     // Black pawn on d7
-    board.squares[51] = Some(Piece::new(PieceType::Pawn, PieceColor::Black));
-    board.side_to_move = PieceColor::Black;
+    board.position.squares[51] = Some(Piece::new(PieceType::Pawn, PieceColor::Black));
+    board.position.side_to_move = PieceColor::Black;
 
     // Black plays d7-d5 (double pawn push)
     board.make_move(51, 35);
 
     // En passant should be available
-    assert_eq!(board.en_passant, Some(43)); // d6
+    assert_eq!(board.position.en_passant, Some(43)); // d6
 
     // White pawn on e5 should be able to capture en passant
     let dests = board.legal_destinations(36);
@@ -1347,10 +2412,10 @@ mod tests {
   #[test]
   fn test_pawn_promotion() {
     let mut board = ChessBoard::empty();
-    board.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
-    board.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black));
+    board.position.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
+    board.position.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black));
     // White pawn on e7
-    board.squares[52] = Some(Piece::new(PieceType::Pawn, PieceColor::White));
+    board.position.squares[52] = Some(Piece::new(PieceType::Pawn, PieceColor::White));
 
     // Move pawn to e8 (promotion)
     board.make_move(52, 60);
@@ -1362,10 +2427,10 @@ mod tests {
   #[test]
   fn test_pawn_promotion_to_knight() {
     let mut board = ChessBoard::empty();
-    board.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
-    board.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black));
+    board.position.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
+    board.position.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black));
     // White pawn on e7
-    board.squares[52] = Some(Piece::new(PieceType::Pawn, PieceColor::White));
+    board.position.squares[52] = Some(Piece::new(PieceType::Pawn, PieceColor::White));
 
     // Move pawn to e8 with knight promotion
     board.make_move_with_promotion(52, 60, Some(PieceType::Knight));
@@ -1383,22 +2448,22 @@ mod tests {
 
     assert_eq!(board.get(12), None);
     assert_eq!(board.get(28), Some(Piece::new(PieceType::Pawn, PieceColor::White)));
-    assert_eq!(board.side_to_move, PieceColor::Black);
+    assert_eq!(board.position.side_to_move, PieceColor::Black);
 
     assert!(board.undo_move());
 
     assert_eq!(board.get(12), Some(Piece::new(PieceType::Pawn, PieceColor::White)));
     assert_eq!(board.get(28), None);
-    assert_eq!(board.side_to_move, PieceColor::White);
+    assert_eq!(board.position.side_to_move, PieceColor::White);
   }
 
   #[test]
   fn test_undo_capture() {
     let mut board = ChessBoard::empty();
-    board.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
-    board.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black));
-    board.squares[28] = Some(Piece::new(PieceType::Pawn, PieceColor::White)); // e4
-    board.squares[35] = Some(Piece::new(PieceType::Pawn, PieceColor::Black)); // d5
+    board.position.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
+    board.position.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black));
+    board.position.squares[28] = Some(Piece::new(PieceType::Pawn, PieceColor::White)); // e4
+    board.position.squares[35] = Some(Piece::new(PieceType::Pawn, PieceColor::Black)); // d5
 
     // Capture
     board.make_move(28, 35); // exd5
@@ -1416,8 +2481,8 @@ mod tests {
   #[test]
   fn test_undo_castling() {
     let mut board = ChessBoard::starting_position();
-    board.squares[5] = None; // f1
-    board.squares[6] = None; // g1
+    board.position.squares[5] = None; // f1
+    board.position.squares[6] = None; // g1
 
     // Castle kingside
     board.make_move(4, 6);
@@ -1442,11 +2507,11 @@ mod tests {
   #[test]
   fn test_undo_en_passant() {
     let mut board = ChessBoard::empty();
-    board.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
-    board.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black));
-    board.squares[36] = Some(Piece::new(PieceType::Pawn, PieceColor::White)); // e5
-    board.squares[51] = Some(Piece::new(PieceType::Pawn, PieceColor::Black)); // d7
-    board.side_to_move = PieceColor::Black;
+    board.position.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
+    board.position.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black));
+    board.position.squares[36] = Some(Piece::new(PieceType::Pawn, PieceColor::White)); // e5
+    board.position.squares[51] = Some(Piece::new(PieceType::Pawn, PieceColor::Black)); // d7
+    board.position.side_to_move = PieceColor::Black;
 
     // Black plays d7-d5
     board.make_move(51, 35);
@@ -1467,9 +2532,9 @@ mod tests {
   #[test]
   fn test_undo_promotion() {
     let mut board = ChessBoard::empty();
-    board.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White)); // e1
-    board.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black)); // e8
-    board.squares[55] = Some(Piece::new(PieceType::Pawn, PieceColor::White)); // h7
+    board.position.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White)); // e1
+    board.position.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black)); // e8
+    board.position.squares[55] = Some(Piece::new(PieceType::Pawn, PieceColor::White)); // h7
 
     // Promote (h7-h8)
     board.make_move(55, 63);
@@ -1484,72 +2549,504 @@ mod tests {
     assert_eq!(board.get(63), None);
   }
 
+  #[test]
+  fn test_undo_keeps_most_recent_moves() {
+    let mut board = ChessBoard::starting_position();
+
+    // Shuffle knights past the undo history capacity
+    for _ in 0..30 {
+      board.make_move(6, 21); // Ng1-f3
+      board.make_move(62, 45); // Ng8-f6
+      board.make_move(21, 6); // Nf3-g1
+      board.make_move(45, 62); // Nf6-g8
+    }
+    board.make_move(6, 21); // Ng1-f3
+
+    let last = board.last_undo_info().unwrap();
+    assert_eq!((last.from, last.to), (6, 21));
+
+    for _ in 0..MAX_UNDO_HISTORY {
+      assert!(board.undo_move());
+    }
+    assert!(!board.undo_move());
+    // Undone back to the position after 21 moves: Nf3 with black to move
+    assert_eq!(board.get(21), Some(Piece::new(PieceType::Knight, PieceColor::White)));
+    assert_eq!(board.side_to_move(), PieceColor::Black);
+  }
+
+  #[test]
+  fn test_legality_check_leaves_position_unchanged() {
+    // Pinned knight: legality is tested by making and unmaking in place
+    let board = ChessBoard::from_fen("4k3/4r3/8/8/8/8/4N3/4K3 w - - 0 1").unwrap();
+    let hash = board.hash();
+
+    assert!(!board.is_legal(12, 29)); // Ne2-f4 exposes the king
+    assert!(board.is_legal(4, 3)); // Ke1-d1
+    assert_eq!(board.legal_moves().len(), 4);
+    assert_eq!(board.hash(), hash);
+    assert_eq!(board.position().compute_hash(), hash);
+  }
+
   #[test]
   fn test_halfmove_clock() {
     let mut board = ChessBoard::starting_position();
 
-    assert_eq!(board.halfmove_clock, 0);
+    assert_eq!(board.position.halfmove_clock, 0);
 
     board.make_move(12, 28); // e2-e4
-    assert_eq!(board.halfmove_clock, 0);
+    assert_eq!(board.position.halfmove_clock, 0);
 
     board.make_move(57, 42); // Nb8-c6
-    assert_eq!(board.halfmove_clock, 1);
+    assert_eq!(board.position.halfmove_clock, 1);
 
     board.make_move(6, 21); // Ng1-f3
-    assert_eq!(board.halfmove_clock, 2);
+    assert_eq!(board.position.halfmove_clock, 2);
 
     board.make_move(52, 36); // e7-e5
-    assert_eq!(board.halfmove_clock, 0);
+    assert_eq!(board.position.halfmove_clock, 0);
   }
 
   #[test]
   fn test_insufficient_material_king_vs_king() {
     let mut board = ChessBoard::empty();
-    board.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
-    board.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black));
+    board.position.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
+    board.position.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black));
 
-    assert!(board.is_insufficient_material());
+    assert!(board.position.is_insufficient_material());
     assert_eq!(board.status(), BoardStatus::InsufficientMaterial);
   }
 
   #[test]
   fn test_insufficient_material_king_bishop_vs_king() {
     let mut board = ChessBoard::empty();
-    board.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
-    board.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black));
-    board.squares[20] = Some(Piece::new(PieceType::Bishop, PieceColor::White));
+    board.position.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
+    board.position.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black));
+    board.position.squares[20] = Some(Piece::new(PieceType::Bishop, PieceColor::White));
 
-    assert!(board.is_insufficient_material());
+    assert!(board.position.is_insufficient_material());
   }
 
   #[test]
   fn test_insufficient_material_king_knight_vs_king() {
     let mut board = ChessBoard::empty();
-    board.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
-    board.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black));
-    board.squares[20] = Some(Piece::new(PieceType::Knight, PieceColor::White));
+    board.position.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
+    board.position.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black));
+    board.position.squares[20] = Some(Piece::new(PieceType::Knight, PieceColor::White));
+
+    assert!(board.position.is_insufficient_material());
+  }
+
+  #[test]
+  fn test_insufficient_material_per_side() {
+    let board = ChessBoard::from_fen("k7/8/8/8/8/8/8/KN6 w - - 0 1").unwrap();
+    assert!(board.has_insufficient_material(PieceColor::White));
+    assert!(board.has_insufficient_material(PieceColor::Black));
+
+    // A knight mates if the opponent has a piece to block its own king
+    let board = ChessBoard::from_fen("kr6/8/8/8/8/8/8/KN6 w - - 0 1").unwrap();
+    assert!(!board.has_insufficient_material(PieceColor::White));
+    assert!(!board.has_insufficient_material(PieceColor::Black));
+
+    // Two knights can mate
+    let board = ChessBoard::from_fen("k7/8/8/8/8/8/8/KNN5 w - - 0 1").unwrap();
+    assert!(!board.has_insufficient_material(PieceColor::White));
+  }
 
-    assert!(board.is_insufficient_material());
+  #[test]
+  fn test_insufficient_material_bishops_per_side() {
+    // Dark-squared bishops against a bishop on the same color cannot mate
+    let board = ChessBoard::from_fen("k7/8/8/8/8/8/1b6/K1B1B3 w - - 0 1").unwrap();
+    assert!(board.has_insufficient_material(PieceColor::White));
+
+    // An opponent bishop on the other color can block
+    let board = ChessBoard::from_fen("k7/8/8/8/8/8/b7/K1B1B3 w - - 0 1").unwrap();
+    assert!(!board.has_insufficient_material(PieceColor::White));
+
+    // Bishops on both colors can mate
+    let board = ChessBoard::from_fen("k7/8/8/8/8/8/8/K1BB4 w - - 0 1").unwrap();
+    assert!(!board.has_insufficient_material(PieceColor::White));
   }
 
   #[test]
   fn test_sufficient_material_king_rook_vs_king() {
     let mut board = ChessBoard::empty();
-    board.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
-    board.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black));
-    board.squares[0] = Some(Piece::new(PieceType::Rook, PieceColor::White));
+    board.position.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
+    board.position.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black));
+    board.position.squares[0] = Some(Piece::new(PieceType::Rook, PieceColor::White));
 
-    assert!(!board.is_insufficient_material());
+    assert!(!board.position.is_insufficient_material());
   }
 
   #[test]
   fn test_sufficient_material_with_pawn() {
     let mut board = ChessBoard::empty();
-    board.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
-    board.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black));
-    board.squares[12] = Some(Piece::new(PieceType::Pawn, PieceColor::White));
+    board.position.squares[4] = Some(Piece::new(PieceType::King, PieceColor::White));
+    board.position.squares[60] = Some(Piece::new(PieceType::King, PieceColor::Black));
+    board.position.squares[12] = Some(Piece::new(PieceType::Pawn, PieceColor::White));
+
+    assert!(!board.position.is_insufficient_material());
+  }
+
+  #[test]
+  fn test_fen_starting_position_roundtrip() {
+    let board = ChessBoard::from_fen(STARTING_FEN).unwrap();
+    assert_eq!(board.get(4), Some(Piece::new(PieceType::King, PieceColor::White)));
+    assert_eq!(board.get(59), Some(Piece::new(PieceType::Queen, PieceColor::Black)));
+    assert_eq!(board.side_to_move(), PieceColor::White);
+    assert_eq!(board.castling_rights(PieceColor::Black), CastlingRights::new());
+
+    let mut buf = [0u8; MAX_FEN_LEN];
+    assert_eq!(ChessBoard::starting_position().to_fen(&mut buf), STARTING_FEN);
+  }
+
+  #[test]
+  fn test_fen_roundtrip_complex_position() {
+    let fen = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    let board = ChessBoard::from_fen(fen).unwrap();
+    let mut buf = [0u8; MAX_FEN_LEN];
+    assert_eq!(board.to_fen(&mut buf), fen);
+
+    let fen = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 b - e3 12 57";
+    let board = ChessBoard::from_fen(fen).unwrap();
+    assert_eq!(board.side_to_move(), PieceColor::Black);
+    assert_eq!(board.position.en_passant, Some(20));
+    assert_eq!(board.position.halfmove_clock, 12);
+    assert_eq!(board.position.fullmove_number, 57);
+    assert_eq!(board.to_fen(&mut buf), fen);
+  }
+
+  #[test]
+  fn test_fen_after_moves() {
+    let mut board = ChessBoard::starting_position();
+    board.make_move(12, 28); // e2-e4
+    board.make_move(50, 34); // c7-c5
+    board.make_move(6, 21); // Ng1-f3
+
+    let mut buf = [0u8; MAX_FEN_LEN];
+    assert_eq!(
+      board.to_fen(&mut buf),
+      "rnbqkbnr/pp1ppppp/8/2p5/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2"
+    );
+  }
+
+  #[test]
+  fn test_fen_optional_move_counters() {
+    let board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/4K3 w - -").unwrap();
+    assert_eq!(board.position.halfmove_clock, 0);
+    assert_eq!(board.position.fullmove_number, 1);
+  }
+
+  #[test]
+  fn test_fen_errors() {
+    assert_eq!(ChessBoard::from_fen("").err(), Some(FenError::InvalidPlacement));
+    assert_eq!(
+      ChessBoard::from_fen("4k3/8/8/8/8/8/8/4K2 w - - 0 1").err(),
+      Some(FenError::InvalidPlacement)
+    );
+    assert_eq!(
+      ChessBoard::from_fen("4k3/8/8/8/8/8/8/4K3/8 w - - 0 1").err(),
+      Some(FenError::InvalidPlacement)
+    );
+    assert_eq!(
+      ChessBoard::from_fen("4k3/8/8/8/8/8/8/4X3 w - - 0 1").err(),
+      Some(FenError::InvalidPlacement)
+    );
+    assert_eq!(
+      ChessBoard::from_fen("8/8/8/8/8/8/8/4K3 w - - 0 1").err(),
+      Some(FenError::InvalidPlacement)
+    );
+    assert_eq!(
+      ChessBoard::from_fen("4k3/8/8/8/8/8/8/4K3 x - - 0 1").err(),
+      Some(FenError::InvalidSideToMove)
+    );
+    assert_eq!(
+      ChessBoard::from_fen("4k3/8/8/8/8/8/8/4K3 w K - 0 1").err(),
+      Some(FenError::InvalidCastling)
+    );
+    assert_eq!(
+      ChessBoard::from_fen("4k3/8/8/8/8/8/8/R3K3 w QQ - 0 1").err(),
+      Some(FenError::InvalidCastling)
+    );
+    assert_eq!(
+      ChessBoard::from_fen("4k3/8/8/8/8/8/8/4K3 w - e3 0 1").err(),
+      Some(FenError::InvalidEnPassant)
+    );
+    assert_eq!(
+      ChessBoard::from_fen("4k3/8/8/8/8/8/8/4K3 w - z9 0 1").err(),
+      Some(FenError::InvalidEnPassant)
+    );
+    assert_eq!(
+      ChessBoard::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 300 1").err(),
+      Some(FenError::InvalidHalfmoveClock)
+    );
+    assert_eq!(
+      ChessBoard::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 0").err(),
+      Some(FenError::InvalidFullmoveNumber)
+    );
+    assert_eq!(
+      ChessBoard::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1 x").err(),
+      Some(FenError::TooManyFields)
+    );
+  }
+
+  #[test]
+  fn test_square_names() {
+    assert_eq!(parse_square("a1"), Some(0));
+    assert_eq!(parse_square("e4"), Some(28));
+    assert_eq!(parse_square("h8"), Some(63));
+    assert_eq!(parse_square("i1"), None);
+    assert_eq!(parse_square("e44"), None);
+    assert_eq!(&square_name(28), b"e4");
+  }
+
+  #[test]
+  fn test_hash_matches_recomputed() {
+    let mut board = ChessBoard::from_fen("r3k2r/1P6/8/8/3p4/8/4P3/R3K2R w KQkq - 0 1").unwrap();
+    let start_hash = board.hash();
+
+    // Double push enabling en passant, en passant capture, castling, promotion with capture
+    let moves = [(12, 28), (27, 20), (4, 6), (60, 58), (49, 56)];
+    for &(from, to) in &moves {
+      board.make_move(from, to);
+      assert_eq!(board.hash(), board.position.compute_hash());
+    }
+
+    for _ in 0..moves.len() {
+      assert!(board.undo_move());
+      assert_eq!(board.hash(), board.position.compute_hash());
+    }
+    assert_eq!(board.hash(), start_hash);
+  }
+
+  #[test]
+  fn test_hash_depends_on_side_and_castling() {
+    let white = ChessBoard::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+    let black = ChessBoard::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").unwrap();
+    let no_castling = ChessBoard::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w - - 0 1").unwrap();
+
+    assert_ne!(white.hash(), black.hash());
+    assert_ne!(white.hash(), no_castling.hash());
+  }
+
+  #[test]
+  fn test_threefold_repetition_claimable() {
+    let mut board = ChessBoard::starting_position();
+    let shuffle = [(6, 21), (62, 45), (21, 6), (45, 62)];
+
+    for &(from, to) in &shuffle {
+      board.make_move(from, to);
+    }
+    assert_eq!(board.repetition_count(), 2);
+    assert_eq!(board.claimable_draw(), None);
+
+    for &(from, to) in &shuffle {
+      board.make_move(from, to);
+    }
+    assert_eq!(board.repetition_count(), 3);
+    assert_eq!(board.claimable_draw(), Some(BoardStatus::ThreefoldRepetition));
+    assert_eq!(board.status(), BoardStatus::Ongoing);
+
+    board.undo_move();
+    assert_eq!(board.claimable_draw(), None);
+  }
+
+  #[test]
+  fn test_fivefold_repetition_automatic() {
+    let mut board = ChessBoard::starting_position();
+    let shuffle = [(6, 21), (62, 45), (21, 6), (45, 62)];
+
+    for _ in 0..3 {
+      for &(from, to) in &shuffle {
+        board.make_move(from, to);
+      }
+    }
+    assert_eq!(board.status(), BoardStatus::Ongoing);
+
+    for &(from, to) in &shuffle {
+      board.make_move(from, to);
+    }
+    assert_eq!(board.repetition_count(), 5);
+    assert_eq!(board.status(), BoardStatus::FivefoldRepetition);
+  }
+
+  #[test]
+  fn test_fifty_and_seventy_five_move_rules() {
+    let mut board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 99 80").unwrap();
+    assert_eq!(board.claimable_draw(), None);
+
+    board.make_move(0, 1);
+    assert_eq!(board.claimable_draw(), Some(BoardStatus::FiftyMoveRule));
+    assert_eq!(board.status(), BoardStatus::Ongoing);
+
+    let mut board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/R3K3 w - - 149 105").unwrap();
+    board.make_move(0, 1);
+    assert_eq!(board.status(), BoardStatus::SeventyFiveMoveRule);
+  }
+
+  #[test]
+  fn test_legal_moves_starting_position() {
+    let board = ChessBoard::starting_position();
+    let moves = board.legal_moves();
+
+    assert_eq!(moves.len(), 20);
+    assert!(moves.iter().all(|m| m.flags == MoveFlags::NONE && m.promotion.is_none()));
+    assert!(moves.find(12, 28, None).is_some());
+    assert!(moves.find(12, 36, None).is_none());
+  }
+
+  #[test]
+  fn test_legal_moves_flags() {
+    // White can capture en passant on d6 and castle both ways
+    let board = ChessBoard::from_fen("r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1").unwrap();
+    let moves = board.legal_moves();
+
+    let ep = moves.find(36, 43, None).unwrap();
+    assert!(ep.is_en_passant() && ep.is_capture());
+
+    assert!(moves.find(4, 6, None).unwrap().is_castle());
+    assert!(moves.find(4, 2, None).unwrap().is_castle());
+
+    let rook_capture = moves.find(0, 56, None).unwrap();
+    assert!(rook_capture.is_capture() && !rook_capture.is_castle());
+  }
+
+  #[test]
+  fn test_legal_moves_underpromotions() {
+    let board = ChessBoard::from_fen("1n2k3/P7/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    let moves = board.legal_moves();
+
+    let promotions: usize = moves.iter().filter(|m| m.promotion.is_some()).count();
+    // a8 push and axb8 capture, four pieces each
+    assert_eq!(promotions, 8);
+    assert!(moves.find(48, 57, Some(PieceType::Knight)).unwrap().is_capture());
+    assert!(moves.find(48, 56, Some(PieceType::Rook)).is_some());
+    assert!(moves.find(48, 56, None).is_none());
+  }
+
+  #[test]
+  fn test_legal_captures() {
+    // Queen promotions with and without capture, en passant, and a pinned
+    // knight that may not take
+    let position = Position::from_fen("1n2k3/P7/8/3pP3/3p4/4r3/4N3/4K3 w - d6 0 1").unwrap();
+    let mut captures = MoveList::new();
+    position.for_each_legal_capture(|mv| captures.push(mv));
+
+    assert_eq!(captures.len(), 3);
+    assert!(captures.find(48, 56, Some(PieceType::Queen)).is_some());
+    assert!(captures.find(48, 57, Some(PieceType::Queen)).is_some());
+    assert!(captures.find(36, 43, None).unwrap().is_en_passant());
+    assert!(captures.iter().all(|mv| mv.is_capture() || mv.promotion.is_some()));
+  }
+
+  #[test]
+  fn test_legal_moves_in_check() {
+    // Black king in check from the rook must deal with it
+    let board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/4RK2 b - - 0 1").unwrap();
+    let moves = board.legal_moves();
 
-    assert!(!board.is_insufficient_material());
+    assert!(moves.iter().all(|m| m.from == 60));
+    assert_eq!(moves.len(), 4);
+
+    let mate = ChessBoard::from_fen("R3k3/8/4K3/8/8/8/8/8 b - - 0 1").unwrap();
+    assert!(mate.legal_moves().is_empty());
+  }
+
+  #[test]
+  fn test_chess960_back_ranks() {
+    use PieceType::{Bishop as B, King as K, Knight as N, Queen as Q, Rook as R};
+
+    assert_eq!(chess960_back_rank(518), Some([R, N, B, Q, K, B, N, R]));
+    assert_eq!(chess960_back_rank(0), Some([B, B, Q, N, N, R, K, R]));
+    assert_eq!(chess960_back_rank(959), Some([R, K, R, N, N, Q, B, B]));
+    assert_eq!(chess960_back_rank(CHESS960_POSITIONS), None);
+
+    let mut seen = std::collections::HashSet::new();
+    for index in 0..CHESS960_POSITIONS {
+      let rank = chess960_back_rank(index).unwrap();
+      assert!(seen.insert(rank.map(|p| p.to_char())), "duplicate position {}", index);
+
+      let bishops: Vec<usize> = (0..8).filter(|&f| rank[f] == B).collect();
+      assert_ne!(bishops[0] % 2, bishops[1] % 2, "bishops on same color in {}", index);
+      let rooks: Vec<usize> = (0..8).filter(|&f| rank[f] == R).collect();
+      let king = rank.iter().position(|&p| p == K).unwrap();
+      assert!(rooks[0] < king && king < rooks[1], "king not between rooks in {}", index);
+    }
+
+    let standard = ChessBoard::chess960(518).unwrap();
+    assert_eq!(standard.hash(), ChessBoard::starting_position().hash());
+    assert!(!standard.position().is_chess960());
+  }
+
+  #[test]
+  fn test_chess960_fen_castling_rights() {
+    let board = ChessBoard::chess960(0).unwrap();
+    let mut buf = [0u8; MAX_FEN_LEN];
+    assert_eq!(
+      board.to_fen(&mut buf),
+      "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w HFhf - 0 1"
+    );
+
+    // X-FEN letters name the outermost rook
+    let xfen = ChessBoard::from_fen("bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1").unwrap();
+    assert_eq!(xfen.hash(), board.hash());
+    assert_eq!(xfen.position().castling_files(), board.position().castling_files());
+
+    // Sides with different castling files cannot come from one start position
+    assert_eq!(
+      ChessBoard::from_fen("rk5r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").err(),
+      Some(FenError::InvalidCastling)
+    );
+  }
+
+  #[test]
+  fn test_chess960_castling() {
+    let mut board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/RK5R w HA - 0 1").unwrap();
+    let moves = board.legal_moves();
+
+    // Encoded as the king moving onto its own rook
+    let queenside = moves.find(1, 0, None).unwrap();
+    assert!(queenside.is_castle() && !queenside.is_capture());
+    assert!(moves.find(1, 7, None).unwrap().is_castle());
+    assert_eq!(board.castling_to(2), Some(queenside));
+
+    board.make_move(1, 0);
+    assert_eq!(board.get(2), Some(Piece::new(PieceType::King, PieceColor::White)));
+    assert_eq!(board.get(3), Some(Piece::new(PieceType::Rook, PieceColor::White)));
+    assert_eq!(board.get(0), None);
+    assert_eq!(board.get(1), None);
+    assert_eq!(board.castling_rights(PieceColor::White), CastlingRights::none());
+    assert_eq!(board.hash(), board.position().compute_hash());
+
+    assert!(board.undo_move());
+    assert_eq!(board.get(0), Some(Piece::new(PieceType::Rook, PieceColor::White)));
+    assert_eq!(board.get(1), Some(Piece::new(PieceType::King, PieceColor::White)));
+    assert_eq!(board.get(3), None);
+
+    board.make_move(1, 7);
+    assert_eq!(board.get(6), Some(Piece::new(PieceType::King, PieceColor::White)));
+    assert_eq!(board.get(5), Some(Piece::new(PieceType::Rook, PieceColor::White)));
+  }
+
+  #[test]
+  fn test_chess960_castling_king_stays() {
+    // King already on g1: only the rook moves
+    let mut board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/6KR w H - 0 1").unwrap();
+    assert_eq!(board.castling_to(6).map(|m| (m.from, m.to)), Some((6, 7)));
+
+    board.make_move(6, 7);
+    assert_eq!(board.get(6), Some(Piece::new(PieceType::King, PieceColor::White)));
+    assert_eq!(board.get(5), Some(Piece::new(PieceType::Rook, PieceColor::White)));
+    assert_eq!(board.get(7), None);
+
+    let undo = board.last_undo_info().unwrap().castling.unwrap();
+    assert_eq!((undo.king_from, undo.king_to, undo.rook_from, undo.rook_to), (6, 6, 7, 5));
+  }
+
+  #[test]
+  fn test_chess960_castling_rook_shield() {
+    // The castling rook on b1 shields the king from the a1 rook; castling would expose it
+    let board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/rRK5 w B - 0 1").unwrap();
+    assert!(!board.is_legal(2, 1));
+    assert_eq!(board.castling_to(2), None);
   }
 }
@@ -0,0 +1,758 @@
+//! Computer opponent
+//!
+//! A small alpha-beta searcher sized for the SAM3X8E: no heap and no
+//! recursion. The moves of every ply on the search path share one fixed move
+//! stack (4 KB) inside the engine, and the search runs in node-limited slices
+//! so it never holds up the caller for long. Evaluation is material plus
+//! piece-square tables. Playing strength comes from a depth and node limit.
+
+use crate::game::chess::{Move, PieceColor, PieceType, Position, UndoInfo};
+
+/// Score of a checkmate delivered at the root; mates further away score lower
+pub const MATE_SCORE: i32 = 30_000;
+
+/// Maximum number of plies searched, including the quiescence search
+pub const MAX_PLY: usize = 16;
+
+/// Strongest level accepted by `SearchLimits::level`
+pub const MAX_LEVEL: u8 = 8;
+
+/// Level used when none is chosen
+pub const DEFAULT_LEVEL: u8 = 3;
+
+const INFINITY: i32 = MATE_SCORE + 1;
+
+/// Scores beyond this are mates
+const MATE_THRESHOLD: i32 = MATE_SCORE - MAX_PLY as i32;
+
+/// How far the search may go
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SearchLimits {
+  /// Deepest iteration of the iterative deepening, in plies
+  pub depth: u8,
+  /// Node budget; when it runs out the last completed iteration is used
+  pub nodes: u32,
+}
+
+impl SearchLimits {
+  /// Limits for strength level 1 (weakest) to `MAX_LEVEL`
+  ///
+  /// Each level searches one ply deeper and may visit twice as many nodes.
+  pub fn level(level: u8) -> Self {
+    let level = level.clamp(1, MAX_LEVEL);
+    Self {
+      depth: level,
+      nodes: 500 << level,
+    }
+  }
+}
+
+impl Default for SearchLimits {
+  fn default() -> Self {
+    Self::level(DEFAULT_LEVEL)
+  }
+}
+
+/// Outcome of a search
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SearchResult {
+  /// Move to play, `None` if the side to move has no legal moves
+  pub best_move: Option<Move>,
+  /// Score in centipawns from the side to move's point of view
+  pub score: i32,
+  /// Depth of the last completed iteration
+  pub depth: u8,
+  /// Nodes visited, including quiescence nodes
+  pub nodes: u32,
+}
+
+/// Moves kept for all plies of the search path together
+const MOVE_STACK_LEN: usize = 1024;
+
+/// A node on the search path, waiting for its moves to be searched
+#[derive(Clone, Copy)]
+struct Frame {
+  /// The node's moves not yet searched are `move_stack[next..end]`
+  next: u16,
+  end: u16,
+  alpha: i32,
+  beta: i32,
+  /// Depth left, including a check extension (0 in quiescence)
+  depth: u8,
+  quiescence: bool,
+  /// Move being searched below this node, and how to unmake it
+  current: Move,
+  undo: Option<UndoInfo>,
+}
+
+impl Frame {
+  const EMPTY: Self = Self {
+    next: 0,
+    end: 0,
+    alpha: 0,
+    beta: 0,
+    depth: 0,
+    quiescence: false,
+    current: Move::new(0, 0),
+    undo: None,
+  };
+}
+
+/// Alpha-beta search with iterative deepening and quiescence search
+///
+/// The search runs in slices: `start` sets it up and each `step` call
+/// searches up to a given number of nodes, so a caller can spread it over
+/// its main loop. The search path lives in the engine rather than on the
+/// call stack.
+pub struct Engine {
+  /// Quiet moves that caused a beta cutoff, two per ply
+  killers: [[Option<Move>; 2]; MAX_PLY],
+  nodes: u32,
+  node_limit: u32,
+  stopped: bool,
+  /// A search is started and not finished
+  searching: bool,
+  /// Position at the end of the search path
+  position: Position,
+  frames: [Frame; MAX_PLY],
+  /// Frame of the node being searched (0 = root)
+  ply: usize,
+  move_stack: [Move; MOVE_STACK_LEN],
+  max_depth: u8,
+  /// Index of the best root move of the current iteration
+  best: usize,
+  result: SearchResult,
+}
+
+impl Engine {
+  pub fn new() -> Self {
+    Self {
+      killers: [[None; 2]; MAX_PLY],
+      nodes: 0,
+      node_limit: 0,
+      stopped: false,
+      searching: false,
+      position: Position::empty(),
+      frames: [Frame::EMPTY; MAX_PLY],
+      ply: 0,
+      move_stack: [Move::new(0, 0); MOVE_STACK_LEN],
+      max_depth: 0,
+      best: 0,
+      result: SearchResult {
+        best_move: None,
+        score: 0,
+        depth: 0,
+        nodes: 0,
+      },
+    }
+  }
+
+  /// Find the best move for the side to move in `position`, in one go
+  pub fn search(&mut self, position: &Position, limits: SearchLimits) -> SearchResult {
+    self.start(position, limits);
+    loop {
+      if let Some(result) = self.step(u32::MAX) {
+        return result;
+      }
+    }
+  }
+
+  /// Start a search for the side to move in `position`, dropping any search
+  /// under way; `step` does the work
+  pub fn start(&mut self, position: &Position, limits: SearchLimits) {
+    self.killers = [[None; 2]; MAX_PLY];
+    self.nodes = 0;
+    self.node_limit = limits.nodes;
+    self.stopped = false;
+    self.position = *position;
+    self.max_depth = limits.depth.clamp(1, MAX_PLY as u8 - 1);
+    self.ply = 0;
+    self.best = 0;
+
+    // The root moves always fit, as they are the first on the stack
+    let end = self.generate_moves(0, false).unwrap_or(0);
+    self.frames[0] = Frame {
+      end,
+      alpha: -INFINITY,
+      beta: INFINITY,
+      depth: 1,
+      ..Frame::EMPTY
+    };
+    self.result = SearchResult {
+      best_move: (end > 0).then_some(self.move_stack[0]),
+      score: 0,
+      depth: 0,
+      nodes: 0,
+    };
+    self.searching = end > 0;
+  }
+
+  /// Whether a search is started and not finished
+  pub fn is_searching(&self) -> bool {
+    self.searching
+  }
+
+  /// Drop the search under way, if any
+  pub fn stop(&mut self) {
+    self.searching = false;
+  }
+
+  /// Search up to `nodes` more nodes, returning the result once the search
+  /// is over (and again on later calls)
+  pub fn step(&mut self, nodes: u32) -> Option<SearchResult> {
+    if !self.searching {
+      return Some(self.result);
+    }
+    let slice_end = self.nodes.saturating_add(nodes);
+    // Score of the node just left, for its parent at `self.ply`
+    let mut returned: Option<i32> = None;
+
+    loop {
+      let ply = self.ply;
+      if let Some(child_score) = returned.take() {
+        let frame = &mut self.frames[ply];
+        if let Some(undo) = frame.undo.take() {
+          self.position.unmake_move(&undo);
+        }
+
+        if self.stopped {
+          if ply == 0 {
+            return self.finish_iteration();
+          }
+          self.ply -= 1;
+          returned = Some(0);
+          continue;
+        }
+
+        let score = -child_score;
+        if score >= frame.beta {
+          let mv = frame.current;
+          let beta = frame.beta;
+          if !frame.quiescence && !mv.is_capture() && mv.promotion.is_none() {
+            self.store_killer(mv, ply);
+          }
+          self.ply -= 1;
+          returned = Some(beta);
+          continue;
+        }
+        if score > frame.alpha {
+          frame.alpha = score;
+          if ply == 0 {
+            self.best = frame.next as usize - 1;
+          }
+        }
+      }
+
+      if self.nodes >= slice_end {
+        return None;
+      }
+
+      let frame = &mut self.frames[ply];
+      if frame.next == frame.end {
+        if ply == 0 {
+          match self.finish_iteration() {
+            Some(result) => return Some(result),
+            None => continue,
+          }
+        }
+        self.ply -= 1;
+        returned = Some(frame.alpha);
+        continue;
+      }
+
+      let mv = self.move_stack[frame.next as usize];
+      frame.next += 1;
+      let Some(undo) = self.position.make_move(mv.from, mv.to, mv.promotion) else {
+        continue;
+      };
+      frame.current = mv;
+      frame.undo = Some(undo);
+
+      let (alpha, beta, depth) = (-frame.beta, -frame.alpha, frame.depth);
+      let score = if frame.quiescence {
+        self.enter_quiescence(ply + 1, alpha, beta)
+      } else {
+        self.enter(ply + 1, depth - 1, alpha, beta)
+      };
+      match score {
+        Some(score) => returned = Some(score),
+        None => self.ply = ply + 1,
+      }
+    }
+  }
+
+  /// Enter a node at `ply` of the main search
+  ///
+  /// Returns its score if it needs no search of its own, otherwise sets up
+  /// its frame.
+  fn enter(&mut self, ply: usize, depth: u8, alpha: i32, beta: i32) -> Option<i32> {
+    let in_check = self.position.is_in_check(self.position.side_to_move());
+    // Extend checks so the search does not stop right before a mate
+    let depth = if in_check { depth + 1 } else { depth };
+    if depth == 0 || ply >= MAX_PLY - 1 {
+      return self.enter_quiescence(ply, alpha, beta);
+    }
+
+    if self.count_node() {
+      return Some(0);
+    }
+    if self.position.halfmove_clock() >= 100 {
+      return Some(0);
+    }
+
+    let Some(end) = self.generate_moves(ply, false) else {
+      // No room for the moves this deep; settle for the evaluation
+      return Some(evaluate(&self.position));
+    };
+    if end == self.moves_start(ply) {
+      return Some(if in_check {
+        -MATE_SCORE + ply as i32
+      } else {
+        0
+      });
+    }
+    self.push_frame(ply, end, depth, alpha, beta, false);
+    None
+  }
+
+  /// Enter a node at `ply` of the quiescence search, which looks at captures
+  /// and queen promotions until the position is quiet
+  fn enter_quiescence(&mut self, ply: usize, mut alpha: i32, beta: i32) -> Option<i32> {
+    if self.count_node() {
+      return Some(0);
+    }
+
+    let stand_pat = evaluate(&self.position);
+    if stand_pat >= beta {
+      return Some(beta);
+    }
+    if ply >= MAX_PLY {
+      return Some(stand_pat);
+    }
+    if stand_pat > alpha {
+      alpha = stand_pat;
+    }
+
+    match self.generate_moves(ply, true) {
+      Some(end) if end > self.moves_start(ply) => {
+        self.push_frame(ply, end, 0, alpha, beta, true);
+        None
+      }
+      _ => Some(alpha),
+    }
+  }
+
+  /// Where the moves of a node at `ply` start on the move stack
+  fn moves_start(&self, ply: usize) -> u16 {
+    if ply == 0 { 0 } else { self.frames[ply - 1].end }
+  }
+
+  /// Put the moves of the node at `ply` on the move stack, ordered
+  ///
+  /// Returns the end of its moves, or `None` if they do not fit.
+  fn generate_moves(&mut self, ply: usize, captures_only: bool) -> Option<u16> {
+    let first = self.moves_start(ply) as usize;
+    let mut end = first;
+    let mut overflow = false;
+    let stack = &mut self.move_stack;
+    let mut push = |mv| match stack.get_mut(end) {
+      Some(slot) => {
+        *slot = mv;
+        end += 1;
+      }
+      None => overflow = true,
+    };
+    if captures_only {
+      self.position.for_each_legal_capture(&mut push);
+    } else {
+      self.position.for_each_legal_move(&mut push);
+    }
+    if overflow {
+      return None;
+    }
+
+    let killers = self.killers.get(ply).copied().unwrap_or([None; 2]);
+    order_moves(&self.position, &mut self.move_stack[first..end], killers);
+    Some(end as u16)
+  }
+
+  fn push_frame(&mut self, ply: usize, end: u16, depth: u8, alpha: i32, beta: i32, quiescence: bool) {
+    self.frames[ply] = Frame {
+      next: self.moves_start(ply),
+      end,
+      alpha,
+      beta,
+      depth,
+      quiescence,
+      ..Frame::EMPTY
+    };
+  }
+
+  /// The root's moves are all searched, or the search stopped: keep the
+  /// iteration's best move and go one ply deeper, or finish
+  fn finish_iteration(&mut self) -> Option<SearchResult> {
+    let root = self.frames[0];
+    // An interrupted iteration is only trusted if nothing completed before it
+    if !(self.stopped && self.result.depth > 0) {
+      self.result.best_move = Some(self.move_stack[self.best]);
+      self.result.score = root.alpha;
+      self.result.depth = root.depth;
+    }
+
+    if self.stopped || root.alpha.abs() > MATE_THRESHOLD || root.depth >= self.max_depth {
+      self.searching = false;
+      self.result.nodes = self.nodes;
+      return Some(self.result);
+    }
+
+    // Search the best move first in the next iteration
+    self.move_stack[..=self.best].rotate_right(1);
+    self.best = 0;
+    self.frames[0] = Frame {
+      next: 0,
+      alpha: -INFINITY,
+      depth: root.depth + 1,
+      ..root
+    };
+    None
+  }
+
+  /// Count a visited node; returns true once the node budget is spent
+  fn count_node(&mut self) -> bool {
+    self.nodes += 1;
+    if self.nodes >= self.node_limit {
+      self.stopped = true;
+    }
+    self.stopped
+  }
+
+  fn store_killer(&mut self, mv: Move, ply: usize) {
+    let killers = &mut self.killers[ply];
+    if killers[0] != Some(mv) {
+      killers[1] = killers[0];
+      killers[0] = Some(mv);
+    }
+  }
+}
+
+impl Default for Engine {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// Sort moves so the most promising are searched first: winning captures
+/// (most valuable victim, least valuable attacker), promotions, killers,
+/// then quiet moves by piece-square gain
+fn order_moves(position: &Position, moves: &mut [Move], killers: [Option<Move>; 2]) {
+  moves.sort_unstable_by_key(|mv| core::cmp::Reverse(move_order_score(position, mv, killers)));
+}
+
+fn move_order_score(position: &Position, mv: &Move, killers: [Option<Move>; 2]) -> i32 {
+  let Some(piece) = position.get(mv.from) else {
+    return 0;
+  };
+
+  if mv.is_capture() {
+    let victim = match position.get(mv.to) {
+      Some(victim) if !mv.is_en_passant() => victim.piece_type,
+      _ => PieceType::Pawn,
+    };
+    return 100_000 + piece_value(victim) * 10 - piece_value(piece.piece_type) / 10;
+  }
+  if let Some(promotion) = mv.promotion {
+    return 90_000 + piece_value(promotion);
+  }
+  if killers[0] == Some(*mv) {
+    return 80_000;
+  }
+  if killers[1] == Some(*mv) {
+    return 79_000;
+  }
+
+  square_value(piece.piece_type, piece.color, mv.to, false)
+    - square_value(piece.piece_type, piece.color, mv.from, false)
+}
+
+/// Static evaluation in centipawns from the side to move's point of view
+pub fn evaluate(position: &Position) -> i32 {
+  // Kings head for the centre once the heavy pieces are gone
+  let mut non_pawn_material = 0;
+  for sq in 0..64 {
+    if let Some(piece) = position.get(sq)
+      && !matches!(piece.piece_type, PieceType::Pawn | PieceType::King)
+    {
+      non_pawn_material += piece_value(piece.piece_type);
+    }
+  }
+  let endgame =
+    non_pawn_material <= 2 * (piece_value(PieceType::Rook) + piece_value(PieceType::Bishop));
+
+  let mut score = 0;
+  for sq in 0..64 {
+    let Some(piece) = position.get(sq) else {
+      continue;
+    };
+    let value =
+      piece_value(piece.piece_type) + square_value(piece.piece_type, piece.color, sq, endgame);
+    if piece.color == position.side_to_move() {
+      score += value;
+    } else {
+      score -= value;
+    }
+  }
+  score
+}
+
+/// Material value in centipawns
+pub fn piece_value(piece_type: PieceType) -> i32 {
+  match piece_type {
+    PieceType::Pawn => 100,
+    PieceType::Knight => 320,
+    PieceType::Bishop => 330,
+    PieceType::Rook => 500,
+    PieceType::Queen => 900,
+    PieceType::King => 0,
+  }
+}
+
+fn square_value(piece_type: PieceType, color: PieceColor, sq: u8, endgame: bool) -> i32 {
+  // Tables are written from white's side with rank 8 first
+  let index = match color {
+    PieceColor::White => sq ^ 56,
+    PieceColor::Black => sq,
+  } as usize;
+
+  let table = match piece_type {
+    PieceType::Pawn => &PAWN_TABLE,
+    PieceType::Knight => &KNIGHT_TABLE,
+    PieceType::Bishop => &BISHOP_TABLE,
+    PieceType::Rook => &ROOK_TABLE,
+    PieceType::Queen => &QUEEN_TABLE,
+    PieceType::King if endgame => &KING_ENDGAME_TABLE,
+    PieceType::King => &KING_TABLE,
+  };
+  table[index] as i32
+}
+
+#[rustfmt::skip]
+const PAWN_TABLE: [i8; 64] = [
+   0,   0,   0,   0,   0,   0,   0,   0,
+  50,  50,  50,  50,  50,  50,  50,  50,
+  10,  10,  20,  30,  30,  20,  10,  10,
+   5,   5,  10,  25,  25,  10,   5,   5,
+   0,   0,   0,  20,  20,   0,   0,   0,
+   5,  -5, -10,   0,   0, -10,  -5,   5,
+   5,  10,  10, -20, -20,  10,  10,   5,
+   0,   0,   0,   0,   0,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_TABLE: [i8; 64] = [
+  -50, -40, -30, -30, -30, -30, -40, -50,
+  -40, -20,   0,   0,   0,   0, -20, -40,
+  -30,   0,  10,  15,  15,  10,   0, -30,
+  -30,   5,  15,  20,  20,  15,   5, -30,
+  -30,   0,  15,  20,  20,  15,   0, -30,
+  -30,   5,  10,  15,  15,  10,   5, -30,
+  -40, -20,   0,   5,   5,   0, -20, -40,
+  -50, -40, -30, -30, -30, -30, -40, -50,
+];
+
+#[rustfmt::skip]
+const BISHOP_TABLE: [i8; 64] = [
+  -20, -10, -10, -10, -10, -10, -10, -20,
+  -10,   0,   0,   0,   0,   0,   0, -10,
+  -10,   0,   5,  10,  10,   5,   0, -10,
+  -10,   5,   5,  10,  10,   5,   5, -10,
+  -10,   0,  10,  10,  10,  10,   0, -10,
+  -10,  10,  10,  10,  10,  10,  10, -10,
+  -10,   5,   0,   0,   0,   0,   5, -10,
+  -20, -10, -10, -10, -10, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const ROOK_TABLE: [i8; 64] = [
+   0,   0,   0,   0,   0,   0,   0,   0,
+   5,  10,  10,  10,  10,  10,  10,   5,
+  -5,   0,   0,   0,   0,   0,   0,  -5,
+  -5,   0,   0,   0,   0,   0,   0,  -5,
+  -5,   0,   0,   0,   0,   0,   0,  -5,
+  -5,   0,   0,   0,   0,   0,   0,  -5,
+  -5,   0,   0,   0,   0,   0,   0,  -5,
+   0,   0,   0,   5,   5,   0,   0,   0,
+];
+
+#[rustfmt::skip]
+const QUEEN_TABLE: [i8; 64] = [
+  -20, -10, -10,  -5,  -5, -10, -10, -20,
+  -10,   0,   0,   0,   0,   0,   0, -10,
+  -10,   0,   5,   5,   5,   5,   0, -10,
+   -5,   0,   5,   5,   5,   5,   0,  -5,
+    0,   0,   5,   5,   5,   5,   0,  -5,
+  -10,   5,   5,   5,   5,   5,   0, -10,
+  -10,   0,   5,   0,   0,   0,   0, -10,
+  -20, -10, -10,  -5,  -5, -10, -10, -20,
+];
+
+#[rustfmt::skip]
+const KING_TABLE: [i8; 64] = [
+  -30, -40, -40, -50, -50, -40, -40, -30,
+  -30, -40, -40, -50, -50, -40, -40, -30,
+  -30, -40, -40, -50, -50, -40, -40, -30,
+  -30, -40, -40, -50, -50, -40, -40, -30,
+  -20, -30, -30, -40, -40, -30, -30, -20,
+  -10, -20, -20, -20, -20, -20, -20, -10,
+   20,  20,   0,   0,   0,   0,  20,  20,
+   20,  30,  10,   0,   0,  10,  30,  20,
+];
+
+#[rustfmt::skip]
+const KING_ENDGAME_TABLE: [i8; 64] = [
+  -50, -40, -30, -20, -20, -30, -40, -50,
+  -30, -20, -10,   0,   0, -10, -20, -30,
+  -30, -10,  20,  30,  30,  20, -10, -30,
+  -30, -10,  30,  40,  40,  30, -10, -30,
+  -30, -10,  30,  40,  40,  30, -10, -30,
+  -30, -10,  20,  30,  30,  20, -10, -30,
+  -30, -30,   0,   0,   0,   0, -30, -30,
+  -50, -30, -30, -30, -30, -30, -30, -50,
+];
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn search(fen: &str, limits: SearchLimits) -> SearchResult {
+    let position = Position::from_fen(fen).unwrap();
+    Engine::new().search(&position, limits)
+  }
+
+  #[test]
+  fn test_starting_position_is_balanced() {
+    let position = Position::starting_position();
+    assert_eq!(evaluate(&position), 0);
+  }
+
+  #[test]
+  fn test_evaluation_is_side_relative() {
+    // White is a queen up
+    let white = Position::from_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1").unwrap();
+    let black = Position::from_fen("4k3/8/8/8/8/8/8/3QK3 b - - 0 1").unwrap();
+    assert!(evaluate(&white) > 800);
+    assert_eq!(evaluate(&white), -evaluate(&black));
+  }
+
+  #[test]
+  fn test_finds_mate_in_one() {
+    let result = search("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1", SearchLimits::level(3));
+    let best = result.best_move.unwrap();
+    assert_eq!((best.from, best.to), (0, 56));
+    assert_eq!(result.score, MATE_SCORE - 1);
+  }
+
+  #[test]
+  fn test_avoids_mate_in_one() {
+    // Black must make luft or cover the back rank
+    let fen = "6k1/5ppp/8/8/8/8/8/R5K1 b - - 0 1";
+    let best = search(fen, SearchLimits::level(3)).best_move.unwrap();
+    let mut after = Position::from_fen(fen).unwrap();
+    after.make_move(best.from, best.to, best.promotion).unwrap();
+    let reply = Engine::new().search(&after, SearchLimits::level(1));
+    assert!(reply.score < MATE_THRESHOLD);
+  }
+
+  #[test]
+  fn test_captures_hanging_queen() {
+    let result = search("4k3/8/8/3q4/8/8/3R4/4K3 w - - 0 1", SearchLimits::level(2));
+    let best = result.best_move.unwrap();
+    assert_eq!((best.from, best.to), (11, 35));
+  }
+
+  #[test]
+  fn test_quiescence_sees_recapture() {
+    // Rxd5 wins a pawn but loses the rook to exd5
+    let result = search(
+      "4k3/8/4p3/3p4/8/8/3R4/4K3 w - - 0 1",
+      SearchLimits::level(1),
+    );
+    let best = result.best_move.unwrap();
+    assert_ne!((best.from, best.to), (11, 35));
+  }
+
+  #[test]
+  fn test_promotes_pawn() {
+    let result = search("8/4P1k1/8/8/8/8/8/4K3 w - - 0 1", SearchLimits::level(2));
+    let best = result.best_move.unwrap();
+    assert_eq!(
+      (best.from, best.to, best.promotion),
+      (52, 60, Some(PieceType::Queen))
+    );
+  }
+
+  #[test]
+  fn test_no_legal_moves() {
+    let result = search("7k/5Q2/6K1/8/8/8/8/8 b - - 0 1", SearchLimits::level(3));
+    assert_eq!(result.best_move, None);
+  }
+
+  #[test]
+  fn test_node_limit_stops_search() {
+    let limits = SearchLimits {
+      depth: 10,
+      nodes: 2_000,
+    };
+    let result = search(crate::game::chess::STARTING_FEN, limits);
+    assert!(result.best_move.is_some());
+    assert!(result.nodes <= limits.nodes);
+    assert!(result.depth < 10);
+  }
+
+  #[test]
+  fn test_search_in_slices_matches_search() {
+    let position = Position::from_fen("4k3/8/4p3/3p4/8/8/3R4/4K3 w - - 0 1").unwrap();
+    let limits = SearchLimits::level(4);
+    let whole = Engine::new().search(&position, limits);
+
+    let mut engine = Engine::new();
+    engine.start(&position, limits);
+    let mut slices = 1;
+    let sliced = loop {
+      if let Some(result) = engine.step(50) {
+        break result;
+      }
+      assert!(engine.is_searching());
+      slices += 1;
+    };
+    assert!(slices > 1);
+    assert!(!engine.is_searching());
+    assert_eq!(sliced, whole);
+  }
+
+  #[test]
+  fn test_stop_drops_search() {
+    let mut engine = Engine::new();
+    engine.start(&Position::starting_position(), SearchLimits::level(5));
+    assert_eq!(engine.step(10), None);
+    engine.stop();
+    assert!(!engine.is_searching());
+
+    // A new search starts from scratch
+    let position = Position::from_fen("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1").unwrap();
+    engine.start(&position, SearchLimits::level(3));
+    let best = engine.step(u32::MAX).unwrap().best_move.unwrap();
+    assert_eq!((best.from, best.to), (0, 56));
+  }
+
+  #[test]
+  fn test_levels_grow_stronger() {
+    let mut previous = SearchLimits::level(1);
+    for level in 2..=MAX_LEVEL {
+      let limits = SearchLimits::level(level);
+      assert!(limits.depth > previous.depth && limits.nodes > previous.nodes);
+      previous = limits;
+    }
+    assert_eq!(SearchLimits::level(0), SearchLimits::level(1));
+    assert_eq!(SearchLimits::level(99), SearchLimits::level(MAX_LEVEL));
+  }
+}
@@ -1,7 +1,11 @@
 //! Chess game state management
 //!
-//! Handles game state, move validation, and chess timer.
+//! Handles game state, move validation, chess timer and the computer opponent.
 
 pub mod chess;
+pub mod engine;
+pub mod pgn;
+pub mod san;
 pub mod state;
 pub mod timer;
+mod zobrist;
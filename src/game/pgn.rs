@@ -0,0 +1,774 @@
+//! PGN (Portable Game Notation) export and import
+//!
+//! Writes the Seven Tag Roster and SAN movetext for a `GameState`, either
+//! to any `core::fmt::Write` sink or into a caller-provided buffer, and
+//! reads the moves of a PGN game back, into a `PgnGame` or one at a time
+//! with a `PgnReader`. Movetext can carry the clock after each move as
+//! `{[%clk 0:04:32] [%emt 0:00:05]}` comments.
+
+use core::fmt::{self, Write};
+
+use crate::game::chess::{BoardStatus, ChessBoard, MAX_FEN_LEN, PieceColor, Position, STARTING_FEN};
+use crate::game::san::{San, parse_san};
+use crate::game::state::{ClockRecord, GameState, GameStatus, MAX_GAME_MOVES, MoveRecord};
+
+/// Maximum line length for PGN export format
+const MAX_LINE_LEN: usize = 79;
+
+/// Descriptive tags of the Seven Tag Roster (Result is derived from the game)
+#[derive(Clone, Copy, Debug)]
+pub struct PgnTags<'a> {
+  pub event: &'a str,
+  pub site: &'a str,
+  /// Date in "YYYY.MM.DD" format, with "??" for unknown parts
+  pub date: &'a str,
+  pub round: &'a str,
+  pub white: &'a str,
+  pub black: &'a str,
+}
+
+impl Default for PgnTags<'_> {
+  fn default() -> Self {
+    Self {
+      event: "?",
+      site: "?",
+      date: "????.??.??",
+      round: "?",
+      white: "?",
+      black: "?",
+    }
+  }
+}
+
+/// Errors that can occur while exporting or importing PGN
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PgnError {
+  /// The output buffer is too small for the game
+  BufferTooSmall,
+  /// Malformed tag pair, comment or token
+  InvalidSyntax,
+  /// The FEN tag does not describe a valid position
+  InvalidFen,
+  /// A move in the movetext is illegal or ambiguous (`ply` counts from 0)
+  IllegalMove { ply: u16 },
+  /// The game has more than `MAX_GAME_MOVES` half-moves
+  TooManyMoves,
+}
+
+/// Get the PGN result string ("1-0", "0-1", "1/2-1/2" or "*") for a game
+pub fn result_str(game: &GameState) -> &'static str {
+  match game.status() {
+    GameStatus::WhiteWins => "1-0",
+    GameStatus::BlackWins => "0-1",
+    GameStatus::Draw => "1/2-1/2",
+    _ => match game.board_status() {
+      BoardStatus::Ongoing => "*",
+      BoardStatus::Checkmate => match game.board().side_to_move() {
+        PieceColor::White => "0-1",
+        PieceColor::Black => "1-0",
+      },
+      _ => "1/2-1/2",
+    },
+  }
+}
+
+/// Write a game as PGN
+///
+/// Games that did not start from the standard position also get
+/// `SetUp` and `FEN` tags, plus `Variant` for Chess960.
+pub fn write_pgn<W: Write>(out: &mut W, game: &GameState, tags: &PgnTags) -> fmt::Result {
+  write_game(out, game, tags, false)
+}
+
+/// Write a game as PGN with the mover's remaining (`%clk`) and elapsed
+/// (`%emt`) time in a comment after each move
+pub fn write_pgn_with_clocks<W: Write>(out: &mut W, game: &GameState, tags: &PgnTags) -> fmt::Result {
+  write_game(out, game, tags, true)
+}
+
+fn write_game<W: Write>(out: &mut W, game: &GameState, tags: &PgnTags, clocks: bool) -> fmt::Result {
+  let result = result_str(game);
+
+  write_tag(out, "Event", tags.event)?;
+  write_tag(out, "Site", tags.site)?;
+  write_tag(out, "Date", tags.date)?;
+  write_tag(out, "Round", tags.round)?;
+  write_tag(out, "White", tags.white)?;
+  write_tag(out, "Black", tags.black)?;
+  write_tag(out, "Result", result)?;
+
+  let mut fen_buf = [0u8; MAX_FEN_LEN];
+  let fen = game.initial_position().to_fen(&mut fen_buf);
+  if game.initial_position().is_chess960() {
+    write_tag(out, "Variant", "Chess960")?;
+  }
+  if fen != STARTING_FEN {
+    write_tag(out, "SetUp", "1")?;
+    write_tag(out, "FEN", fen)?;
+  }
+  out.write_str("\n")?;
+
+  let mut movetext = MovetextWriter { out, column: 0 };
+  let mut board = ChessBoard::with_position(*game.initial_position());
+  let mut number_buf = [0u8; 8];
+  let mut comment_buf = [0u8; MAX_CLOCK_COMMENT_LEN];
+
+  // A game starting with black to move opens with "N..."
+  if board.side_to_move() == PieceColor::Black {
+    movetext.token(move_number(&mut number_buf, board.fullmove_number(), "..."))?;
+  }
+
+  let mut clock_history = game.clock_history();
+  for mv in game.move_history() {
+    if board.side_to_move() == PieceColor::White {
+      movetext.token(move_number(&mut number_buf, board.fullmove_number(), "."))?;
+    }
+    let san = San::from_move(&board, mv.from, mv.to, mv.promotion);
+    movetext.token(san.as_str())?;
+    let clock = clock_history.next();
+    if clocks && let Some(clock) = clock {
+      movetext.token(clock_comment(&mut comment_buf, &clock))?;
+    }
+    board.make_move_with_promotion(mv.from, mv.to, mv.promotion);
+  }
+
+  movetext.token(result)?;
+  out.write_str("\n")
+}
+
+/// Write a game as PGN into `buf`, returning the written text
+pub fn write_pgn_to_buffer<'a>(
+  buf: &'a mut [u8],
+  game: &GameState,
+  tags: &PgnTags,
+) -> Result<&'a str, PgnError> {
+  let mut writer = BufferWriter { buf, len: 0 };
+  write_pgn(&mut writer, game, tags).map_err(|_| PgnError::BufferTooSmall)?;
+  let len = writer.len;
+  core::str::from_utf8(&buf[..len]).map_err(|_| PgnError::BufferTooSmall)
+}
+
+fn write_tag<W: Write>(out: &mut W, name: &str, value: &str) -> fmt::Result {
+  out.write_str("[")?;
+  out.write_str(name)?;
+  out.write_str(" \"")?;
+  for c in value.chars() {
+    if c == '"' || c == '\\' {
+      out.write_char('\\')?;
+    }
+    out.write_char(c)?;
+  }
+  out.write_str("\"]\n")
+}
+
+/// Format a move number with its suffix ("12." or "12...")
+fn move_number<'a>(buf: &'a mut [u8; 8], number: u16, suffix: &str) -> &'a str {
+  let mut digits = [0u8; 5];
+  let mut n = number;
+  let mut count = 0;
+  loop {
+    digits[count] = b'0' + (n % 10) as u8;
+    count += 1;
+    n /= 10;
+    if n == 0 {
+      break;
+    }
+  }
+
+  let mut len = 0;
+  for &digit in digits[..count].iter().rev() {
+    buf[len] = digit;
+    len += 1;
+  }
+  for &byte in suffix.as_bytes() {
+    buf[len] = byte;
+    len += 1;
+  }
+  core::str::from_utf8(&buf[..len]).unwrap_or("")
+}
+
+/// Length of "{[%clk 1193:02:47] [%emt 1193:02:47]}"
+const MAX_CLOCK_COMMENT_LEN: usize = 38;
+
+/// Format a move's clock comment, e.g. "{[%clk 0:04:32] [%emt 0:00:05]}"
+fn clock_comment<'a>(buf: &'a mut [u8; MAX_CLOCK_COMMENT_LEN], clock: &ClockRecord) -> &'a str {
+  let mut writer = BufferWriter { buf: &mut buf[..], len: 0 };
+  let written = write!(
+    writer,
+    "{{[%clk {}] [%emt {}]}}",
+    ClockTime(clock.remaining_ms),
+    ClockTime(clock.elapsed_ms)
+  );
+  let len = if written.is_ok() { writer.len } else { 0 };
+  core::str::from_utf8(&buf[..len]).unwrap_or("")
+}
+
+/// Milliseconds formatted as "H:MM:SS" (whole seconds)
+struct ClockTime(u32);
+
+impl fmt::Display for ClockTime {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let seconds = self.0 / 1000;
+    write!(f, "{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+  }
+}
+
+/// Writes space-separated movetext tokens, wrapping lines at `MAX_LINE_LEN`
+struct MovetextWriter<'a, W: Write> {
+  out: &'a mut W,
+  column: usize,
+}
+
+impl<W: Write> MovetextWriter<'_, W> {
+  fn token(&mut self, token: &str) -> fmt::Result {
+    if self.column > 0 {
+      if self.column + 1 + token.len() > MAX_LINE_LEN {
+        self.out.write_str("\n")?;
+        self.column = 0;
+      } else {
+        self.out.write_str(" ")?;
+        self.column += 1;
+      }
+    }
+    self.out.write_str(token)?;
+    self.column += token.len();
+    Ok(())
+  }
+}
+
+/// `fmt::Write` adapter over a byte slice
+struct BufferWriter<'a> {
+  buf: &'a mut [u8],
+  len: usize,
+}
+
+impl Write for BufferWriter<'_> {
+  fn write_str(&mut self, s: &str) -> fmt::Result {
+    let end = self.len + s.len();
+    if end > self.buf.len() {
+      return Err(fmt::Error);
+    }
+    self.buf[self.len..end].copy_from_slice(s.as_bytes());
+    self.len = end;
+    Ok(())
+  }
+}
+
+/// Moves of an imported PGN game
+pub struct PgnGame {
+  /// Position the game starts from (standard position unless a FEN tag is given)
+  initial_position: Position,
+  moves: [Option<MoveRecord>; MAX_GAME_MOVES],
+  move_count: u16,
+}
+
+impl PgnGame {
+  /// Position the game starts from
+  pub fn initial_position(&self) -> &Position {
+    &self.initial_position
+  }
+
+  /// Number of half-moves in the game
+  pub fn len(&self) -> u16 {
+    self.move_count
+  }
+
+  /// Whether the game has no moves
+  pub fn is_empty(&self) -> bool {
+    self.move_count == 0
+  }
+
+  /// Get the half-move at `ply` (0 = first move)
+  pub fn get(&self, ply: u16) -> Option<MoveRecord> {
+    self.moves.get(ply as usize).copied().flatten()
+  }
+
+  /// Iterate over the moves in order
+  pub fn moves(&self) -> impl Iterator<Item = MoveRecord> + '_ {
+    self.moves.iter().map_while(|m| *m)
+  }
+}
+
+/// Parse the first game of a PGN file into its moves
+///
+/// Tag pairs other than `FEN` are skipped, as are comments, NAGs and
+/// variations. Every SAN move is validated against the position.
+pub fn parse_pgn(pgn: &[u8]) -> Result<PgnGame, PgnError> {
+  let (mut reader, initial_position) = PgnReader::new(pgn)?;
+  let mut game = PgnGame {
+    initial_position,
+    moves: [None; MAX_GAME_MOVES],
+    move_count: 0,
+  };
+  let mut board = ChessBoard::with_position(initial_position);
+
+  while let Some(mv) = reader.next_move(&board)? {
+    let slot = game.moves.get_mut(game.move_count as usize).ok_or(PgnError::TooManyMoves)?;
+    *slot = Some(mv);
+    board.make_move_with_promotion(mv.from, mv.to, mv.promotion);
+    game.move_count += 1;
+  }
+
+  Ok(game)
+}
+
+/// Check that every move of the first game of a PGN file is legal
+///
+/// Returns the position the game starts from. Unlike `parse_pgn` the moves
+/// are not kept, so games of any length pass.
+pub fn check_pgn(pgn: &[u8]) -> Result<Position, PgnError> {
+  let (mut reader, initial_position) = PgnReader::new(pgn)?;
+  let mut board = ChessBoard::with_position(initial_position);
+  while let Some(mv) = reader.next_move(&board)? {
+    board.make_move_with_promotion(mv.from, mv.to, mv.promotion);
+  }
+  Ok(initial_position)
+}
+
+/// Reads the moves of a PGN game one at a time, straight from its text
+///
+/// Only the place in the text is kept, so a game can be followed from a
+/// borrowed buffer without holding its moves. The game ends at its result,
+/// at the tag pairs of a following game, or at the end of the text.
+#[derive(Clone, Copy, Debug)]
+pub struct PgnReader<'a> {
+  pgn: &'a [u8],
+  pos: usize,
+  /// Half-moves read so far
+  ply: u16,
+}
+
+impl<'a> PgnReader<'a> {
+  /// Read the tag pairs of the first game in `pgn`
+  ///
+  /// Returns the reader, placed before the first move, and the position the
+  /// game starts from (standard position unless a FEN tag is given).
+  pub fn new(pgn: &'a [u8]) -> Result<(Self, Position), PgnError> {
+    let mut reader = Self { pgn, pos: 0, ply: 0 };
+    let mut position = Position::starting_position();
+
+    while reader.skip_to_token()? == Some(b'[') {
+      let (name, value, end) = parse_tag(pgn, reader.pos)?;
+      if name == b"FEN" {
+        let fen = core::str::from_utf8(value).map_err(|_| PgnError::InvalidFen)?;
+        position = Position::from_fen(fen).map_err(|_| PgnError::InvalidFen)?;
+      }
+      reader.pos = end;
+    }
+
+    Ok((reader, position))
+  }
+
+  /// Half-moves read so far
+  pub fn ply(&self) -> u16 {
+    self.ply
+  }
+
+  /// Read the next move, validating it against `board`, the position after
+  /// the moves read so far
+  ///
+  /// Returns `None` at the end of the game.
+  pub fn next_move(&mut self, board: &ChessBoard) -> Result<Option<MoveRecord>, PgnError> {
+    let Some(san) = self.next_san()? else {
+      return Ok(None);
+    };
+    let ply = self.ply;
+    let (from, to, promotion) = parse_san(board, san).map_err(|_| PgnError::IllegalMove { ply })?;
+    self.ply += 1;
+    Ok(Some(MoveRecord { from, to, promotion }))
+  }
+
+  /// Skip the next move without validating it
+  ///
+  /// Returns `false` at the end of the game.
+  pub fn skip_move(&mut self) -> Result<bool, PgnError> {
+    let found = self.next_san()?.is_some();
+    if found {
+      self.ply += 1;
+    }
+    Ok(found)
+  }
+
+  /// Skip whitespace, comments, escape lines and variations, returning the
+  /// byte that starts the next token
+  fn skip_to_token(&mut self) -> Result<Option<u8>, PgnError> {
+    let pgn = self.pgn;
+    while self.pos < pgn.len() {
+      let pos = self.pos;
+      self.pos = match pgn[pos] {
+        b' ' | b'\t' | b'\r' | b'\n' => pos + 1,
+        b'{' => skip_past(pgn, pos, b'}').ok_or(PgnError::InvalidSyntax)?,
+        b';' => skip_past(pgn, pos, b'\n').unwrap_or(pgn.len()),
+        // Escape lines start with '%'
+        b'%' if pos == 0 || pgn[pos - 1] == b'\n' => skip_past(pgn, pos, b'\n').unwrap_or(pgn.len()),
+        b'(' => skip_variation(pgn, pos)?,
+        byte => return Ok(Some(byte)),
+      };
+    }
+    Ok(None)
+  }
+
+  /// SAN of the next move, without its move number
+  ///
+  /// The reader stays put at the end of the game, so later calls return
+  /// `None` as well.
+  fn next_san(&mut self) -> Result<Option<&'a str>, PgnError> {
+    let pgn = self.pgn;
+    loop {
+      match self.skip_to_token()? {
+        // Tag pairs start the next game
+        None | Some(b'[') => return Ok(None),
+        Some(_) => {}
+      }
+
+      let start = self.pos;
+      let mut end = start;
+      while end < pgn.len() && !is_token_end(pgn[end]) {
+        end += 1;
+      }
+      if end == start {
+        return Err(PgnError::InvalidSyntax);
+      }
+      let token = core::str::from_utf8(&pgn[start..end]).map_err(|_| PgnError::InvalidSyntax)?;
+
+      if matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*") {
+        return Ok(None);
+      }
+      self.pos = end;
+      if token.starts_with('$') {
+        continue;
+      }
+      // Move numbers ("12." or "12...") may be glued to the move
+      let san = match token.rfind('.') {
+        Some(dot) if token.as_bytes()[0].is_ascii_digit() => &token[dot + 1..],
+        _ => token,
+      };
+      if !san.is_empty() {
+        return Ok(Some(san));
+      }
+    }
+  }
+}
+
+/// Parse a `[Name "value"]` tag pair starting at `start`
+///
+/// Returns the name, the raw (still escaped) value and the position after
+/// the closing bracket.
+fn parse_tag(pgn: &[u8], start: usize) -> Result<(&[u8], &[u8], usize), PgnError> {
+  let mut pos = start + 1;
+  let name_start = pos;
+  while pos < pgn.len() && (pgn[pos].is_ascii_alphanumeric() || pgn[pos] == b'_') {
+    pos += 1;
+  }
+  let name = &pgn[name_start..pos];
+
+  while pos < pgn.len() && pgn[pos] == b' ' {
+    pos += 1;
+  }
+  if name.is_empty() || pgn.get(pos) != Some(&b'"') {
+    return Err(PgnError::InvalidSyntax);
+  }
+  pos += 1;
+
+  let value_start = pos;
+  while pos < pgn.len() && pgn[pos] != b'"' {
+    if pgn[pos] == b'\\' {
+      pos += 1;
+    }
+    pos += 1;
+  }
+  if pos >= pgn.len() {
+    return Err(PgnError::InvalidSyntax);
+  }
+  let value = &pgn[value_start..pos];
+  pos += 1;
+
+  while pos < pgn.len() && pgn[pos] == b' ' {
+    pos += 1;
+  }
+  if pgn.get(pos) != Some(&b']') {
+    return Err(PgnError::InvalidSyntax);
+  }
+
+  Ok((name, value, pos + 1))
+}
+
+/// Position just after the next `end` byte at or after `start`
+fn skip_past(pgn: &[u8], start: usize, end: u8) -> Option<usize> {
+  pgn[start..].iter().position(|&b| b == end).map(|offset| start + offset + 1)
+}
+
+/// Skip a (possibly nested) variation starting at `start`
+fn skip_variation(pgn: &[u8], start: usize) -> Result<usize, PgnError> {
+  let mut depth = 0;
+  let mut pos = start;
+  while pos < pgn.len() {
+    match pgn[pos] {
+      b'(' => depth += 1,
+      b')' => {
+        depth -= 1;
+        if depth == 0 {
+          return Ok(pos + 1);
+        }
+      }
+      b'{' => {
+        pos = skip_past(pgn, pos, b'}').ok_or(PgnError::InvalidSyntax)?;
+        continue;
+      }
+      _ => {}
+    }
+    pos += 1;
+  }
+  Err(PgnError::InvalidSyntax)
+}
+
+fn is_token_end(byte: u8) -> bool {
+  byte.is_ascii_whitespace() || matches!(byte, b'[' | b']' | b'{' | b'}' | b'(' | b')' | b';')
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::game::chess::PieceType;
+  use crate::game::timer::TimeControl;
+
+  fn play(game: &mut GameState, moves: &[(u8, u8)]) {
+    game.set_status(GameStatus::InProgress);
+    for &(from, to) in moves {
+      assert!(game.make_move(from, to));
+    }
+  }
+
+  #[test]
+  fn test_in_progress_game() {
+    let mut game = GameState::new();
+    play(&mut game, &[(12, 28), (52, 36), (6, 21)]);
+
+    let tags = PgnTags {
+      event: "Club night",
+      white: "Alice",
+      black: "Bob \"The Rook\"",
+      ..PgnTags::default()
+    };
+    let mut buf = [0u8; 512];
+    let pgn = write_pgn_to_buffer(&mut buf, &game, &tags).unwrap();
+
+    assert_eq!(
+      pgn,
+      "[Event \"Club night\"]\n\
+       [Site \"?\"]\n\
+       [Date \"????.??.??\"]\n\
+       [Round \"?\"]\n\
+       [White \"Alice\"]\n\
+       [Black \"Bob \\\"The Rook\\\"\"]\n\
+       [Result \"*\"]\n\
+       \n\
+       1. e4 e5 2. Nf3 *\n"
+    );
+  }
+
+  #[test]
+  fn test_checkmate_result() {
+    let mut game = GameState::new();
+    // Scholar's mate
+    play(
+      &mut game,
+      &[
+        (12, 28),
+        (52, 36),
+        (3, 21),
+        (57, 42),
+        (5, 26),
+        (48, 40),
+        (21, 53),
+      ],
+    );
+
+    assert_eq!(result_str(&game), "1-0");
+
+    let mut buf = [0u8; 512];
+    let pgn = write_pgn_to_buffer(&mut buf, &game, &PgnTags::default()).unwrap();
+    assert!(pgn.contains("[Result \"1-0\"]"));
+    assert!(pgn.ends_with("1. e4 e5 2. Qf3 Nc6 3. Bc4 a6 4. Qxf7# 1-0\n"));
+  }
+
+  #[test]
+  fn test_result_from_game_status() {
+    let mut game = GameState::new();
+    game.set_status(GameStatus::BlackWins);
+    assert_eq!(result_str(&game), "0-1");
+    game.set_status(GameStatus::Draw);
+    assert_eq!(result_str(&game), "1/2-1/2");
+
+    let game = GameState::from_fen("4k3/8/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(result_str(&game), "1/2-1/2");
+  }
+
+  #[test]
+  fn test_custom_start_position() {
+    let mut game = GameState::from_fen("4k3/1P6/8/8/8/8/8/4K3 b - - 0 40").unwrap();
+    game.set_status(GameStatus::InProgress);
+    assert!(game.make_move(60, 59));
+    assert!(game.make_move_with_promotion(49, 57, Some(PieceType::Queen)));
+
+    let mut buf = [0u8; 512];
+    let pgn = write_pgn_to_buffer(&mut buf, &game, &PgnTags::default()).unwrap();
+    assert!(pgn.contains("[SetUp \"1\"]\n[FEN \"4k3/1P6/8/8/8/8/8/4K3 b - - 0 40\"]\n"));
+    assert!(pgn.ends_with("\n40... Kd8 41. b8=Q+ *\n"));
+  }
+
+  #[test]
+  fn test_line_wrapping() {
+    let mut game = GameState::new();
+    // Shuffle knights back and forth
+    for _ in 0..10 {
+      play(&mut game, &[(6, 21), (62, 45), (21, 6), (45, 62)]);
+    }
+
+    let mut buf = [0u8; 2048];
+    let pgn = write_pgn_to_buffer(&mut buf, &game, &PgnTags::default()).unwrap();
+    assert!(pgn.lines().all(|line| line.len() <= MAX_LINE_LEN));
+    assert!(pgn.lines().filter(|line| !line.starts_with('[')).count() > 2);
+  }
+
+  #[test]
+  fn test_buffer_too_small() {
+    let game = GameState::new();
+    let mut buf = [0u8; 32];
+    assert_eq!(
+      write_pgn_to_buffer(&mut buf, &game, &PgnTags::default()),
+      Err(PgnError::BufferTooSmall)
+    );
+  }
+
+  #[test]
+  fn test_parse_pgn() {
+    let pgn = b"[Event \"Casual \\\"Game\\\"\"]\n\
+      [White \"Anderssen\"]\n\
+      \n\
+      1. e4 {King's pawn} e5 2. Nf3 $1 (2. f4 exf4 (2... d5)) Nc6\n\
+      ; rest of line comment\n\
+      3.Bb5 a6 4. O-O 1-0\n";
+    let game = parse_pgn(pgn).unwrap();
+
+    assert_eq!(game.len(), 7);
+    assert_eq!(game.get(0), Some(MoveRecord { from: 12, to: 28, promotion: None }));
+    assert_eq!(game.get(4), Some(MoveRecord { from: 5, to: 33, promotion: None }));
+    assert_eq!(game.get(6), Some(MoveRecord { from: 4, to: 6, promotion: None }));
+    assert_eq!(game.get(7), None);
+    assert_eq!(game.moves().count(), 7);
+  }
+
+  #[test]
+  fn test_parse_pgn_with_fen() {
+    let pgn = b"[SetUp \"1\"]\n[FEN \"4k3/1P6/8/8/8/8/8/4K3 b - - 0 40\"]\n\n40... Kd8 41. b8=R *";
+    let game = parse_pgn(pgn).unwrap();
+
+    let mut fen_buf = [0u8; MAX_FEN_LEN];
+    assert_eq!(game.initial_position().to_fen(&mut fen_buf), "4k3/1P6/8/8/8/8/8/4K3 b - - 0 40");
+    assert_eq!(
+      game.get(1),
+      Some(MoveRecord { from: 49, to: 57, promotion: Some(PieceType::Rook) })
+    );
+  }
+
+  #[test]
+  fn test_parse_pgn_errors() {
+    assert_eq!(parse_pgn(b"1. e4 e5 2. Ke3").err(), Some(PgnError::IllegalMove { ply: 2 }));
+    assert_eq!(parse_pgn(b"[Event \"Unterminated]").err(), Some(PgnError::InvalidSyntax));
+    assert_eq!(parse_pgn(b"1. e4 {open comment").err(), Some(PgnError::InvalidSyntax));
+    assert_eq!(parse_pgn(b"1. e4 (1. d4").err(), Some(PgnError::InvalidSyntax));
+    assert_eq!(parse_pgn(b"[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"]").err(), Some(PgnError::InvalidFen));
+  }
+
+  #[test]
+  fn test_pgn_reader() {
+    let pgn = b"[Event \"First\"]\n\n1. e4 e5 {reply} 2. Nf3 1-0\n\n[Event \"Second\"]\n\n1. d4 *";
+    let (mut reader, position) = PgnReader::new(pgn).unwrap();
+    assert_eq!(position.to_fen(&mut [0u8; MAX_FEN_LEN]), STARTING_FEN);
+
+    let mut board = ChessBoard::starting_position();
+    let mv = reader.next_move(&board).unwrap().unwrap();
+    assert_eq!(mv, MoveRecord { from: 12, to: 28, promotion: None });
+    board.make_move(mv.from, mv.to);
+
+    // Moves are skipped unchecked, then read against the board again
+    assert!(reader.skip_move().unwrap());
+    assert_eq!(reader.ply(), 2);
+    board.make_move(52, 36);
+    assert_eq!(reader.next_move(&board).unwrap(), Some(MoveRecord { from: 6, to: 21, promotion: None }));
+
+    // The result ends the game, and the next game is not read into it
+    assert_eq!(reader.next_move(&board).unwrap(), None);
+    assert!(!reader.skip_move().unwrap());
+    assert_eq!(reader.ply(), 3);
+  }
+
+  #[test]
+  fn test_check_pgn() {
+    let pgn = b"[FEN \"4k3/1P6/8/8/8/8/8/4K3 b - - 0 40\"]\n\n40... Kd8 41. b8=R *";
+    let position = check_pgn(pgn).unwrap();
+    assert_eq!(position.side_to_move(), PieceColor::Black);
+    assert_eq!(check_pgn(b"1. e4 e5 2. Ke3").err(), Some(PgnError::IllegalMove { ply: 2 }));
+  }
+
+  #[test]
+  fn test_export_import_roundtrip() {
+    let mut game = GameState::new();
+    play(&mut game, &[(12, 28), (51, 35), (28, 35), (59, 35), (1, 18), (35, 59)]);
+
+    let mut buf = [0u8; 512];
+    let pgn = write_pgn_to_buffer(&mut buf, &game, &PgnTags::default()).unwrap();
+    let imported = parse_pgn(pgn.as_bytes()).unwrap();
+
+    assert!(imported.moves().eq(game.move_history()));
+  }
+
+  #[test]
+  fn test_clock_comments() {
+    let mut game = GameState::new();
+    game.set_time_control(TimeControl::minutes(90, 0));
+    game.set_status(GameStatus::InProgress);
+    game.tick_timer(5_000);
+    assert!(game.make_move(12, 28));
+    game.tick_timer(3_723_000);
+    assert!(game.make_move(52, 36));
+
+    let mut buf = [0u8; 512];
+    let mut writer = BufferWriter { buf: &mut buf, len: 0 };
+    write_pgn_with_clocks(&mut writer, &game, &PgnTags::default()).unwrap();
+    let len = writer.len;
+    let pgn = core::str::from_utf8(&buf[..len]).unwrap();
+
+    assert!(pgn.contains("\n1. e4 {[%clk 1:29:55] [%emt 0:00:05]} e5"));
+    assert!(pgn.ends_with(" {[%clk 0:27:57] [%emt 1:02:03]} *\n"));
+
+    // Clock comments are skipped on import
+    let imported = parse_pgn(pgn.as_bytes()).unwrap();
+    assert!(imported.moves().eq(game.move_history()));
+  }
+
+  #[test]
+  fn test_clock_time_format() {
+    let mut buf = [0u8; MAX_CLOCK_COMMENT_LEN];
+    let clock = ClockRecord {
+      remaining_ms: u32::MAX,
+      elapsed_ms: 59_999,
+    };
+    assert_eq!(clock_comment(&mut buf, &clock), "{[%clk 1193:02:47] [%emt 0:00:59]}");
+  }
+
+  #[test]
+  fn test_chess960_roundtrip() {
+    let mut game = GameState::from_fen("4k3/8/8/8/8/8/8/RK5R w HA - 0 1").unwrap();
+    play(&mut game, &[(1, 0), (60, 52)]);
+
+    let mut buf = [0u8; 512];
+    let pgn = write_pgn_to_buffer(&mut buf, &game, &PgnTags::default()).unwrap();
+    assert!(pgn.contains("[Variant \"Chess960\"]\n[SetUp \"1\"]\n"));
+    assert!(pgn.ends_with("\n1. O-O-O Ke7 *\n"));
+
+    let imported = parse_pgn(pgn.as_bytes()).unwrap();
+    assert!(imported.moves().eq(game.move_history()));
+  }
+}
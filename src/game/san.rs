@@ -0,0 +1,357 @@
+//! Standard Algebraic Notation (SAN)
+//!
+//! Formats moves as "Nbxd7+", "O-O", "exd8=Q#" and parses them back
+//! into legal (from, to, promotion) moves on a `ChessBoard`.
+
+use core::fmt;
+
+use crate::game::chess::{parse_square, square_name, ChessBoard, PieceType};
+
+/// Maximum length of a SAN move (e.g. "exd8=Q#" or "Qh4xe1+")
+pub const MAX_SAN_LEN: usize = 8;
+
+/// Errors that can occur while parsing a SAN move
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SanError {
+  /// The text is not well-formed SAN
+  InvalidSyntax,
+  /// No legal move in the position matches
+  IllegalMove,
+  /// More than one legal move matches (missing disambiguation)
+  AmbiguousMove,
+}
+
+/// A move formatted in Standard Algebraic Notation
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct San {
+  bytes: [u8; MAX_SAN_LEN],
+  len: usize,
+}
+
+impl San {
+  /// Format a move in SAN
+  ///
+  /// `board` must be the position *before* the move is made. As with
+  /// `ChessBoard::make_move_with_promotion`, a pawn reaching the last rank
+  /// without an explicit `promotion` is formatted as a queen promotion.
+  pub fn from_move(board: &ChessBoard, from: u8, to: u8, promotion: Option<PieceType>) -> Self {
+    let mut san = Self {
+      bytes: [0; MAX_SAN_LEN],
+      len: 0,
+    };
+
+    let Some(piece) = board.get(from) else {
+      return san;
+    };
+
+    let from_file = from % 8;
+    let to_file = to % 8;
+
+    if let Some(castle) = board.castling_move(from, to) {
+      san.push_str(if castle.kingside { "O-O" } else { "O-O-O" });
+    } else if piece.piece_type == PieceType::Pawn {
+      // Diagonal pawn moves are always captures (including en passant)
+      let is_capture = from_file != to_file;
+      if is_capture {
+        san.push(b'a' + from_file);
+        san.push(b'x');
+      }
+      san.push_square(to);
+
+      if to / 8 == 7 || to / 8 == 0 {
+        san.push(b'=');
+        san.push(promotion.unwrap_or(PieceType::Queen).to_char() as u8);
+      }
+    } else {
+      san.push(piece.piece_type.to_char() as u8);
+
+      // Other pieces of the same type that could also legally reach `to`
+      let mut ambiguous = false;
+      let mut same_file = false;
+      let mut same_rank = false;
+      for other in 0..64u8 {
+        if other == from || board.get(other) != Some(piece) || !board.is_legal(other, to) {
+          continue;
+        }
+        ambiguous = true;
+        same_file |= other % 8 == from_file;
+        same_rank |= other / 8 == from / 8;
+      }
+
+      if ambiguous {
+        let [file, rank] = square_name(from);
+        if !same_file {
+          san.push(file);
+        } else if !same_rank {
+          san.push(rank);
+        } else {
+          san.push(file);
+          san.push(rank);
+        }
+      }
+
+      if board.get(to).is_some() {
+        san.push(b'x');
+      }
+      san.push_square(to);
+    }
+
+    let mut after = *board.position();
+    after.make_move(from, to, promotion);
+    if after.is_in_check(after.side_to_move()) {
+      if !after.has_legal_moves() {
+        san.push(b'#');
+      } else {
+        san.push(b'+');
+      }
+    }
+
+    san
+  }
+
+  /// Get the SAN text
+  pub fn as_str(&self) -> &str {
+    core::str::from_utf8(&self.bytes[..self.len]).unwrap_or("")
+  }
+
+  fn push(&mut self, byte: u8) {
+    if self.len < MAX_SAN_LEN {
+      self.bytes[self.len] = byte;
+      self.len += 1;
+    }
+  }
+
+  fn push_str(&mut self, s: &str) {
+    for &byte in s.as_bytes() {
+      self.push(byte);
+    }
+  }
+
+  fn push_square(&mut self, sq: u8) {
+    for byte in square_name(sq) {
+      self.push(byte);
+    }
+  }
+}
+
+impl fmt::Display for San {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+/// Parse a SAN move into a legal (from, to, promotion) move
+///
+/// Accepts trailing check/mate markers and annotations ("+", "#", "!", "?"),
+/// castling written with letter O or digit zero, and promotions with or
+/// without the "=" sign.
+pub fn parse_san(board: &ChessBoard, san: &str) -> Result<(u8, u8, Option<PieceType>), SanError> {
+  let san = san.trim_end_matches(['+', '#', '!', '?']);
+
+  if matches!(san, "O-O" | "0-0" | "O-O-O" | "0-0-0") {
+    return parse_castling(board, san.len() == 3);
+  }
+
+  let mut text = san.as_bytes();
+
+  // Piece letter (pawn moves have none)
+  let piece_type = match text.first().and_then(|&c| PieceType::from_char(c as char)) {
+    Some(PieceType::Pawn) => return Err(SanError::InvalidSyntax),
+    Some(piece_type) => {
+      text = &text[1..];
+      piece_type
+    }
+    None => PieceType::Pawn,
+  };
+
+  // Promotion suffix ("=Q" or "Q")
+  let mut promotion = None;
+  if let Some((&last, rest)) = text.split_last()
+    && let Some(promo) = PieceType::from_char(last as char)
+  {
+    if piece_type != PieceType::Pawn || matches!(promo, PieceType::Pawn | PieceType::King) {
+      return Err(SanError::InvalidSyntax);
+    }
+    promotion = Some(promo);
+    text = rest.strip_suffix(b"=").unwrap_or(rest);
+  }
+
+  // Destination square
+  if text.len() < 2 {
+    return Err(SanError::InvalidSyntax);
+  }
+  let (prefix, dest) = text.split_at(text.len() - 2);
+  let to = core::str::from_utf8(dest)
+    .ok()
+    .and_then(parse_square)
+    .ok_or(SanError::InvalidSyntax)?;
+
+  // Disambiguation and capture marker
+  let prefix = prefix.strip_suffix(b"x").unwrap_or(prefix);
+  let (from_file, from_rank) = match prefix {
+    [] => (None, None),
+    [f @ b'a'..=b'h'] => (Some(f - b'a'), None),
+    [r @ b'1'..=b'8'] => (None, Some(r - b'1')),
+    [f @ b'a'..=b'h', r @ b'1'..=b'8'] => (Some(f - b'a'), Some(r - b'1')),
+    _ => return Err(SanError::InvalidSyntax),
+  };
+
+  let is_promotion_square = to / 8 == 7 || to / 8 == 0;
+  if piece_type == PieceType::Pawn && promotion.is_some() != is_promotion_square {
+    return Err(SanError::IllegalMove);
+  }
+
+  let mut found = None;
+  for mv in &board.legal_moves() {
+    if mv.to != to || mv.promotion != promotion || mv.is_castle() {
+      continue;
+    }
+    if board.get(mv.from).map(|p| p.piece_type) != Some(piece_type) {
+      continue;
+    }
+    if from_file.is_some_and(|f| f != mv.from % 8) || from_rank.is_some_and(|r| r != mv.from / 8) {
+      continue;
+    }
+    if found.is_some() {
+      return Err(SanError::AmbiguousMove);
+    }
+    found = Some(mv.from);
+  }
+
+  found
+    .map(|from| (from, to, promotion))
+    .ok_or(SanError::IllegalMove)
+}
+
+fn parse_castling(board: &ChessBoard, kingside: bool) -> Result<(u8, u8, Option<PieceType>), SanError> {
+  board
+    .legal_moves()
+    .iter()
+    .find(|mv| mv.is_castle() && board.castling_move(mv.from, mv.to).is_some_and(|c| c.kingside == kingside))
+    .map(|mv| (mv.from, mv.to, None))
+    .ok_or(SanError::IllegalMove)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn san(fen: &str, from: u8, to: u8, promotion: Option<PieceType>) -> San {
+    San::from_move(&ChessBoard::from_fen(fen).unwrap(), from, to, promotion)
+  }
+
+  #[test]
+  fn test_format_pawn_and_piece_moves() {
+    let board = ChessBoard::starting_position();
+    assert_eq!(San::from_move(&board, 12, 28, None).as_str(), "e4");
+    assert_eq!(San::from_move(&board, 6, 21, None).as_str(), "Nf3");
+  }
+
+  #[test]
+  fn test_format_captures() {
+    // 1. e4 d5
+    let fen = "rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2";
+    assert_eq!(san(fen, 28, 35, None).as_str(), "exd5");
+
+    let fen = "4k3/8/8/3p4/8/4N3/8/4K3 w - - 0 1";
+    assert_eq!(san(fen, 20, 35, None).as_str(), "Nxd5");
+  }
+
+  #[test]
+  fn test_format_en_passant() {
+    let fen = "4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1";
+    assert_eq!(san(fen, 36, 43, None).as_str(), "exd6");
+  }
+
+  #[test]
+  fn test_format_castling() {
+    let fen = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+    assert_eq!(san(fen, 4, 6, None).as_str(), "O-O");
+    assert_eq!(san(fen, 4, 2, None).as_str(), "O-O-O");
+  }
+
+  #[test]
+  fn test_format_disambiguation() {
+    // Knights on b1 and f1 can both reach d2
+    let fen = "4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1";
+    assert_eq!(san(fen, 1, 11, None).as_str(), "Nbd2");
+
+    // Rooks on a1 and a5 can both reach a3
+    let fen = "4k3/8/8/R7/8/8/8/R3K3 w - - 0 1";
+    assert_eq!(san(fen, 0, 16, None).as_str(), "R1a3");
+
+    // Queens on a1, a3 and c1 can all reach b2
+    let fen = "4k3/8/8/8/8/Q7/8/Q1Q1K3 w - - 0 1";
+    assert_eq!(san(fen, 0, 9, None).as_str(), "Qa1b2");
+  }
+
+  #[test]
+  fn test_format_pinned_piece_needs_no_disambiguation() {
+    // Knight on f2 is pinned by the bishop on h4, so only Nb4 can reach d3
+    let fen = "4k3/8/8/8/1N5b/8/5N2/4K3 w - - 0 1";
+    assert_eq!(san(fen, 25, 19, None).as_str(), "Nd3");
+  }
+
+  #[test]
+  fn test_format_promotion_and_check() {
+    let fen = "3r2k1/4P3/8/8/8/8/8/4K3 w - - 0 1";
+    assert_eq!(san(fen, 52, 59, Some(PieceType::Queen)).as_str(), "exd8=Q+");
+    assert_eq!(san(fen, 52, 60, Some(PieceType::Knight)).as_str(), "e8=N");
+    assert_eq!(san(fen, 52, 60, None).as_str(), "e8=Q+");
+  }
+
+  #[test]
+  fn test_format_checkmate() {
+    // Scholar's mate
+    let fen = "r1bqkbnr/pppp1ppp/2n5/4p3/2B1P3/5Q2/PPPP1PPP/RNB1K1NR w KQkq - 4 4";
+    let board = ChessBoard::from_fen(fen).unwrap();
+    assert_eq!(San::from_move(&board, 21, 53, None).as_str(), "Qxf7#");
+  }
+
+  #[test]
+  fn test_parse_simple_moves() {
+    let board = ChessBoard::starting_position();
+    assert_eq!(parse_san(&board, "e4"), Ok((12, 28, None)));
+    assert_eq!(parse_san(&board, "Nf3"), Ok((6, 21, None)));
+    assert_eq!(parse_san(&board, "Nf3!?"), Ok((6, 21, None)));
+    assert_eq!(parse_san(&board, "e5"), Err(SanError::IllegalMove));
+    assert_eq!(parse_san(&board, "Ke2"), Err(SanError::IllegalMove));
+    assert_eq!(parse_san(&board, "Zf3"), Err(SanError::InvalidSyntax));
+    assert_eq!(parse_san(&board, ""), Err(SanError::InvalidSyntax));
+  }
+
+  #[test]
+  fn test_parse_disambiguation() {
+    let board = ChessBoard::from_fen("4k3/8/8/8/8/8/8/1N2KN2 w - - 0 1").unwrap();
+    assert_eq!(parse_san(&board, "Nd2"), Err(SanError::AmbiguousMove));
+    assert_eq!(parse_san(&board, "Nbd2"), Ok((1, 11, None)));
+    assert_eq!(parse_san(&board, "Nfd2"), Ok((5, 11, None)));
+    assert_eq!(parse_san(&board, "Nf1d2"), Ok((5, 11, None)));
+  }
+
+  #[test]
+  fn test_parse_castling_and_promotion() {
+    let board = ChessBoard::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1").unwrap();
+    assert_eq!(parse_san(&board, "O-O"), Ok((60, 62, None)));
+    assert_eq!(parse_san(&board, "0-0-0"), Ok((60, 58, None)));
+
+    let board = ChessBoard::from_fen("3r2k1/4P3/8/8/8/8/8/4K3 w - - 0 1").unwrap();
+    assert_eq!(parse_san(&board, "exd8=Q+"), Ok((52, 59, Some(PieceType::Queen))));
+    assert_eq!(parse_san(&board, "e8N"), Ok((52, 60, Some(PieceType::Knight))));
+    assert_eq!(parse_san(&board, "e8"), Err(SanError::IllegalMove));
+    assert_eq!(parse_san(&board, "e8=K"), Err(SanError::InvalidSyntax));
+  }
+
+  #[test]
+  fn test_roundtrip_all_moves_from_start() {
+    let board = ChessBoard::starting_position();
+    for from in 0..64u8 {
+      for to in board.legal_destinations(from) {
+        if board.is_legal(from, to) {
+          let san = San::from_move(&board, from, to, None);
+          assert_eq!(parse_san(&board, san.as_str()), Ok((from, to, None)));
+        }
+      }
+    }
+  }
+}
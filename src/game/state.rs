@@ -2,8 +2,11 @@
 //!
 //! Tracks chess board state with move validation.
 
-use crate::game::chess::{BoardStatus, ChessBoard, Destinations, PieceColor, PieceType, UndoInfo};
-use crate::game::timer::{ChessTimer, Color};
+use crate::game::chess::{
+  BoardStatus, ChessBoard, Destinations, FenError, MoveList, PieceColor, PieceType, Position,
+  UndoInfo,
+};
+use crate::game::timer::{ChessTimer, ClockMode, Color, TimeControl};
 
 /// Current game status
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -24,6 +27,54 @@ pub enum GameStatus {
   Draw,
 }
 
+/// Maximum number of half-moves kept in the game's move history
+///
+/// Later moves are still played and counted, but not recorded: they are
+/// missing from `move_history` and `clock_history`, and so from PGN export.
+pub const MAX_GAME_MOVES: usize = 512;
+
+/// A move as recorded in the game's move history
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MoveRecord {
+  pub from: u8,
+  pub to: u8,
+  /// Promotion piece chosen by the player (`None` = default queen promotion)
+  pub promotion: Option<PieceType>,
+}
+
+/// The mover's clock as recorded after a completed move
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ClockRecord {
+  /// Time left after the move, including any increment, in milliseconds
+  pub remaining_ms: u32,
+  /// Time spent on the move in milliseconds
+  pub elapsed_ms: u32,
+}
+
+/// A move in the history with the mover's clock after it
+#[derive(Clone, Copy, Debug)]
+struct PlayedMove {
+  mv: MoveRecord,
+  /// Mover's move time overruns after the move (fixed time per move)
+  overruns: u8,
+  clock: ClockRecord,
+}
+
+impl PlayedMove {
+  const EMPTY: Self = Self {
+    mv: MoveRecord {
+      from: 0,
+      to: 0,
+      promotion: None,
+    },
+    overruns: 0,
+    clock: ClockRecord {
+      remaining_ms: 0,
+      elapsed_ms: 0,
+    },
+  };
+}
+
 /// Main game state
 pub struct GameState {
   /// Current turn
@@ -40,6 +91,10 @@ pub struct GameState {
   lift_square: Option<u8>,
   /// Move count (half-moves)
   move_count: u16,
+  /// Position the game started from (for replaying the move history)
+  initial_position: Position,
+  /// Moves played since `initial_position`, the first `move_count` valid
+  history: [PlayedMove; MAX_GAME_MOVES],
 }
 
 impl Default for GameState {
@@ -59,9 +114,23 @@ impl GameState {
       lifted_piece: None,
       lift_square: None,
       move_count: 0,
+      initial_position: Position::starting_position(),
+      history: [PlayedMove::EMPTY; MAX_GAME_MOVES],
     }
   }
 
+  /// Create a new game state starting from a FEN position
+  pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+    Ok(Self::with_board(ChessBoard::from_fen(fen)?))
+  }
+
+  /// Create a new game state starting from the given board position
+  pub fn with_board(board: ChessBoard) -> Self {
+    let mut state = Self::new();
+    state.reset_to(board);
+    state
+  }
+
   /// Get current game status
   pub fn status(&self) -> GameStatus {
     self.status
@@ -91,6 +160,56 @@ impl GameState {
     &self.timer
   }
 
+  /// Set the time control and reset the clocks to it
+  pub fn set_time_control(&mut self, time_control: TimeControl) {
+    self.timer.set_time_control(time_control);
+  }
+
+  /// Get the current board position
+  pub fn board(&self) -> &ChessBoard {
+    &self.board
+  }
+
+  /// Get the position the game started from
+  pub fn initial_position(&self) -> &Position {
+    &self.initial_position
+  }
+
+  /// Number of half-moves played
+  pub fn move_count(&self) -> u16 {
+    self.move_count
+  }
+
+  /// Number of moves `color` has completed since the initial position
+  pub fn moves_played(&self, color: Color) -> u16 {
+    let first_to_move = match self.initial_position.side_to_move() {
+      PieceColor::White => Color::White,
+      PieceColor::Black => Color::Black,
+    };
+    if color == first_to_move {
+      self.move_count.div_ceil(2)
+    } else {
+      self.move_count / 2
+    }
+  }
+
+  /// Moves played since the initial position, in order
+  ///
+  /// Moves beyond `MAX_GAME_MOVES` are not recorded.
+  pub fn move_history(&self) -> impl Iterator<Item = MoveRecord> + '_ {
+    self.recorded().iter().map(|played| played.mv)
+  }
+
+  /// Mover's clock after each move of `move_history`, in order
+  pub fn clock_history(&self) -> impl Iterator<Item = ClockRecord> + '_ {
+    self.recorded().iter().map(|played| played.clock)
+  }
+
+  fn recorded(&self) -> &[PlayedMove] {
+    let len = (self.move_count as usize).min(MAX_GAME_MOVES);
+    &self.history[..len]
+  }
+
   /// Check if a move from one square to another is legal (including check rules)
   pub fn is_legal_move(&self, from: u8, to: u8) -> bool {
     self.board.is_legal(from, to)
@@ -101,6 +220,16 @@ impl GameState {
     self.board.status()
   }
 
+  /// Get a draw the player to move may claim (threefold repetition or 50-move rule)
+  pub fn claimable_draw(&self) -> Option<BoardStatus> {
+    self.board.claimable_draw()
+  }
+
+  /// Get all legal moves for the player to move
+  pub fn legal_moves(&self) -> MoveList {
+    self.board.legal_moves()
+  }
+
   /// Get all legal destination squares for a piece at the given square
   /// Only returns moves that don't leave the king in check
   pub fn legal_destinations(&self, from: u8) -> Destinations {
@@ -146,6 +275,7 @@ impl GameState {
       return false;
     }
 
+    let mover = self.turn;
     self.board.make_move_with_promotion(from, to, promotion);
 
     // Switch turns
@@ -153,7 +283,19 @@ impl GameState {
       Color::White => Color::Black,
       Color::Black => Color::White,
     };
+    let elapsed_ms = self.timer.move_elapsed();
     self.move_count += 1;
+    self.timer.move_completed(mover, self.moves_played(mover));
+    if let Some(slot) = self.history.get_mut(self.move_count as usize - 1) {
+      *slot = PlayedMove {
+        mv: MoveRecord { from, to, promotion },
+        overruns: self.timer.overruns(mover),
+        clock: ClockRecord {
+          remaining_ms: self.timer.time_remaining(mover),
+          elapsed_ms,
+        },
+      };
+    }
     self.lifted_piece = None;
     self.lift_square = None;
 
@@ -162,6 +304,8 @@ impl GameState {
 
   /// Undo the last move (takeback)
   ///
+  /// Both clocks go back to where they were when the undone move started
+  /// (for moves within `MAX_GAME_MOVES`; later ones leave the clocks running on).
   /// Returns `true` if a move was undone, `false` if no move to undo.
   pub fn undo_move(&mut self) -> bool {
     if !self.board.undo_move() {
@@ -174,12 +318,61 @@ impl GameState {
       Color::Black => Color::White,
     };
     self.move_count = self.move_count.saturating_sub(1);
+    self.rewind_clocks();
     self.lifted_piece = None;
     self.lift_square = None;
 
     true
   }
 
+  /// Put both clocks back to when the move to be made now started
+  ///
+  /// Each player's time then is the one recorded after their last move, or
+  /// their base time before it. In an hourglass the mover also got back the
+  /// time the opponent spent on the move in between.
+  fn rewind_clocks(&mut self) {
+    let count = self.move_count as usize;
+    if count > MAX_GAME_MOVES {
+      return;
+    }
+    let before = |plies: usize| count.checked_sub(plies).map(|index| self.history[index]);
+    let (own, opponent) = (before(2), before(1));
+
+    let time_control = self.timer.time_control();
+    let mover = self.turn;
+    let mut own_ms = own.map_or(time_control.base_time(mover), |m| m.clock.remaining_ms);
+    if let (ClockMode::Hourglass, Some(opponent)) = (time_control.mode, opponent) {
+      own_ms = own_ms.saturating_add(time_control.delay.charged_ms(opponent.clock.elapsed_ms));
+    }
+    let opponent_ms = opponent.map_or(time_control.base_time(mover.opponent()), |m| m.clock.remaining_ms);
+
+    let mut times_ms = [0; 2];
+    let mut overruns = [0; 2];
+    times_ms[mover as usize] = own_ms;
+    times_ms[mover.opponent() as usize] = opponent_ms;
+    overruns[mover as usize] = own.map_or(0, |m| m.overruns);
+    overruns[mover.opponent() as usize] = opponent.map_or(0, |m| m.overruns);
+    let moves_played = [self.moves_played(Color::White), self.moves_played(Color::Black)];
+    self.timer.rewind(times_ms, overruns, moves_played);
+  }
+
+  /// Take back the last move to replace it with another
+  ///
+  /// Unlike `undo_move`, the mover is still charged for the time spent on
+  /// the move so far, including the time since it was made.
+  pub fn retract_move(&mut self) -> bool {
+    let Some(played) = self.recorded().last() else {
+      return false;
+    };
+    let spent_ms = played.clock.elapsed_ms.saturating_add(self.timer.move_elapsed());
+
+    if !self.undo_move() {
+      return false;
+    }
+    self.timer.tick(self.turn, spent_ms);
+    true
+  }
+
   /// Get information about the last move (for takeback detection)
   pub fn last_undo_info(&self) -> Option<&UndoInfo> {
     self.board.last_undo_info()
@@ -191,6 +384,12 @@ impl GameState {
     self.lift_square = Some(square);
   }
 
+  /// Record that the lifted piece was put back on its square
+  pub fn piece_replaced(&mut self) {
+    self.lifted_piece = None;
+    self.lift_square = None;
+  }
+
   /// Get the currently lifted piece square (if any)
   pub fn lifted_piece(&self) -> Option<u8> {
     self.lifted_piece
@@ -198,18 +397,30 @@ impl GameState {
 
   /// Reset the game to starting position
   pub fn reset(&mut self) {
-    self.turn = Color::White;
+    self.reset_to(ChessBoard::starting_position());
+    self.status = GameStatus::WaitingForSetup;
+  }
+
+  /// Start the game afresh from `board`, keeping the status and time control
+  ///
+  /// Replaces the state in place, so no second `GameState` is built.
+  pub fn reset_to(&mut self, board: ChessBoard) {
+    self.turn = match board.side_to_move() {
+      PieceColor::White => Color::White,
+      PieceColor::Black => Color::Black,
+    };
     self.timer.reset();
-    self.board = ChessBoard::starting_position();
+    self.initial_position = *board.position();
+    self.board = board;
     self.lifted_piece = None;
     self.lift_square = None;
     self.move_count = 0;
-    self.status = GameStatus::WaitingForSetup;
   }
 
   /// Update timer with elapsed time
   ///
-  /// Returns `true` if time expired for the current player.
+  /// Returns `true` if time expired for the current player. The current player
+  /// loses, unless the opponent could never checkmate and the game is drawn.
   pub fn tick_timer(&mut self, elapsed_ms: u32) -> bool {
     if self.status != GameStatus::InProgress {
       return false;
@@ -218,10 +429,16 @@ impl GameState {
     let expired = self.timer.tick(self.current_turn(), elapsed_ms);
 
     if expired {
-      // Current player loses on time
-      match self.current_turn() {
-        Color::White => self.set_status(GameStatus::BlackWins),
-        Color::Black => self.set_status(GameStatus::WhiteWins),
+      let opponent = match self.current_turn() {
+        Color::White => PieceColor::Black,
+        Color::Black => PieceColor::White,
+      };
+      if self.board.has_insufficient_material(opponent) {
+        self.set_status(GameStatus::Draw);
+      } else if opponent == PieceColor::White {
+        self.set_status(GameStatus::WhiteWins);
+      } else {
+        self.set_status(GameStatus::BlackWins);
       }
     }
 
@@ -241,6 +458,7 @@ impl GameState {
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::game::timer::{MoveTimePenalty, TimeStage};
 
   #[test]
   fn test_new_game() {
@@ -363,4 +581,230 @@ mod tests {
     assert_eq!(game.status(), GameStatus::WaitingForSetup);
     assert_eq!(game.current_turn(), Color::White);
   }
+
+  #[test]
+  fn test_from_fen() {
+    let game = GameState::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1").unwrap();
+    assert_eq!(game.status(), GameStatus::WaitingForCalibration);
+    assert_eq!(game.current_turn(), Color::Black);
+    assert_eq!(game.legal_destinations(12).len(), 0);
+    assert_eq!(game.legal_destinations(60).len(), 5);
+
+    assert!(GameState::from_fen("not a fen").is_err());
+  }
+
+  #[test]
+  fn test_from_fen_plays_moves() {
+    let mut game = GameState::from_fen("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1").unwrap();
+    game.set_status(GameStatus::InProgress);
+
+    assert!(game.make_move(12, 28));
+    let mut buf = [0u8; crate::game::chess::MAX_FEN_LEN];
+    assert_eq!(game.board().to_fen(&mut buf), "4k3/8/8/8/4P3/8/8/4K3 b - - 0 1");
+  }
+
+  #[test]
+  fn test_move_adds_increment_to_mover() {
+    let mut game = GameState::new();
+    game.set_time_control(TimeControl::RAPID_15_10);
+    game.set_status(GameStatus::InProgress);
+
+    game.tick_timer(3_000);
+    assert!(game.make_move(12, 28));
+    assert_eq!(game.timer().time_remaining(Color::White), 900_000 - 3_000 + 10_000);
+    assert_eq!(game.timer().time_remaining(Color::Black), 900_000);
+
+    // Reset keeps the time control
+    game.reset();
+    assert_eq!(game.timer().formatted_time(Color::White), (15, 0));
+  }
+
+  #[test]
+  fn test_timeout_loses_when_opponent_can_mate() {
+    // Black's lone pawn could still help a knight deliver mate
+    let mut game = GameState::from_fen("k7/p7/8/8/8/8/8/K1N5 b - - 0 1").unwrap();
+    game.set_time_control(TimeControl::minutes(1, 0));
+    game.set_status(GameStatus::InProgress);
+
+    assert!(game.tick_timer(60_000));
+    assert_eq!(game.status(), GameStatus::WhiteWins);
+  }
+
+  #[test]
+  fn test_timeout_draws_when_opponent_cannot_mate() {
+    // White still has mating material, but Black's lone king has none
+    let mut game = GameState::from_fen("k7/8/8/8/8/8/P7/K1N5 w - - 0 1").unwrap();
+    game.set_time_control(TimeControl::minutes(1, 0));
+    game.set_status(GameStatus::InProgress);
+
+    assert!(game.tick_timer(60_000));
+    assert_eq!(game.status(), GameStatus::Draw);
+  }
+
+  #[test]
+  fn test_retract_move_keeps_time_spent() {
+    let mut game = GameState::new();
+    game.set_status(GameStatus::InProgress);
+
+    game.tick_timer(2_000);
+    assert!(game.make_move(12, 28));
+    game.tick_timer(500);
+
+    assert!(game.retract_move());
+    assert_eq!(game.current_turn(), Color::White);
+    assert_eq!(game.move_count(), 0);
+    assert_eq!(game.timer().time_remaining(Color::White), 600_000 - 2_500);
+    assert_eq!(game.timer().time_remaining(Color::Black), 600_000);
+  }
+
+  #[test]
+  fn test_moves_played_per_side() {
+    let mut game = GameState::from_fen("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1").unwrap();
+    game.set_status(GameStatus::InProgress);
+
+    assert!(game.make_move(60, 59));
+    assert_eq!(game.moves_played(Color::Black), 1);
+    assert_eq!(game.moves_played(Color::White), 0);
+    assert!(game.make_move(12, 28));
+    assert_eq!(game.moves_played(Color::White), 1);
+  }
+
+  #[test]
+  fn test_move_starts_next_time_stage() {
+    let mut game = GameState::new();
+    game.set_time_control(TimeControl::minutes(5, 0).with_stage(TimeStage {
+      after_moves: 2,
+      added_ms: 60_000,
+      increment_ms: 0,
+    }));
+    game.set_status(GameStatus::InProgress);
+
+    for (from, to) in [(6, 21), (62, 45), (21, 6), (45, 62)] {
+      assert!(game.make_move(from, to));
+    }
+    assert_eq!(game.timer().stage(Color::White), 1);
+    assert_eq!(game.timer().stage(Color::Black), 1);
+    assert_eq!(game.timer().time_remaining(Color::Black), 360_000);
+  }
+
+  #[test]
+  fn test_clock_history() {
+    let mut game = GameState::new();
+    game.set_time_control(TimeControl::BLITZ_3_2);
+    game.set_status(GameStatus::InProgress);
+
+    game.tick_timer(4_000);
+    assert!(game.make_move(12, 28));
+    game.tick_timer(1_500);
+    assert!(game.make_move(52, 36));
+
+    assert!(game.clock_history().eq([
+      ClockRecord { remaining_ms: 178_000, elapsed_ms: 4_000 },
+      ClockRecord { remaining_ms: 180_500, elapsed_ms: 1_500 },
+    ]));
+
+    // Takebacks unwind the history with the moves
+    assert!(game.undo_move());
+    assert_eq!(game.clock_history().count(), 1);
+    game.tick_timer(2_000);
+    assert!(game.make_move(51, 35));
+    assert_eq!(game.clock_history().nth(1).unwrap().elapsed_ms, 2_000);
+
+    game.reset();
+    assert_eq!(game.clock_history().count(), 0);
+  }
+
+  #[test]
+  fn test_clock_history_counts_overruns() {
+    let mut game = GameState::new();
+    game.set_time_control(TimeControl::per_move(30_000, MoveTimePenalty::Overruns(3)));
+    game.set_status(GameStatus::InProgress);
+
+    game.tick_timer(29_000);
+    game.tick_timer(2_000);
+    game.tick_timer(5_000);
+    assert!(game.make_move(12, 28));
+
+    assert!(game.clock_history().eq([ClockRecord { remaining_ms: 30_000, elapsed_ms: 36_000 }]));
+  }
+
+  #[test]
+  fn test_undo_restores_clocks() {
+    let mut game = GameState::new();
+    game.set_time_control(TimeControl::BLITZ_3_2);
+    game.set_status(GameStatus::InProgress);
+
+    game.tick_timer(4_000);
+    assert!(game.make_move(12, 28));
+    game.tick_timer(10_000);
+    assert!(game.make_move(52, 36));
+    game.tick_timer(3_000);
+
+    // Black's move is undone: both clocks as when black started thinking
+    assert!(game.undo_move());
+    assert_eq!(game.timer().time_remaining(Color::White), 178_000);
+    assert_eq!(game.timer().time_remaining(Color::Black), 180_000);
+
+    assert!(game.undo_move());
+    assert_eq!(game.timer().time_remaining(Color::White), 180_000);
+    assert_eq!(game.timer().time_remaining(Color::Black), 180_000);
+    assert_eq!(game.timer().move_elapsed(), 0);
+  }
+
+  #[test]
+  fn test_undo_restores_hourglass_clocks() {
+    let mut game = GameState::new();
+    game.set_time_control(TimeControl::hourglass(120_000));
+    game.set_status(GameStatus::InProgress);
+
+    game.tick_timer(4_000);
+    assert!(game.make_move(12, 28));
+    game.tick_timer(10_000);
+    assert!(game.make_move(52, 36));
+    game.tick_timer(3_000);
+
+    // Black had white's 4 seconds added when black's move started
+    assert!(game.undo_move());
+    assert_eq!(game.timer().time_remaining(Color::White), 116_000);
+    assert_eq!(game.timer().time_remaining(Color::Black), 124_000);
+  }
+
+  #[test]
+  fn test_undo_restores_overruns() {
+    let mut game = GameState::new();
+    game.set_time_control(TimeControl::per_move(10_000, MoveTimePenalty::Overruns(3)));
+    game.set_status(GameStatus::InProgress);
+
+    game.tick_timer(12_000);
+    assert!(game.make_move(12, 28));
+    game.tick_timer(1_000);
+    assert!(game.make_move(52, 36));
+
+    assert!(game.undo_move());
+    assert_eq!(game.timer().overruns(Color::White), 1);
+    assert!(game.undo_move());
+    assert_eq!(game.timer().overruns(Color::White), 0);
+    assert_eq!(game.timer().time_remaining(Color::White), 10_000);
+  }
+
+  #[test]
+  fn test_move_history() {
+    let mut game = GameState::new();
+    game.set_status(GameStatus::InProgress);
+
+    assert!(game.make_move(12, 28));
+    assert!(game.make_move(52, 36));
+    assert!(!game.make_move(0, 63));
+    assert_eq!(game.move_history().count(), 2);
+    assert_eq!(
+      game.move_history().next(),
+      Some(MoveRecord { from: 12, to: 28, promotion: None })
+    );
+
+    assert!(game.undo_move());
+    assert_eq!(game.move_history().count(), 1);
+
+    game.reset();
+    assert_eq!(game.move_history().count(), 0);
+  }
 }
@@ -1,6 +1,15 @@
 //! Chess clock implementation
 //!
-//! Fixed 10+0 time control (10 minutes per side, no increment).
+//! Base time per side (unequal for odds games) plus a Fischer increment added
+//! after each move, optionally with a US simple or Bronstein delay. Classical
+//! controls add further stages after a number of moves, e.g. 40 moves in 90
+//! minutes, then 30 minutes more. Defaults to 10+0.
+//!
+//! Besides the usual per-game budget, the clock can run as an hourglass or
+//! with a fixed time for every move (see `ClockMode`).
+//!
+//! Clock faces switch to seconds and tenths under ten seconds (`TimeDisplay`),
+//! and `LowTimeAlarm` sounds the configured low time warnings.
 
 /// Chess timer for both players
 #[derive(Clone, Copy, Debug)]
@@ -11,14 +20,352 @@ pub struct ChessTimer {
   black_time_ms: u32,
   /// Whether the timer is running
   running: bool,
+  /// Time control the clocks are reset to
+  time_control: TimeControl,
+  /// Time spent on the current move so far
+  move_elapsed_ms: u32,
+  /// Stage each player is in (0 = base time), indexed white, black
+  stages: [u8; 2],
+  /// Move time overruns so far in fixed-time-per-move mode, indexed white, black
+  overruns: [u8; 2],
 }
 
 /// Initial time per player (10 minutes = 600,000 ms)
 pub const INITIAL_TIME_MS: u32 = 10 * 60 * 1000;
 
+/// Per-move delay before or against the player's own time
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Delay {
+  #[default]
+  None,
+  /// US simple delay: the clock only starts counting down after this many
+  /// milliseconds of each move
+  Simple(u32),
+  /// Bronstein delay: time used on a move is given back after the move, up
+  /// to this many milliseconds
+  Bronstein(u32),
+}
+
+impl Delay {
+  /// Delay per move in milliseconds
+  pub fn duration_ms(&self) -> u32 {
+    match *self {
+      Delay::None => 0,
+      Delay::Simple(ms) | Delay::Bronstein(ms) => ms,
+    }
+  }
+
+  /// Time taken off the clock for a move that took `elapsed_ms`
+  pub fn charged_ms(&self, elapsed_ms: u32) -> u32 {
+    match *self {
+      Delay::Simple(ms) => elapsed_ms.saturating_sub(ms),
+      _ => elapsed_ms,
+    }
+  }
+}
+
+/// What happens when a player runs out of time on a move in
+/// `ClockMode::FixedPerMove`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MoveTimePenalty {
+  /// The player loses on time
+  Loss,
+  /// The player's clock is set to the move time again and the overrun is
+  /// counted; the player loses on reaching this many overruns
+  Overruns(u8),
+}
+
+/// How the clocks run
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ClockMode {
+  /// Each player has a budget for the whole game (or stage)
+  #[default]
+  Standard,
+  /// Time drained from the player to move is added to the opponent's clock
+  Hourglass,
+  /// The base time is the time for each move and restarts after every move
+  FixedPerMove(MoveTimePenalty),
+}
+
+/// Maximum number of stages following the base time
+pub const MAX_TIME_STAGES: usize = 3;
+
+/// A later period of a multi-stage time control
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeStage {
+  /// Move number (per player, counted from the start) after which the stage begins
+  pub after_moves: u16,
+  /// Time added to the player's clock when the stage begins
+  pub added_ms: u32,
+  /// Increment per move during the stage
+  pub increment_ms: u32,
+}
+
+/// Base time per side and increment per move
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimeControl {
+  /// White's starting time in milliseconds
+  pub white_base_ms: u32,
+  /// Black's starting time in milliseconds
+  pub black_base_ms: u32,
+  /// Time added to a player's clock after each of their moves (base stage)
+  pub increment_ms: u32,
+  pub delay: Delay,
+  /// Further stages in order of their move trigger
+  pub stages: [Option<TimeStage>; MAX_TIME_STAGES],
+  pub mode: ClockMode,
+}
+
+impl TimeControl {
+  /// 10 minutes per side, no increment
+  pub const RAPID_10_0: Self = Self::minutes(10, 0);
+  /// 3 minutes per side, 2 seconds per move
+  pub const BLITZ_3_2: Self = Self::minutes(3, 2);
+  /// 15 minutes per side, 10 seconds per move
+  pub const RAPID_15_10: Self = Self::minutes(15, 10);
+  /// FIDE classical: 90 minutes for 40 moves, then 30 minutes, 30 seconds per
+  /// move from move 1
+  pub const CLASSICAL_90_30: Self = Self::minutes(90, 30).with_stage(TimeStage {
+    after_moves: 40,
+    added_ms: 30 * 60 * 1000,
+    increment_ms: 30 * 1000,
+  });
+
+  /// Same base time for both sides
+  pub const fn new(base_ms: u32, increment_ms: u32) -> Self {
+    Self {
+      white_base_ms: base_ms,
+      black_base_ms: base_ms,
+      increment_ms,
+      delay: Delay::None,
+      stages: [None; MAX_TIME_STAGES],
+      mode: ClockMode::Standard,
+    }
+  }
+
+  /// Time control in the usual "minutes + seconds" notation, e.g. 3+2
+  pub const fn minutes(base_minutes: u32, increment_seconds: u32) -> Self {
+    Self::new(base_minutes * 60 * 1000, increment_seconds * 1000)
+  }
+
+  /// Time odds: each side starts with its own base time
+  pub const fn odds(white_base_ms: u32, black_base_ms: u32, increment_ms: u32) -> Self {
+    Self {
+      white_base_ms,
+      black_base_ms,
+      increment_ms,
+      delay: Delay::None,
+      stages: [None; MAX_TIME_STAGES],
+      mode: ClockMode::Standard,
+    }
+  }
+
+  /// Hourglass with `base_ms` on each side
+  pub const fn hourglass(base_ms: u32) -> Self {
+    Self::new(base_ms, 0).with_mode(ClockMode::Hourglass)
+  }
+
+  /// `move_ms` for every move, with `penalty` for running out
+  pub const fn per_move(move_ms: u32, penalty: MoveTimePenalty) -> Self {
+    Self::new(move_ms, 0).with_mode(ClockMode::FixedPerMove(penalty))
+  }
+
+  /// The same time control with another clock mode
+  pub const fn with_mode(self, mode: ClockMode) -> Self {
+    Self { mode, ..self }
+  }
+
+  /// The same time control with a per-move delay
+  pub const fn with_delay(self, delay: Delay) -> Self {
+    Self { delay, ..self }
+  }
+
+  /// The same time control with another stage appended
+  ///
+  /// Stages beyond `MAX_TIME_STAGES` are ignored.
+  pub const fn with_stage(mut self, stage: TimeStage) -> Self {
+    let mut i = 0;
+    while i < MAX_TIME_STAGES {
+      if self.stages[i].is_none() {
+        self.stages[i] = Some(stage);
+        break;
+      }
+      i += 1;
+    }
+    self
+  }
+
+  /// Increment per move during `stage` (0 = base time)
+  pub fn increment(&self, stage: u8) -> u32 {
+    match stage.checked_sub(1) {
+      None => self.increment_ms,
+      Some(index) => self
+        .stages
+        .get(index as usize)
+        .copied()
+        .flatten()
+        .map_or(0, |s| s.increment_ms),
+    }
+  }
+
+  /// Starting time for a player in milliseconds
+  pub fn base_time(&self, color: Color) -> u32 {
+    match color {
+      Color::White => self.white_base_ms,
+      Color::Black => self.black_base_ms,
+    }
+  }
+}
+
+impl Default for TimeControl {
+  fn default() -> Self {
+    Self::RAPID_10_0
+  }
+}
+
 /// Low time warning threshold (30 seconds)
 pub const LOW_TIME_THRESHOLD_MS: u32 = 30 * 1000;
 
+/// Below this, clocks show seconds and tenths
+pub const TENTHS_THRESHOLD_MS: u32 = 10 * 1000;
+
+/// Remaining time as shown on a clock face
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeDisplay {
+  /// MM:SS
+  MinutesSeconds(u8, u8),
+  /// SS.t, under ten seconds
+  SecondsTenths(u8, u8),
+}
+
+impl TimeDisplay {
+  pub fn from_ms(time_ms: u32) -> Self {
+    if time_ms < TENTHS_THRESHOLD_MS {
+      Self::SecondsTenths((time_ms / 1000) as u8, (time_ms % 1000 / 100) as u8)
+    } else {
+      let (minutes, seconds) = ChessTimer::format_time(time_ms);
+      Self::MinutesSeconds(minutes, seconds)
+    }
+  }
+}
+
+impl Default for TimeDisplay {
+  fn default() -> Self {
+    Self::MinutesSeconds(0, 0)
+  }
+}
+
+/// Maximum number of low time warning thresholds
+pub const MAX_LOW_TIME_WARNINGS: usize = 3;
+
+/// Remaining times at which a low time warning sounds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LowTimeWarnings {
+  /// Thresholds in milliseconds, from the earliest to the most urgent warning
+  pub thresholds_ms: [Option<u32>; MAX_LOW_TIME_WARNINGS],
+  /// Beep every second below this time (0 = no countdown)
+  pub countdown_ms: u32,
+}
+
+impl LowTimeWarnings {
+  /// Warnings at 60, 30 and 10 seconds, then a countdown from 5 seconds
+  pub const STANDARD: Self = Self {
+    thresholds_ms: [Some(60_000), Some(30_000), Some(10_000)],
+    countdown_ms: 5_000,
+  };
+
+  /// No warnings at all
+  pub const OFF: Self = Self {
+    thresholds_ms: [None; MAX_LOW_TIME_WARNINGS],
+    countdown_ms: 0,
+  };
+
+  /// One past the index of the most urgent threshold crossed with
+  /// `remaining_ms` left, or 0 if none. Thresholds at or above the base time
+  /// are skipped, so a 30 second move time does not start out in a warning.
+  fn level(&self, remaining_ms: u32, base_ms: u32) -> u8 {
+    self
+      .thresholds_ms
+      .iter()
+      .enumerate()
+      .filter_map(|(index, threshold)| threshold.map(|t| (index, t)))
+      .filter(|&(_, threshold)| threshold < base_ms && remaining_ms < threshold)
+      .map(|(index, _)| index as u8 + 1)
+      .max()
+      .unwrap_or(0)
+  }
+}
+
+impl Default for LowTimeWarnings {
+  fn default() -> Self {
+    Self::STANDARD
+  }
+}
+
+/// A low time warning to sound
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TimeWarning {
+  /// Threshold crossed, by its index in `LowTimeWarnings::thresholds_ms`
+  Threshold(u8),
+  /// Another second passed in the final countdown
+  Countdown,
+}
+
+/// Tracks which low time warnings each player has had.
+///
+/// Each threshold warns once when crossed. A warning is re-armed when the
+/// player's time goes back above its threshold, e.g. after an increment.
+#[derive(Clone, Copy, Debug)]
+pub struct LowTimeAlarm {
+  warnings: LowTimeWarnings,
+  /// Thresholds crossed per player
+  levels: [u8; 2],
+  /// Whole seconds left per player when last checked
+  seconds: [u32; 2],
+}
+
+impl LowTimeAlarm {
+  pub const fn new(warnings: LowTimeWarnings) -> Self {
+    Self {
+      warnings,
+      levels: [0; 2],
+      seconds: [u32::MAX; 2],
+    }
+  }
+
+  pub fn warnings(&self) -> LowTimeWarnings {
+    self.warnings
+  }
+
+  /// Check a player's clock, returning the warning to sound if any
+  pub fn check(&mut self, timer: &ChessTimer, color: Color) -> Option<TimeWarning> {
+    let remaining_ms = timer.time_remaining(color);
+    let base_ms = timer.time_control().base_time(color);
+    let level = self.warnings.level(remaining_ms, base_ms);
+    let seconds = remaining_ms / 1000;
+
+    let last_level = core::mem::replace(&mut self.levels[color as usize], level);
+    let last_seconds = core::mem::replace(&mut self.seconds[color as usize], seconds);
+
+    if remaining_ms == 0 {
+      return None;
+    }
+    if level > last_level {
+      return Some(TimeWarning::Threshold(level - 1));
+    }
+    if remaining_ms < self.warnings.countdown_ms && seconds < last_seconds {
+      return Some(TimeWarning::Countdown);
+    }
+    None
+  }
+}
+
+impl Default for LowTimeAlarm {
+  fn default() -> Self {
+    Self::new(LowTimeWarnings::default())
+  }
+}
+
 /// Player color for timer
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Color {
@@ -26,6 +373,15 @@ pub enum Color {
   Black,
 }
 
+impl Color {
+  pub fn opponent(self) -> Self {
+    match self {
+      Color::White => Color::Black,
+      Color::Black => Color::White,
+    }
+  }
+}
+
 impl Default for ChessTimer {
   fn default() -> Self {
     Self::new()
@@ -35,13 +391,32 @@ impl Default for ChessTimer {
 impl ChessTimer {
   /// Create a new timer with default time control (10+0)
   pub fn new() -> Self {
+    Self::with_time_control(TimeControl::default())
+  }
+
+  /// Create a new timer for the given time control
+  pub fn with_time_control(time_control: TimeControl) -> Self {
     Self {
-      white_time_ms: INITIAL_TIME_MS,
-      black_time_ms: INITIAL_TIME_MS,
+      white_time_ms: time_control.white_base_ms,
+      black_time_ms: time_control.black_base_ms,
       running: false,
+      time_control,
+      move_elapsed_ms: 0,
+      stages: [0; 2],
+      overruns: [0; 2],
     }
   }
 
+  /// Get the time control
+  pub fn time_control(&self) -> TimeControl {
+    self.time_control
+  }
+
+  /// Switch to another time control and reset both clocks to it
+  pub fn set_time_control(&mut self, time_control: TimeControl) {
+    *self = Self::with_time_control(time_control);
+  }
+
   /// Start the timer
   pub fn start(&mut self) {
     self.running = true;
@@ -52,12 +427,58 @@ impl ChessTimer {
     self.running = false;
   }
 
-
   /// Reset both clocks to initial time
   pub fn reset(&mut self) {
-    self.white_time_ms = INITIAL_TIME_MS;
-    self.black_time_ms = INITIAL_TIME_MS;
+    self.white_time_ms = self.time_control.white_base_ms;
+    self.black_time_ms = self.time_control.black_base_ms;
     self.running = false;
+    self.move_elapsed_ms = 0;
+    self.stages = [0; 2];
+    self.overruns = [0; 2];
+  }
+
+  /// Notify the timer that `color` completed their `moves_played`th move
+  ///
+  /// Adds the increment and any Bronstein refund, restarts the delay for the
+  /// next move and enters the next stage when its move count is reached.
+  /// Returns `true` if a new stage began.
+  pub fn move_completed(&mut self, color: Color, moves_played: u16) -> bool {
+    if let ClockMode::FixedPerMove(_) = self.time_control.mode {
+      *self.time_mut(color) = self.time_control.base_time(color);
+      self.move_elapsed_ms = 0;
+      return false;
+    }
+
+    let stage = self.stage(color);
+    let mut added = self.time_control.increment(stage);
+    if let Delay::Bronstein(delay_ms) = self.time_control.delay {
+      added = added.saturating_add(self.move_elapsed_ms.min(delay_ms));
+    }
+    self.move_elapsed_ms = 0;
+
+    let next = self
+      .time_control
+      .stages
+      .get(stage as usize)
+      .copied()
+      .flatten();
+    let new_stage = match next {
+      Some(next) if moves_played >= next.after_moves => {
+        added = added.saturating_add(next.added_ms);
+        self.stages[color as usize] = stage + 1;
+        true
+      }
+      _ => false,
+    };
+
+    let time = self.time_mut(color);
+    *time = time.saturating_add(added);
+    new_stage
+  }
+
+  /// Stage a player is in (0 = base time)
+  pub fn stage(&self, color: Color) -> u8 {
+    self.stages[color as usize]
   }
 
   /// Update the timer, decrementing the active player's time
@@ -68,16 +489,83 @@ impl ChessTimer {
       return false;
     }
 
-    match active_color {
-      Color::White => {
-        self.white_time_ms = self.white_time_ms.saturating_sub(elapsed_ms);
-        self.white_time_ms == 0
+    // A simple delay is used up before the clock itself runs
+    let charged_ms = match self.time_control.delay {
+      Delay::Simple(_) => elapsed_ms.saturating_sub(self.delay_remaining()),
+      _ => elapsed_ms,
+    };
+    self.move_elapsed_ms = self.move_elapsed_ms.saturating_add(elapsed_ms);
+
+    let drained_ms = charged_ms.min(self.time_remaining(active_color));
+    *self.time_mut(active_color) -= drained_ms;
+    if self.time_control.mode == ClockMode::Hourglass {
+      let opponent = self.time_mut(active_color.opponent());
+      *opponent = opponent.saturating_add(drained_ms);
+    }
+
+    if self.time_remaining(active_color) > 0 {
+      return false;
+    }
+
+    match self.time_control.mode {
+      ClockMode::FixedPerMove(MoveTimePenalty::Overruns(limit)) => {
+        let overruns = &mut self.overruns[active_color as usize];
+        *overruns = overruns.saturating_add(1);
+        if *overruns >= limit {
+          return true;
+        }
+        // Another full move time to finish the move; the time spent on the
+        // move, and so any delay, keeps counting from the start of the move
+        *self.time_mut(active_color) = self.time_control.base_time(active_color);
+        false
       }
-      Color::Black => {
-        self.black_time_ms = self.black_time_ms.saturating_sub(elapsed_ms);
-        self.black_time_ms == 0
+      _ => true,
+    }
+  }
+
+  /// Move time overruns so far in fixed-time-per-move mode
+  pub fn overruns(&self, color: Color) -> u8 {
+    self.overruns[color as usize]
+  }
+
+  /// Put both clocks back to the start of a move, starting the move time afresh
+  ///
+  /// Times, overruns and completed moves are indexed white, black; each
+  /// player's stage follows from their completed moves.
+  pub fn rewind(&mut self, times_ms: [u32; 2], overruns: [u8; 2], moves_played: [u16; 2]) {
+    self.white_time_ms = times_ms[Color::White as usize];
+    self.black_time_ms = times_ms[Color::Black as usize];
+    self.overruns = overruns;
+    self.stages = [
+      self.stage_after(moves_played[Color::White as usize]),
+      self.stage_after(moves_played[Color::Black as usize]),
+    ];
+    self.move_elapsed_ms = 0;
+  }
+
+  /// Stage a player is in after completing `moves_played` moves
+  fn stage_after(&self, moves_played: u16) -> u8 {
+    if let ClockMode::FixedPerMove(_) = self.time_control.mode {
+      return 0;
+    }
+
+    // As in `move_completed`, at most one stage begins per move
+    let mut stage = 0;
+    for completed in 1..=moves_played {
+      match self.time_control.stages.get(stage as usize).copied().flatten() {
+        Some(next) if completed >= next.after_moves => stage += 1,
+        Some(_) => {}
+        None => break,
       }
     }
+    stage
+  }
+
+  fn time_mut(&mut self, color: Color) -> &mut u32 {
+    match color {
+      Color::White => &mut self.white_time_ms,
+      Color::Black => &mut self.black_time_ms,
+    }
   }
 
   /// Get remaining time for a player in milliseconds
@@ -88,9 +576,35 @@ impl ChessTimer {
     }
   }
 
-  /// Check if a player is in low time (< 30 seconds)
+  /// Time spent on the current move so far in milliseconds
+  pub fn move_elapsed(&self) -> u32 {
+    self.move_elapsed_ms
+  }
+
+  /// Delay left on the current move in milliseconds
+  pub fn delay_remaining(&self) -> u32 {
+    self
+      .time_control
+      .delay
+      .duration_ms()
+      .saturating_sub(self.move_elapsed_ms)
+  }
+
+  /// Whether the player to move is still within the delay
+  pub fn in_delay(&self) -> bool {
+    self.delay_remaining() > 0
+  }
+
+  /// Check if a player is in low time (< 30 seconds, or the last third of
+  /// the move time in fixed-time-per-move mode)
   pub fn is_low_time(&self, color: Color) -> bool {
-    self.time_remaining(color) < LOW_TIME_THRESHOLD_MS
+    let threshold = match self.time_control.mode {
+      ClockMode::FixedPerMove(_) => {
+        LOW_TIME_THRESHOLD_MS.min(self.time_control.base_time(color) / 3)
+      }
+      _ => LOW_TIME_THRESHOLD_MS,
+    };
+    self.time_remaining(color) < threshold
   }
 
   fn format_time(time_ms: u32) -> (u8, u8) {
@@ -104,6 +618,11 @@ impl ChessTimer {
   pub fn formatted_time(&self, color: Color) -> (u8, u8) {
     Self::format_time(self.time_remaining(color))
   }
+
+  /// Remaining time for a player as shown on their clock face
+  pub fn display_time(&self, color: Color) -> TimeDisplay {
+    TimeDisplay::from_ms(self.time_remaining(color))
+  }
 }
 
 #[cfg(test)]
@@ -209,6 +728,261 @@ mod tests {
     assert!(!timer.running);
   }
 
+  #[test]
+  fn test_increment_added_after_move() {
+    let mut timer = ChessTimer::with_time_control(TimeControl::BLITZ_3_2);
+    assert_eq!(timer.formatted_time(Color::White), (3, 0));
+    timer.start();
+
+    timer.tick(Color::White, 5_000);
+    timer.move_completed(Color::White, 1);
+    assert_eq!(timer.time_remaining(Color::White), 180_000 - 5_000 + 2_000);
+    assert_eq!(timer.time_remaining(Color::Black), 180_000);
+
+    timer.tick(Color::Black, 1_000);
+    timer.move_completed(Color::Black, 1);
+    assert_eq!(timer.time_remaining(Color::Black), 181_000);
+  }
+
+  #[test]
+  fn test_no_increment_by_default() {
+    let mut timer = ChessTimer::new();
+    timer.move_completed(Color::White, 1);
+    assert_eq!(timer.time_remaining(Color::White), INITIAL_TIME_MS);
+  }
+
+  #[test]
+  fn test_time_odds() {
+    let odds = TimeControl::odds(5 * 60 * 1000, 2 * 60 * 1000, 0);
+    let mut timer = ChessTimer::with_time_control(odds);
+    assert_eq!(timer.formatted_time(Color::White), (5, 0));
+    assert_eq!(timer.formatted_time(Color::Black), (2, 0));
+
+    timer.start();
+    timer.tick(Color::Black, 60_000);
+    timer.reset();
+    assert_eq!(
+      timer.time_remaining(Color::Black),
+      odds.base_time(Color::Black)
+    );
+  }
+
+  #[test]
+  fn test_set_time_control() {
+    let mut timer = ChessTimer::new();
+    timer.start();
+    timer.tick(Color::White, 60_000);
+
+    timer.set_time_control(TimeControl::RAPID_15_10);
+    assert_eq!(timer.time_control(), TimeControl::RAPID_15_10);
+    assert_eq!(timer.formatted_time(Color::White), (15, 0));
+    assert_eq!(timer.formatted_time(Color::Black), (15, 0));
+    assert!(!timer.running);
+  }
+
+  #[test]
+  fn test_simple_delay() {
+    let tc = TimeControl::minutes(5, 0).with_delay(Delay::Simple(5_000));
+    let mut timer = ChessTimer::with_time_control(tc);
+    timer.start();
+
+    // The first five seconds of a move are free
+    timer.tick(Color::White, 3_000);
+    assert_eq!(timer.time_remaining(Color::White), 300_000);
+    assert!(timer.in_delay());
+    assert_eq!(timer.delay_remaining(), 2_000);
+
+    timer.tick(Color::White, 3_000);
+    assert_eq!(timer.time_remaining(Color::White), 299_000);
+    assert!(!timer.in_delay());
+
+    // The delay starts again on the next move, and time is never added
+    timer.move_completed(Color::White, 1);
+    assert!(timer.in_delay());
+    assert_eq!(timer.time_remaining(Color::White), 299_000);
+    timer.tick(Color::Black, 4_000);
+    assert_eq!(timer.time_remaining(Color::Black), 300_000);
+  }
+
+  #[test]
+  fn test_bronstein_delay() {
+    let tc = TimeControl::minutes(5, 0).with_delay(Delay::Bronstein(5_000));
+    let mut timer = ChessTimer::with_time_control(tc);
+    timer.start();
+
+    // Used time is refunded in full up to the delay
+    timer.tick(Color::White, 3_000);
+    assert_eq!(timer.time_remaining(Color::White), 297_000);
+    assert!(timer.in_delay());
+    timer.move_completed(Color::White, 1);
+    assert_eq!(timer.time_remaining(Color::White), 300_000);
+
+    // ...and capped at the delay for longer moves
+    timer.tick(Color::Black, 8_000);
+    assert!(!timer.in_delay());
+    timer.move_completed(Color::Black, 1);
+    assert_eq!(timer.time_remaining(Color::Black), 297_000);
+  }
+
+  #[test]
+  fn test_no_delay() {
+    let mut timer = ChessTimer::new();
+    timer.start();
+    assert!(!timer.in_delay());
+    timer.tick(Color::White, 1_000);
+    assert_eq!(timer.delay_remaining(), 0);
+  }
+
+  #[test]
+  fn test_stages_begin_after_move_count() {
+    let mut timer = ChessTimer::with_time_control(TimeControl::CLASSICAL_90_30);
+    timer.start();
+
+    // Increment from move 1
+    assert!(!timer.move_completed(Color::White, 1));
+    assert_eq!(timer.time_remaining(Color::White), 90 * 60_000 + 30_000);
+
+    for moves in 2..40 {
+      assert!(!timer.move_completed(Color::White, moves));
+    }
+    assert_eq!(timer.stage(Color::White), 0);
+
+    // Move 40 adds the second period on top of the increment
+    assert!(timer.move_completed(Color::White, 40));
+    assert_eq!(timer.stage(Color::White), 1);
+    assert_eq!(
+      timer.time_remaining(Color::White),
+      90 * 60_000 + 40 * 30_000 + 30 * 60_000
+    );
+    assert_eq!(timer.stage(Color::Black), 0);
+
+    // No further stages
+    assert!(!timer.move_completed(Color::White, 41));
+    assert_eq!(timer.stage(Color::White), 1);
+
+    timer.reset();
+    assert_eq!(timer.stage(Color::White), 0);
+  }
+
+  #[test]
+  fn test_stage_changes_increment() {
+    let tc = TimeControl::minutes(60, 0).with_stage(TimeStage {
+      after_moves: 2,
+      added_ms: 0,
+      increment_ms: 5_000,
+    });
+    let mut timer = ChessTimer::with_time_control(tc);
+    timer.move_completed(Color::Black, 1);
+    assert_eq!(timer.time_remaining(Color::Black), 3_600_000);
+    timer.move_completed(Color::Black, 2);
+    assert_eq!(timer.time_remaining(Color::Black), 3_600_000);
+    timer.move_completed(Color::Black, 3);
+    assert_eq!(timer.time_remaining(Color::Black), 3_605_000);
+  }
+
+  #[test]
+  fn test_with_stage_keeps_order() {
+    let stage = |after_moves| TimeStage {
+      after_moves,
+      added_ms: 0,
+      increment_ms: 0,
+    };
+    let mut tc = TimeControl::minutes(120, 0);
+    for after_moves in [40, 60, 80, 100] {
+      tc = tc.with_stage(stage(after_moves));
+    }
+    assert_eq!(
+      tc.stages,
+      [Some(stage(40)), Some(stage(60)), Some(stage(80))]
+    );
+  }
+
+  #[test]
+  fn test_hourglass_moves_time_to_opponent() {
+    let mut timer = ChessTimer::with_time_control(TimeControl::hourglass(60_000));
+    timer.start();
+
+    timer.tick(Color::White, 10_000);
+    assert_eq!(timer.time_remaining(Color::White), 50_000);
+    assert_eq!(timer.time_remaining(Color::Black), 70_000);
+
+    timer.move_completed(Color::White, 1);
+    timer.tick(Color::Black, 5_000);
+    assert_eq!(timer.time_remaining(Color::White), 55_000);
+    assert_eq!(timer.time_remaining(Color::Black), 65_000);
+
+    // Only the time actually left can drain across
+    assert!(timer.tick(Color::Black, 100_000));
+    assert_eq!(timer.time_remaining(Color::White), 120_000);
+  }
+
+  #[test]
+  fn test_fixed_time_per_move_restarts() {
+    let mut timer =
+      ChessTimer::with_time_control(TimeControl::per_move(30_000, MoveTimePenalty::Loss));
+    timer.start();
+
+    timer.tick(Color::White, 21_000);
+    assert!(timer.is_low_time(Color::White));
+    timer.move_completed(Color::White, 1);
+    assert_eq!(timer.time_remaining(Color::White), 30_000);
+    assert!(!timer.is_low_time(Color::White));
+
+    assert!(!timer.tick(Color::Black, 29_900));
+    assert!(timer.tick(Color::Black, 100));
+  }
+
+  #[test]
+  fn test_fixed_time_per_move_overruns() {
+    let tc = TimeControl::per_move(30_000, MoveTimePenalty::Overruns(2));
+    let mut timer = ChessTimer::with_time_control(tc);
+    timer.start();
+
+    // The first overrun gives the move time again
+    assert!(!timer.tick(Color::White, 30_000));
+    assert_eq!(timer.overruns(Color::White), 1);
+    assert_eq!(timer.time_remaining(Color::White), 30_000);
+    timer.move_completed(Color::White, 1);
+
+    // Overruns are counted per player
+    assert!(!timer.tick(Color::Black, 30_000));
+    assert_eq!(timer.overruns(Color::Black), 1);
+    timer.move_completed(Color::Black, 1);
+
+    // White's second overrun loses
+    assert!(timer.tick(Color::White, 30_000));
+    assert_eq!(timer.overruns(Color::White), 2);
+  }
+
+  #[test]
+  fn test_overrun_keeps_move_elapsed() {
+    let tc = TimeControl::per_move(30_000, MoveTimePenalty::Overruns(3));
+    let mut timer = ChessTimer::with_time_control(tc);
+    timer.start();
+
+    assert!(!timer.tick(Color::White, 29_000));
+    assert!(!timer.tick(Color::White, 2_000));
+    assert!(!timer.tick(Color::White, 5_000));
+    assert_eq!(timer.overruns(Color::White), 1);
+    assert_eq!(timer.time_remaining(Color::White), 25_000);
+    assert_eq!(timer.move_elapsed(), 36_000);
+  }
+
+  #[test]
+  fn test_overrun_does_not_grant_delay_again() {
+    let tc =
+      TimeControl::per_move(10_000, MoveTimePenalty::Overruns(3)).with_delay(Delay::Simple(5_000));
+    let mut timer = ChessTimer::with_time_control(tc);
+    timer.start();
+
+    // The delay is used up before the move time runs out
+    assert!(!timer.tick(Color::White, 15_000));
+    assert_eq!(timer.overruns(Color::White), 1);
+    assert!(!timer.in_delay());
+    assert!(!timer.tick(Color::White, 1_000));
+    assert_eq!(timer.time_remaining(Color::White), 9_000);
+  }
+
   #[test]
   fn test_saturating_sub() {
     let mut timer = ChessTimer::new();
@@ -218,4 +992,147 @@ mod tests {
     timer.tick(Color::White, INITIAL_TIME_MS + 10000);
     assert_eq!(timer.time_remaining(Color::White), 0);
   }
+
+  #[test]
+  fn test_display_time_tenths() {
+    let mut timer = ChessTimer::new();
+    assert_eq!(
+      timer.display_time(Color::White),
+      TimeDisplay::MinutesSeconds(10, 0)
+    );
+
+    timer.start();
+    timer.tick(Color::White, INITIAL_TIME_MS - 10_000);
+    assert_eq!(
+      timer.display_time(Color::White),
+      TimeDisplay::MinutesSeconds(0, 10)
+    );
+
+    timer.tick(Color::White, 1);
+    assert_eq!(
+      timer.display_time(Color::White),
+      TimeDisplay::SecondsTenths(9, 9)
+    );
+
+    timer.tick(Color::White, 9_349);
+    assert_eq!(
+      timer.display_time(Color::White),
+      TimeDisplay::SecondsTenths(0, 6)
+    );
+  }
+
+  #[test]
+  fn test_low_time_alarm_thresholds() {
+    let mut timer = ChessTimer::new();
+    let mut alarm = LowTimeAlarm::default();
+    timer.start();
+
+    assert_eq!(alarm.check(&timer, Color::White), None);
+    timer.tick(Color::White, INITIAL_TIME_MS - 60_000);
+    assert_eq!(alarm.check(&timer, Color::White), None);
+
+    // Each threshold sounds once, with its own warning
+    timer.tick(Color::White, 100);
+    assert_eq!(
+      alarm.check(&timer, Color::White),
+      Some(TimeWarning::Threshold(0))
+    );
+    timer.tick(Color::White, 100);
+    assert_eq!(alarm.check(&timer, Color::White), None);
+
+    timer.tick(Color::White, 29_800);
+    assert_eq!(alarm.check(&timer, Color::White), None);
+    timer.tick(Color::White, 100);
+    assert_eq!(
+      alarm.check(&timer, Color::White),
+      Some(TimeWarning::Threshold(1))
+    );
+
+    // Crossing several at once sounds only the most urgent
+    assert_eq!(alarm.check(&timer, Color::Black), None);
+    timer.tick(Color::Black, INITIAL_TIME_MS - 9_000);
+    assert_eq!(
+      alarm.check(&timer, Color::Black),
+      Some(TimeWarning::Threshold(2))
+    );
+  }
+
+  #[test]
+  fn test_low_time_alarm_rearms() {
+    let mut timer = ChessTimer::with_time_control(TimeControl::new(60_000, 20_000));
+    let mut alarm = LowTimeAlarm::default();
+    timer.start();
+
+    timer.tick(Color::White, 31_000);
+    assert_eq!(
+      alarm.check(&timer, Color::White),
+      Some(TimeWarning::Threshold(1))
+    );
+
+    // The increment lifts white back above 30 seconds
+    timer.move_completed(Color::White, 1);
+    assert_eq!(alarm.check(&timer, Color::White), None);
+    timer.tick(Color::White, 20_000);
+    assert_eq!(
+      alarm.check(&timer, Color::White),
+      Some(TimeWarning::Threshold(1))
+    );
+  }
+
+  #[test]
+  fn test_low_time_alarm_countdown() {
+    let mut timer = ChessTimer::new();
+    let mut alarm = LowTimeAlarm::new(LowTimeWarnings {
+      thresholds_ms: [None; MAX_LOW_TIME_WARNINGS],
+      countdown_ms: 3_000,
+    });
+    timer.start();
+
+    let mut beeps = 0;
+    timer.tick(Color::White, INITIAL_TIME_MS - 5_000);
+    while timer.time_remaining(Color::White) > 0 {
+      if alarm.check(&timer, Color::White) == Some(TimeWarning::Countdown) {
+        beeps += 1;
+      }
+      timer.tick(Color::White, 100);
+    }
+    assert_eq!(beeps, 3);
+    assert_eq!(alarm.check(&timer, Color::White), None);
+  }
+
+  #[test]
+  fn test_low_time_alarm_skips_thresholds_above_base_time() {
+    let mut timer =
+      ChessTimer::with_time_control(TimeControl::per_move(30_000, MoveTimePenalty::Loss));
+    let mut alarm = LowTimeAlarm::default();
+    timer.start();
+
+    timer.tick(Color::White, 100);
+    assert_eq!(alarm.check(&timer, Color::White), None);
+    timer.tick(Color::White, 20_000);
+    assert_eq!(
+      alarm.check(&timer, Color::White),
+      Some(TimeWarning::Threshold(2))
+    );
+  }
+
+  #[test]
+  fn test_rewind() {
+    let mut timer = ChessTimer::with_time_control(TimeControl::minutes(5, 0).with_stage(TimeStage {
+      after_moves: 2,
+      added_ms: 60_000,
+      increment_ms: 0,
+    }));
+    timer.start();
+    timer.tick(Color::White, 7_000);
+
+    timer.rewind([175_000, 180_000], [0, 1], [2, 1]);
+    assert_eq!(timer.time_remaining(Color::White), 175_000);
+    assert_eq!(timer.time_remaining(Color::Black), 180_000);
+    assert_eq!(timer.stage(Color::White), 1);
+    assert_eq!(timer.stage(Color::Black), 0);
+    assert_eq!(timer.overruns(Color::Black), 1);
+    assert_eq!(timer.move_elapsed(), 0);
+    assert!(timer.running);
+  }
 }
@@ -0,0 +1,68 @@
+//! Zobrist hashing keys
+//!
+//! Keys are generated at compile time with a fixed-seed SplitMix64
+//! generator, so hashes are stable across builds.
+
+use crate::game::chess::{CastlingRights, Piece, PieceColor, PieceType};
+
+const PIECE_KEYS: usize = 12 * 64;
+const SIDE_KEY: usize = PIECE_KEYS;
+const CASTLING_KEYS: usize = SIDE_KEY + 1;
+const EN_PASSANT_KEYS: usize = CASTLING_KEYS + 4;
+const KEY_COUNT: usize = EN_PASSANT_KEYS + 8;
+
+static KEYS: [u64; KEY_COUNT] = generate_keys();
+
+const fn generate_keys() -> [u64; KEY_COUNT] {
+  let mut keys = [0u64; KEY_COUNT];
+  let mut state: u64 = 0x2545_F491_4F6C_DD1D;
+  let mut i = 0;
+  while i < KEY_COUNT {
+    state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    keys[i] = z ^ (z >> 31);
+    i += 1;
+  }
+  keys
+}
+
+/// Key for a piece standing on a square
+pub(crate) fn piece(piece: Piece, sq: u8) -> u64 {
+  let type_index = match piece.piece_type {
+    PieceType::Pawn => 0,
+    PieceType::Knight => 1,
+    PieceType::Bishop => 2,
+    PieceType::Rook => 3,
+    PieceType::Queen => 4,
+    PieceType::King => 5,
+  };
+  let color_index = match piece.color {
+    PieceColor::White => 0,
+    PieceColor::Black => 6,
+  };
+  KEYS[(type_index + color_index) * 64 + sq as usize]
+}
+
+/// Key toggled when black is to move
+pub(crate) fn black_to_move() -> u64 {
+  KEYS[SIDE_KEY]
+}
+
+/// Combined key for both sides' castling rights
+pub(crate) fn castling(white: CastlingRights, black: CastlingRights) -> u64 {
+  let rights = [white.kingside, white.queenside, black.kingside, black.queenside];
+  let mut hash = 0;
+  for (i, &allowed) in rights.iter().enumerate() {
+    if allowed {
+      hash ^= KEYS[CASTLING_KEYS + i];
+    }
+  }
+  hash
+}
+
+/// Key for the file of an en passant target square
+pub(crate) fn en_passant(sq: u8) -> u64 {
+  KEYS[EN_PASSANT_KEYS + (sq % 8) as usize]
+}
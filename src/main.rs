@@ -19,9 +19,13 @@ use embedded_hal::delay::DelayNs;
 use sam3x8e_hal::pac::{PIOB, PIOC, TC0};
 
 #[cfg(target_arch = "arm")]
-use certabo::app::{App, Hardware};
+use certabo::app::buttons::{ButtonEdge, GestureRecognizer};
 #[cfg(target_arch = "arm")]
-use certabo::arm_io::{ArmBoard, ArmBuzzer, ArmClockDisplay, ArmDelay, ArmDisplay};
+use certabo::app::{App, AppState, Button, Hardware, TimeSource};
+#[cfg(target_arch = "arm")]
+use certabo::arm_io::{
+    ArmBoard, ArmBuzzer, ArmClockDisplay, ArmDelay, ArmDisplay, ArmRandom, ArmTimeSource,
+};
 #[cfg(target_arch = "arm")]
 use certabo::buzzer::Buzzer;
 #[cfg(target_arch = "arm")]
@@ -31,7 +35,9 @@ use certabo::certabo::protocol::RfidReading;
 #[cfg(target_arch = "arm")]
 use certabo::display::Display;
 #[cfg(target_arch = "arm")]
-use certabo::events::{consume, BLUE_BUTTON_PRESSED, MILLIS, TIMER_TICK, WHITE_BUTTON_PRESSED};
+use certabo::events::{consume, pop_button_edge, push_button_edge, TIMER_TICK};
+#[cfg(target_arch = "arm")]
+use certabo::game::pgn::{write_pgn_with_clocks, PgnTags};
 #[cfg(target_arch = "arm")]
 use certabo::i2c::I2C;
 #[cfg(target_arch = "arm")]
@@ -58,12 +64,15 @@ fn main() -> ! {
     ChessClockDisplays::init();
 
     let mut app = App::new();
+    app.set_study_game(include_bytes!("../games/opera.pgn"));
     let mut hw = Hardware {
         board: ArmBoard,
         display: ArmDisplay,
         buzzer: ArmBuzzer,
         clock: ArmClockDisplay,
         delay: ArmDelay,
+        time: ArmTimeSource,
+        random: ArmRandom,
     };
 
     Display::with(|d| {
@@ -72,29 +81,41 @@ fn main() -> ! {
     Peripherals::with(|p| p.delay.delay_ms(1000));
 
     app.start(&mut hw);
+    let mut game_archived = false;
+    let mut gestures = GestureRecognizer::default();
 
     loop {
-        if consume(&BLUE_BUTTON_PRESSED) {
-            app.on_blue_button(&mut hw.display);
+        while let Some(edge) = pop_button_edge() {
+            if let Some(event) = gestures.push(edge) {
+                app.on_button_event(event, &mut hw.display, &mut hw.random);
+            }
         }
-
-        if consume(&WHITE_BUTTON_PRESSED) {
-            app.on_white_button(&mut hw.display);
+        while let Some(event) = gestures.poll(hw.time.now_ms()) {
+            app.on_button_event(event, &mut hw.display, &mut hw.random);
         }
 
+        // TIMER_TICK only paces the display; the clocks run on RTT timestamps
         if consume(&TIMER_TICK) {
-            app.tick(100, &mut hw.display, &mut hw.buzzer, &mut hw.clock);
+            let now = hw.time.now_ms();
+            app.tick_at(now, &mut hw.display, &mut hw.buzzer, &mut hw.clock);
             app.update_display(&mut hw.display);
         }
 
         hw.board.poll();
+        let received_at = hw.time.now_ms();
 
         let mut line_buffer = [0u8; MAX_LINE_LEN];
         if let Some(len) = hw.board.read_line(&mut line_buffer) {
             if len > 2 {
                 let trimmed = &line_buffer[1..len - 1];
                 if let Some(reading) = RfidReading::parse(trimmed) {
-                    app.on_board_reading(reading, &mut hw.display, &mut hw.buzzer, &mut hw.delay);
+                    app.on_board_reading_at(
+                        reading,
+                        received_at,
+                        &mut hw.display,
+                        &mut hw.buzzer,
+                        &mut hw.delay,
+                    );
                 }
             }
         }
@@ -104,25 +125,48 @@ fn main() -> ! {
             app.leds_sent();
         }
 
+        // Archive finished games as PGN over the serial port
+        if app.state() == AppState::GameEnded {
+            if !game_archived {
+                Serial::with(|serial| {
+                    let _ = write_pgn_with_clocks(serial, app.game(), &PgnTags::default());
+                    let _ = serial.write_str("\n\r");
+                });
+                game_archived = true;
+            }
+        } else {
+            game_archived = false;
+        }
+
         Peripherals::with(|p| p.delay.delay_ms(10u32));
     }
 }
 
+// The buttons pull their pins low while pressed
+
 #[cfg(target_arch = "arm")]
 #[unsafe(no_mangle)]
 pub extern "C" fn PIOB() {
-    let isr = unsafe { (*PIOB::ptr()).isr().read().bits() };
-    if isr & (1 << 25) != 0 {
-        BLUE_BUTTON_PRESSED.store(true, Ordering::SeqCst);
+    let piob = unsafe { &*PIOB::ptr() };
+    if piob.isr().read().bits() & (1 << 25) != 0 {
+        push_button_edge(ButtonEdge {
+            button: Button::Blue,
+            pressed: piob.pdsr().read().bits() & (1 << 25) == 0,
+            at_ms: ArmTimeSource.now_ms(),
+        });
     }
 }
 
 #[cfg(target_arch = "arm")]
 #[unsafe(no_mangle)]
 pub extern "C" fn PIOC() {
-    let isr = unsafe { (*PIOC::ptr()).isr().read().bits() };
-    if isr & (1 << 28) != 0 {
-        WHITE_BUTTON_PRESSED.store(true, Ordering::SeqCst);
+    let pioc = unsafe { &*PIOC::ptr() };
+    if pioc.isr().read().bits() & (1 << 28) != 0 {
+        push_button_edge(ButtonEdge {
+            button: Button::White,
+            pressed: pioc.pdsr().read().bits() & (1 << 28) == 0,
+            at_ms: ArmTimeSource.now_ms(),
+        });
     }
 }
 
@@ -130,7 +174,6 @@ pub extern "C" fn PIOC() {
 #[unsafe(no_mangle)]
 pub extern "C" fn TC0() {
     let _ = unsafe { (*TC0::ptr()).sr0().read() };
-    MILLIS.fetch_add(100, Ordering::Relaxed);
     TIMER_TICK.store(true, Ordering::SeqCst);
 }
 
@@ -9,6 +9,7 @@ use sam3x8e_hal::pac as sam3x8e;
 use sam3x8e_hal::pac::{Interrupt, PIOB, PIOC, RTT, TC0, TWI0, UART, UOTGHS};
 use sam3x8e_hal::pmc::{Config, MainOscillator, PeripheralClock, Pmc, PmcExt};
 use sam3x8e_hal::prelude::*;
+use sam3x8e_hal::rng::Rng;
 use sam3x8e_hal::time::Hertz;
 use sam3x8e_hal::timer::Timer;
 
@@ -24,6 +25,7 @@ pub struct Peripherals {
   pub white_button: PC28<Input<PullUp>>,
   pub buzzer: PA29<Output<PushPull>>,
   pub timer: Timer<RTT>,
+  pub rng: Rng,
   // TM1637 display pins (Grove D7 = White clock, D5 = Black clock)
   pub white_clk: PC23<Output<PushPull>>,
   pub white_dio: PC22<Output<PushPull>>,
@@ -41,6 +43,9 @@ impl Peripherals {
       p.WDT.mr().write_with_zero(|w| w.wddis().set_bit());
     }
 
+    // TRNG (enables its peripheral clock, so set up before freezing the PMC)
+    let rng = Rng::new(p.TRNG, &p.PMC);
+
     let mut pmc = p
       .PMC
       .freeze(Config::main_clock(MainOscillator::XtalOscillator));
@@ -100,7 +105,7 @@ impl Peripherals {
       .pc24
       .into_push_pull_output(&mut pioc.mddr, &mut pioc.oer);
 
-    // Configure button interrupts (falling edge on PB25 and PC28)
+    // Configure button interrupts (both edges on PB25 and PC28)
     configure_button_interrupts();
 
     // Configure TC0 for 100ms periodic interrupt
@@ -127,6 +132,7 @@ impl Peripherals {
         pmc,
         delay,
         timer,
+        rng,
         white_clk,
         white_dio,
         black_clk,
@@ -149,34 +155,30 @@ impl Peripherals {
   }
 }
 
-/// Configure PIO interrupts for buttons (falling edge).
+/// Configure PIO interrupts for buttons (both edges).
+///
+/// Presses and releases are both needed to time long and double presses.
 fn configure_button_interrupts() {
   unsafe {
     let piob = &*PIOB::ptr();
     let pioc = &*PIOC::ptr();
 
-    // Blue button on PB25: enable PIO and configure falling edge interrupt
+    // Blue button on PB25: enable PIO and configure input change interrupt
     // PER: PIO Enable Register - enable PIO control of this pin
     piob.per().write_with_zero(|w| w.bits(1 << 25));
     // ODR: Output Disable Register - configure as input
     piob.odr().write_with_zero(|w| w.bits(1 << 25));
-    // AIMER: Additional Interrupt Modes Enable
-    piob.aimer().write_with_zero(|w| w.bits(1 << 25));
-    // ESR: Edge Select Register (1 = edge, not level)
-    piob.esr().write_with_zero(|w| w.bits(1 << 25));
-    // FELLSR: Falling Edge/Low Level Select (1 = falling edge)
-    piob.fellsr().write_with_zero(|w| w.bits(1 << 25));
+    // AIMDR: Additional Interrupt Modes Disable - interrupt on any input change
+    piob.aimdr().write_with_zero(|w| w.bits(1 << 25));
     // Clear any pending interrupt by reading ISR
     let _ = piob.isr().read();
     // IER: Interrupt Enable Register
     piob.ier().write_with_zero(|w| w.bits(1 << 25));
 
-    // White button on PC28: enable PIO and configure falling edge interrupt
+    // White button on PC28: enable PIO and configure input change interrupt
     pioc.per().write_with_zero(|w| w.bits(1 << 28));
     pioc.odr().write_with_zero(|w| w.bits(1 << 28));
-    pioc.aimer().write_with_zero(|w| w.bits(1 << 28));
-    pioc.esr().write_with_zero(|w| w.bits(1 << 28));
-    pioc.fellsr().write_with_zero(|w| w.bits(1 << 28));
+    pioc.aimdr().write_with_zero(|w| w.bits(1 << 28));
     let _ = pioc.isr().read();
     pioc.ier().write_with_zero(|w| w.bits(1 << 28));
   }
@@ -211,6 +213,8 @@ fn configure_timer_interrupt(tc0: &TC0, pmc: &mut Pmc) {
     // Empirically adjusted - system clock appears to be ~21MHz (84/4)
     // 21MHz / 128 = 164,062.5 Hz
     // 164,062.5 * 0.1s = 16,406 counts
+    // Only the display refresh depends on this; the game clock is measured
+    // with the RTT (see `ArmTimeSource`), so an inexact period loses no time
     tc0.rc0().write(|w| w.rc().bits(16406));
 
     // Enable RC compare interrupt (CPCS)
@@ -4,6 +4,7 @@
 //! - White's clock: D7 connector (PC23=CLK, PC22=DIO)
 //! - Black's clock: D5 connector (PC25=CLK, PC24=DIO)
 
+use crate::game::timer::TimeDisplay;
 use crate::peripherals::Peripherals;
 use core::cell::RefCell;
 use critical_section::Mutex;
@@ -45,8 +46,8 @@ impl ChessClockDisplays {
     let displays = ChessClockDisplays { colon_visible: true };
 
     // Initialize both displays with "00:00"
-    displays.update_white(0, 0, false);
-    displays.update_black(0, 0, false);
+    displays.update_white(TimeDisplay::MinutesSeconds(0, 0), false, false);
+    displays.update_black(TimeDisplay::MinutesSeconds(0, 0), false, false);
 
     critical_section::with(|cs| {
       CHESS_CLOCKS.borrow(cs).replace(Some(displays));
@@ -72,12 +73,12 @@ impl ChessClockDisplays {
 
   /// Update White's clock display
   ///
-  /// - `minutes`: 0-99
-  /// - `seconds`: 0-59
+  /// - `time`: MM:SS (minutes 0-99), or SS.t under ten seconds
   /// - `active`: if true, show blinking colon
-  pub fn update_white(&self, minutes: u8, seconds: u8, active: bool) {
-    let show_colon = if active { self.colon_visible } else { true };
-    let data = self.format_time(minutes, seconds, show_colon);
+  /// - `in_delay`: if true, the per-move delay is running; colon stays off
+  pub fn update_white(&self, time: TimeDisplay, active: bool, in_delay: bool) {
+    let show_colon = self.show_colon(active, in_delay);
+    let data = self.format_time(time, show_colon);
 
     Peripherals::with(|p| {
       self.send_to_display(&mut p.white_clk, &mut p.white_dio, &data);
@@ -86,20 +87,43 @@ impl ChessClockDisplays {
 
   /// Update Black's clock display
   ///
-  /// - `minutes`: 0-99
-  /// - `seconds`: 0-59
+  /// - `time`: MM:SS (minutes 0-99), or SS.t under ten seconds
   /// - `active`: if true, show blinking colon
-  pub fn update_black(&self, minutes: u8, seconds: u8, active: bool) {
-    let show_colon = if active { self.colon_visible } else { true };
-    let data = self.format_time(minutes, seconds, show_colon);
+  /// - `in_delay`: if true, the per-move delay is running; colon stays off
+  pub fn update_black(&self, time: TimeDisplay, active: bool, in_delay: bool) {
+    let show_colon = self.show_colon(active, in_delay);
+    let data = self.format_time(time, show_colon);
 
     Peripherals::with(|p| {
       self.send_to_display(&mut p.black_clk, &mut p.black_dio, &data);
     });
   }
 
-  /// Format time as 4 segment bytes [M1, M0:, S1, S0]
-  fn format_time(&self, minutes: u8, seconds: u8, show_colon: bool) -> [u8; 4] {
+  /// Colon pattern: steady on when idle, blinking while running, off during delay
+  fn show_colon(&self, active: bool, in_delay: bool) -> bool {
+    match (active, in_delay) {
+      (true, true) => false,
+      (true, false) => self.colon_visible,
+      (false, _) => true,
+    }
+  }
+
+  /// Format time as 4 segment bytes [M1, M0:, S1, S0], or [S1, S0:, t, blank]
+  /// for seconds and tenths. The display has no decimal points, so the colon
+  /// stays on as the separator.
+  fn format_time(&self, time: TimeDisplay, show_colon: bool) -> [u8; 4] {
+    let (minutes, seconds) = match time {
+      TimeDisplay::MinutesSeconds(minutes, seconds) => (minutes, seconds),
+      TimeDisplay::SecondsTenths(seconds, tenths) => {
+        return [
+          DIGITS[(seconds / 10) as usize],
+          DIGITS[(seconds % 10) as usize] | COLON,
+          DIGITS[tenths as usize],
+          0,
+        ];
+      }
+    };
+
     let m1 = (minutes / 10) as usize;
     let m0 = (minutes % 10) as usize;
     let s1 = (seconds / 10) as usize;
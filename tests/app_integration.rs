@@ -1,22 +1,41 @@
 #![cfg(feature = "simulator")]
 
 use certabo::app::{
-    App, AppState, BoardIO, BuzzerSound, DisplayMessage, Hardware, MockBuzzer, MockClockDisplay,
-    MockDelay, MockDisplay,
+    App, AppState, BoardIO, Button, ButtonEvent, BuzzerSound, ComputerOpponent, DisplayMessage,
+    Hardware, MockBuzzer, MockClockDisplay, MockDelay, MockDisplay, MockRandom, MockTimeSource,
+    TakebackPolicy,
 };
 use certabo::certabo::buffer::MAX_LINE_LEN;
 use certabo::certabo::calibration::Piece;
+use certabo::certabo::filter::FilterConfig;
 use certabo::certabo::protocol::RfidReading;
 use certabo::certabo::simulator::SimulatedBoard;
+use certabo::certabo::leds::LedState;
+use certabo::game::chess::{ChessBoard, Move, PieceType};
+use certabo::game::state::GameStatus;
+use certabo::game::timer::{
+    Color, Delay, LowTimeWarnings, MoveTimePenalty, TimeControl, TimeDisplay, TimeStage,
+};
 
-fn create_test_hardware() -> Hardware<SimulatedBoard, MockDisplay, MockBuzzer, MockClockDisplay, MockDelay>
-{
+type TestHardware = Hardware<
+    SimulatedBoard,
+    MockDisplay,
+    MockBuzzer,
+    MockClockDisplay,
+    MockDelay,
+    MockTimeSource,
+    MockRandom,
+>;
+
+fn create_test_hardware() -> TestHardware {
     Hardware {
         board: SimulatedBoard::with_starting_position(),
         display: MockDisplay::new(),
         buzzer: MockBuzzer::new(),
         clock: MockClockDisplay::new(),
         delay: MockDelay::new(),
+        time: MockTimeSource::new(),
+        random: MockRandom::new(),
     }
 }
 
@@ -30,7 +49,7 @@ fn poll_reading(board: &mut SimulatedBoard) -> RfidReading {
 
 struct TestGame {
     app: App,
-    hw: Hardware<SimulatedBoard, MockDisplay, MockBuzzer, MockClockDisplay, MockDelay>,
+    hw: TestHardware,
 }
 
 impl TestGame {
@@ -39,7 +58,7 @@ impl TestGame {
         let mut hw = create_test_hardware();
 
         app.start(&mut hw);
-        app.on_blue_button(&mut hw.display);
+        app.on_button_event(ButtonEvent::Short(Button::Blue), &mut hw.display, &mut hw.random);
 
         let reading = poll_reading(&mut hw.board);
         app.on_board_reading(reading, &mut hw.display, &mut hw.buzzer, &mut hw.delay);
@@ -47,11 +66,53 @@ impl TestGame {
         let reading = poll_reading(&mut hw.board);
         app.on_board_reading(reading, &mut hw.display, &mut hw.buzzer, &mut hw.delay);
 
+        assert_eq!(app.state(), AppState::SelectingTimeControl);
+        app.on_button_event(ButtonEvent::Short(Button::White), &mut hw.display, &mut hw.random);
         assert_eq!(app.state(), AppState::GameInProgress);
 
         Self { app, hw }
     }
 
+    fn new_calibrated() -> Self {
+        let mut app = App::new();
+        let mut hw = create_test_hardware();
+
+        app.start(&mut hw);
+        app.on_button_event(ButtonEvent::Short(Button::Blue), &mut hw.display, &mut hw.random);
+
+        let reading = poll_reading(&mut hw.board);
+        app.on_board_reading(reading, &mut hw.display, &mut hw.buzzer, &mut hw.delay);
+        assert_eq!(app.state(), AppState::WaitingForSetup);
+
+        Self { app, hw }
+    }
+
+    fn new_replay(pgn: &'static [u8]) -> Self {
+        let Self { mut app, mut hw } = Self::new_calibrated();
+
+        assert!(app.load_replay(pgn, &mut hw.display));
+
+        let reading = poll_reading(&mut hw.board);
+        app.on_board_reading(reading, &mut hw.display, &mut hw.buzzer, &mut hw.delay);
+
+        assert_eq!(app.state(), AppState::Replaying);
+
+        Self { app, hw }
+    }
+
+    /// Read the set-up pieces and keep the preselected time control
+    fn start_game(&mut self) {
+        let reading = poll_reading(&mut self.hw.board);
+        self.app
+            .on_board_reading(reading, &mut self.hw.display, &mut self.hw.buzzer, &mut self.hw.delay);
+        assert_eq!(self.app.state(), AppState::SelectingTimeControl);
+        self.press(ButtonEvent::Short(Button::White));
+    }
+
+    fn press(&mut self, event: ButtonEvent) {
+        self.app.on_button_event(event, &mut self.hw.display, &mut self.hw.random);
+    }
+
     fn make_move(&mut self, from: u8, to: u8) {
         let piece = self.hw.board.virtual_board().get(from).cloned().unwrap();
         self.hw.board.virtual_board_mut().remove_piece(from);
@@ -68,6 +129,19 @@ impl TestGame {
             .on_board_reading(reading, &mut self.hw.display, &mut self.hw.buzzer, &mut self.hw.delay);
     }
 
+    /// Move a piece to an empty square within one reading
+    fn slide_piece(&mut self, from: u8, to: u8) {
+        let piece = self.hw.board.virtual_board().get(from).cloned().unwrap();
+        self.hw.board.virtual_board_mut().remove_piece(from);
+        self.hw
+            .board
+            .virtual_board_mut()
+            .place_piece_with_chip_id(to, piece.piece, piece.chip_id);
+        let reading = poll_reading(&mut self.hw.board);
+        self.app
+            .on_board_reading(reading, &mut self.hw.display, &mut self.hw.buzzer, &mut self.hw.delay);
+    }
+
     fn make_capture(&mut self, from: u8, to: u8) {
         let piece = self.hw.board.virtual_board().get(from).cloned().unwrap();
         self.hw.board.virtual_board_mut().remove_piece(from);
@@ -136,6 +210,34 @@ impl TestGame {
     fn tick(&mut self, ms: u32) {
         self.app.tick(ms, &mut self.hw.display, &mut self.hw.buzzer, &mut self.hw.clock);
     }
+
+    fn tick_at(&mut self, now_ms: u32) {
+        self.hw.time.time_ms = now_ms;
+        self.app
+            .tick_at(now_ms, &mut self.hw.display, &mut self.hw.buzzer, &mut self.hw.clock);
+    }
+
+    /// Tick until the computer has chosen its move
+    fn await_computer_move(&mut self) -> Move {
+        for _ in 0..100 {
+            if let Some(mv) = self.app.computer_move() {
+                return mv;
+            }
+            self.tick(100);
+        }
+        panic!("computer should move");
+    }
+
+    fn reading_at(&mut self, received_ms: u32) {
+        let reading = poll_reading(&mut self.hw.board);
+        self.app.on_board_reading_at(
+            reading,
+            received_ms,
+            &mut self.hw.display,
+            &mut self.hw.buzzer,
+            &mut self.hw.delay,
+        );
+    }
 }
 
 #[test]
@@ -164,7 +266,7 @@ fn test_blue_button_starts_calibration() {
     let mut hw = create_test_hardware();
 
     app.start(&mut hw);
-    app.on_blue_button(&mut hw.display);
+    app.on_button_event(ButtonEvent::Short(Button::Blue), &mut hw.display, &mut hw.random);
 
     assert_eq!(app.state(), AppState::Calibrating);
 }
@@ -175,7 +277,7 @@ fn test_calibration_flow() {
     let mut hw = create_test_hardware();
 
     app.start(&mut hw);
-    app.on_blue_button(&mut hw.display);
+    app.on_button_event(ButtonEvent::Short(Button::Blue), &mut hw.display, &mut hw.random);
 
     let reading = poll_reading(&mut hw.board);
     app.on_board_reading(reading, &mut hw.display, &mut hw.buzzer, &mut hw.delay);
@@ -194,17 +296,121 @@ fn test_game_starts_when_pieces_detected() {
     let mut hw = create_test_hardware();
 
     app.start(&mut hw);
-    app.on_blue_button(&mut hw.display);
+    app.on_button_event(ButtonEvent::Short(Button::Blue), &mut hw.display, &mut hw.random);
 
     let reading = poll_reading(&mut hw.board);
     app.on_board_reading(reading, &mut hw.display, &mut hw.buzzer, &mut hw.delay);
 
     let reading = poll_reading(&mut hw.board);
     app.on_board_reading(reading, &mut hw.display, &mut hw.buzzer, &mut hw.delay);
+    assert_eq!(app.state(), AppState::SelectingTimeControl);
+    assert!(hw.buzzer.sounds.contains(&BuzzerSound::Move));
 
+    // White keeps the preselected time control
+    app.on_button_event(ButtonEvent::Short(Button::White), &mut hw.display, &mut hw.random);
     assert_eq!(app.state(), AppState::GameInProgress);
 }
 
+/// Read the board as it is while waiting for the pieces to be set up
+fn read_setup(game: &mut TestGame) {
+    let reading = poll_reading(&mut game.hw.board);
+    game.app
+        .on_board_reading(reading, &mut game.hw.display, &mut game.hw.buzzer, &mut game.hw.delay);
+}
+
+fn swap_pieces(game: &mut TestGame, a: u8, b: u8) {
+    let board = game.hw.board.virtual_board_mut();
+    let first = board.get(a).cloned();
+    let second = board.get(b).cloned();
+    board.set(a, second);
+    board.set(b, first);
+}
+
+#[test]
+fn test_setup_swapped_king_and_queen_rejected() {
+    let mut game = TestGame::new_calibrated();
+
+    swap_pieces(&mut game, 3, 4);
+    read_setup(&mut game);
+
+    assert_eq!(game.app.state(), AppState::WaitingForSetup);
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::SetupProblem {
+            problem: "Swap d1/e1".into()
+        })
+    );
+    let lit: Vec<u8> = (0..64).filter(|&sq| game.app.led_state().is_on(sq)).collect();
+    assert_eq!(lit, vec![3, 4]);
+
+    swap_pieces(&mut game, 3, 4);
+    read_setup(&mut game);
+
+    assert_eq!(game.app.state(), AppState::SelectingTimeControl);
+    assert!(game.app.led_state().as_bytes().iter().all(|&b| b == 0));
+}
+
+#[test]
+fn test_setup_missing_and_misplaced_pieces_shown() {
+    let mut game = TestGame::new_calibrated();
+    let pawn = game.hw.board.virtual_board().get(11).cloned().unwrap();
+
+    game.hw.board.virtual_board_mut().remove_piece(11);
+    read_setup(&mut game);
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::SetupProblem {
+            problem: "Missing piece d2".into()
+        })
+    );
+    assert!(game.app.led_state().is_on(11));
+
+    // 32 pieces on the board are not enough if one is on the wrong square
+    game.hw
+        .board
+        .virtual_board_mut()
+        .place_piece_with_chip_id(27, pawn.piece, pawn.chip_id);
+    read_setup(&mut game);
+    assert_eq!(game.app.state(), AppState::WaitingForSetup);
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::SetupProblem {
+            problem: "2 squares wrong".into()
+        })
+    );
+    assert!(game.app.led_state().is_on(11));
+    assert!(game.app.led_state().is_on(27));
+
+    // Nothing new to say while the board stays the same
+    let shown = game.hw.display.messages.len();
+    read_setup(&mut game);
+    assert_eq!(game.hw.display.messages.len(), shown);
+
+    game.hw.board.virtual_board_mut().remove_piece(27);
+    game.hw
+        .board
+        .virtual_board_mut()
+        .place_piece_with_chip_id(11, pawn.piece, pawn.chip_id);
+    read_setup(&mut game);
+    assert_eq!(game.app.state(), AppState::SelectingTimeControl);
+}
+
+#[test]
+fn test_chess960_setup_rejects_standard_layout() {
+    let mut game = TestGame::new_calibrated();
+    assert!(game.app.setup_chess960(454, &mut game.hw.display));
+
+    read_setup(&mut game);
+
+    assert_eq!(game.app.state(), AppState::WaitingForSetup);
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::SetupProblem {
+            problem: "4 squares wrong".into()
+        })
+    );
+}
+
 #[test]
 fn test_move_detection_e2_e4() {
     let mut game = TestGame::new();
@@ -219,14 +425,147 @@ fn test_move_detection_e2_e4() {
 fn test_pause_resume() {
     let mut game = TestGame::new();
 
-    game.app.on_white_button(&mut game.hw.display);
+    game.press(ButtonEvent::Short(Button::White));
     assert_eq!(game.app.state(), AppState::GamePaused);
     assert!(game.hw.display.messages.contains(&DisplayMessage::Paused));
 
-    game.app.on_white_button(&mut game.hw.display);
+    game.press(ButtonEvent::Short(Button::White));
+    assert_eq!(game.app.state(), AppState::GameInProgress);
+}
+
+#[test]
+fn test_long_press_resigns_for_button_side() {
+    let mut game = TestGame::new();
+    game.make_move(12, 28);
+
+    // Blue is black's button, pressed again to confirm
+    game.press(ButtonEvent::Long(Button::Blue));
+    assert_eq!(game.app.state(), AppState::GameInProgress);
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::ResignPrompt { is_white: false })
+    );
+    game.press(ButtonEvent::Short(Button::Blue));
+
+    assert_eq!(game.app.state(), AppState::GameEnded);
+    assert_eq!(game.app.game().status(), GameStatus::WhiteWins);
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::GameOver {
+            winner: "White".into(),
+            reason: "Resignation".into()
+        })
+    );
+    assert!(game.app.led_state().as_bytes().iter().all(|&b| b == 0));
+}
+
+#[test]
+fn test_long_press_resigns_while_paused() {
+    let mut game = TestGame::new();
+
+    game.press(ButtonEvent::Short(Button::White));
+    game.press(ButtonEvent::Long(Button::White));
+    game.press(ButtonEvent::Short(Button::White));
+
+    assert_eq!(game.app.state(), AppState::GameEnded);
+    assert_eq!(game.app.game().status(), GameStatus::BlackWins);
+}
+
+#[test]
+fn test_resign_prompt_cancelled_by_other_button() {
+    let mut game = TestGame::new();
+
+    game.press(ButtonEvent::Long(Button::White));
+    game.press(ButtonEvent::Short(Button::Blue));
+    assert_eq!(game.app.state(), AppState::GameInProgress);
+    assert_ne!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::ResignPrompt { is_white: true })
+    );
+
+    // The next press is an ordinary one again
+    game.press(ButtonEvent::Short(Button::White));
+    assert_eq!(game.app.state(), AppState::GamePaused);
+}
+
+#[test]
+fn test_resign_prompt_cancelled_while_paused() {
+    let mut game = TestGame::new();
+
+    game.press(ButtonEvent::Short(Button::White));
+    game.press(ButtonEvent::Long(Button::Blue));
+    game.press(ButtonEvent::Long(Button::Blue));
+
+    assert_eq!(game.app.state(), AppState::GamePaused);
+    assert_eq!(game.hw.display.last_message, Some(DisplayMessage::Paused));
+}
+
+#[test]
+fn test_both_buttons_agree_draw() {
+    let mut game = TestGame::new();
+    game.make_move(12, 28);
+
+    // Black offers, White accepts
+    game.press(ButtonEvent::Both);
+    assert_eq!(game.app.state(), AppState::GameInProgress);
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::DrawOffer { opponent_is_white: true })
+    );
+    game.press(ButtonEvent::Short(Button::White));
+
+    assert_eq!(game.app.state(), AppState::GameEnded);
+    assert_eq!(game.app.game().status(), GameStatus::Draw);
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::Draw {
+            reason: "Agreed".into()
+        })
+    );
+}
+
+#[test]
+fn test_draw_offer_declined() {
+    let mut game = TestGame::new();
+
+    // The offering side's own button does not accept
+    game.press(ButtonEvent::Both);
+    game.press(ButtonEvent::Short(Button::White));
+    assert_eq!(game.app.state(), AppState::GameInProgress);
+    assert_eq!(game.app.game().status(), GameStatus::InProgress);
+
+    // A move withdraws the offer
+    game.press(ButtonEvent::Both);
+    game.make_move(12, 28);
+    game.press(ButtonEvent::Short(Button::Blue));
+    assert_eq!(game.app.state(), AppState::GameInProgress);
+    assert_eq!(game.app.game().status(), GameStatus::InProgress);
+}
+
+#[test]
+fn test_double_press_in_game_is_one_press() {
+    let mut game = TestGame::new();
+
+    game.press(ButtonEvent::Double(Button::White));
+    assert_eq!(game.app.state(), AppState::GamePaused);
+
+    game.press(ButtonEvent::Double(Button::White));
     assert_eq!(game.app.state(), AppState::GameInProgress);
 }
 
+#[test]
+fn test_game_gestures_outside_game() {
+    let mut game = TestGame::new_calibrated();
+
+    // Nothing to agree on before the game
+    game.press(ButtonEvent::Both);
+    assert_eq!(game.app.state(), AppState::WaitingForSetup);
+
+    // A long press is taken as a short one
+    game.press(ButtonEvent::Long(Button::Blue));
+    assert_eq!(game.app.state(), AppState::Calibrating);
+}
+
 #[test]
 fn test_led_animation_during_calibration() {
     let mut app = App::new();
@@ -247,7 +586,7 @@ fn test_clock_updates_during_game() {
 
     game.tick(100);
 
-    assert_eq!(game.hw.clock.white_time, (10, 0));
+    assert_eq!(game.hw.clock.white_time, TimeDisplay::MinutesSeconds(10, 0));
     assert!(game.hw.clock.white_active);
     assert!(!game.hw.clock.black_active);
 }
@@ -265,7 +604,7 @@ fn test_full_game_scenario() {
         .iter()
         .any(|m| matches!(m, DisplayMessage::Turn { is_white: false })));
 
-    game.app.on_white_button(&mut game.hw.display);
+    game.press(ButtonEvent::Short(Button::White));
     assert_eq!(game.app.state(), AppState::GamePaused);
 }
 
@@ -426,58 +765,173 @@ fn test_en_passant_capture() {
     );
 }
 
+#[test]
+fn test_en_passant_captured_pawn_removed_last() {
+    let mut game = TestGame::new();
+
+    game.make_move(12, 28);
+    game.make_move(48, 40);
+    game.make_move(28, 36);
+    game.make_move(51, 35);
+
+    game.hw.buzzer.sounds.clear();
+    game.make_move(36, 43);
+    assert_eq!(game.app.game().move_count(), 4, "Captured pawn still on the board");
+
+    game.lift_piece(35);
+
+    assert_eq!(game.app.game().move_count(), 5);
+    assert_eq!(game.app.game().board().get(35), None);
+    assert!(!game.hw.buzzer.sounds.contains(&BuzzerSound::Error));
+}
+
+#[test]
+fn test_castling_rook_first() {
+    let mut game = TestGame::new();
+
+    game.make_move(12, 28);
+    game.make_move(52, 36);
+    game.make_move(6, 21);
+    game.make_move(57, 42);
+    game.make_move(5, 12);
+    game.make_move(62, 45);
+
+    game.hw.buzzer.sounds.clear();
+    game.tick(1000);
+    // On its own the rook move is Rf1...
+    game.make_move(7, 5);
+    assert_eq!(game.app.game().move_count(), 7);
+    game.tick(500);
+
+    // ...until the king crosses over it
+    game.make_move(4, 6);
+
+    assert_eq!(game.app.game().move_count(), 7);
+    assert_eq!(game.app.game().current_turn(), Color::Black);
+    let timer = game.app.game().timer();
+    assert_eq!(timer.time_remaining(Color::White), 600_000 - 1500);
+    assert_eq!(timer.time_remaining(Color::Black), 600_000);
+    let board = game.app.game().board();
+    assert_eq!(board.get(6).map(|p| p.piece_type), Some(PieceType::King));
+    assert_eq!(board.get(5).map(|p| p.piece_type), Some(PieceType::Rook));
+    assert!(!game.hw.buzzer.sounds.contains(&BuzzerSound::Error));
+}
+
+#[test]
+fn test_capture_with_captured_piece_removed_first() {
+    let mut game = TestGame::new();
+
+    game.make_move(12, 28);
+    game.make_move(51, 35);
+
+    game.hw.buzzer.sounds.clear();
+    game.lift_piece(35);
+    game.make_move(28, 35);
+
+    assert_eq!(game.app.game().move_count(), 3);
+    assert!(!game.hw.buzzer.sounds.contains(&BuzzerSound::Error));
+}
+
+#[test]
+fn test_move_recognized_without_intermediate_reading() {
+    let mut game = TestGame::new();
+
+    game.slide_piece(12, 28);
+
+    assert_eq!(game.app.game().move_count(), 1);
+    assert!(game.hw.buzzer.sounds.contains(&BuzzerSound::Move));
+}
+
+#[test]
+fn test_lifted_piece_put_back() {
+    let mut game = TestGame::new();
+
+    let pawn = game.hw.board.virtual_board().get(12).cloned().unwrap();
+    game.lift_piece(12);
+    assert!(game.app.led_state().is_on(28));
+    game.place_piece(12, pawn.piece, pawn.chip_id);
+
+    assert_eq!(game.app.game().lifted_piece(), None);
+    assert!(!game.app.led_state().is_on(28));
+    assert!(!game.hw.buzzer.sounds.contains(&BuzzerSound::Error));
+}
+
 #[test]
 fn test_pawn_promotion_to_queen() {
     let mut game = TestGame::new();
 
     let white_queen_chip = game.hw.board.virtual_board().get(3).cloned().unwrap().chip_id;
 
-    game.make_move(15, 31);
-    game.make_move(54, 38);
-    game.make_capture(31, 38);
+    // 1. e4 d5 2. exd5 Qxd5 3. Qg4 Bxg4 takes off the white queen
+    game.make_move(12, 28);
+    game.make_move(51, 35);
+    game.make_capture(28, 35);
+    game.make_capture(59, 35);
+    game.make_move(3, 30);
+    game.make_capture(58, 30);
+    // 4. a4 b5 5. axb5 Nf6 6. b6 a6 7. bxc7 Ne4
+    game.make_move(8, 24);
+    game.make_move(49, 33);
+    game.make_capture(24, 33);
     game.make_move(62, 45);
-    game.make_move(38, 46);
+    game.make_move(33, 41);
+    game.make_move(48, 40);
+    game.make_capture(41, 50);
     game.make_move(45, 28);
-    game.make_move(46, 54);
-    game.make_move(28, 43);
 
     game.hw.buzzer.sounds.clear();
 
-    game.lift_piece(54);
-    game.hw.board.virtual_board_mut().remove_piece(3);
-    game.place_piece(62, Piece::WhiteQueen, white_queen_chip);
+    game.lift_piece(50);
+    game.place_piece(58, Piece::WhiteQueen, white_queen_chip);
 
     assert!(
         game.hw.buzzer.sounds.contains(&BuzzerSound::Move),
         "Pawn promotion to queen should be valid"
     );
+    assert_eq!(
+        game.app.game().board().get(58).map(|p| p.piece_type),
+        Some(PieceType::Queen)
+    );
 }
 
 #[test]
 fn test_pawn_promotion_to_knight() {
     let mut game = TestGame::new();
 
-    let white_knight_chip = game.hw.board.virtual_board().get(1).cloned().unwrap().chip_id;
+    let white_knight_chip = game.hw.board.virtual_board().get(6).cloned().unwrap().chip_id;
 
-    game.make_move(15, 31);
-    game.make_move(54, 38);
-    game.make_capture(31, 38);
+    // 1. e4 d5 2. exd5 Qxd5 3. Nf3 Bg4 4. Nd4 Qxd4 takes off a white knight
+    game.make_move(12, 28);
+    game.make_move(51, 35);
+    game.make_capture(28, 35);
+    game.make_capture(59, 35);
+    game.make_move(6, 21);
+    game.make_move(58, 30);
+    game.make_move(21, 27);
+    game.make_capture(35, 27);
+    // 5. a4 b5 6. axb5 Nf6 7. b6 a6 8. bxc7 Ne4
+    game.make_move(8, 24);
+    game.make_move(49, 33);
+    game.make_capture(24, 33);
     game.make_move(62, 45);
-    game.make_move(38, 46);
+    game.make_move(33, 41);
+    game.make_move(48, 40);
+    game.make_capture(41, 50);
     game.make_move(45, 28);
-    game.make_move(46, 54);
-    game.make_move(28, 43);
 
     game.hw.buzzer.sounds.clear();
 
-    game.lift_piece(54);
-    game.hw.board.virtual_board_mut().remove_piece(1);
-    game.place_piece(62, Piece::WhiteKnight, white_knight_chip);
+    game.lift_piece(50);
+    game.place_piece(58, Piece::WhiteKnight, white_knight_chip);
 
     assert!(
         game.hw.buzzer.sounds.contains(&BuzzerSound::Move),
         "Pawn promotion to knight should be valid"
     );
+    assert_eq!(
+        game.app.game().board().get(58).map(|p| p.piece_type),
+        Some(PieceType::Knight)
+    );
 }
 
 #[test]
@@ -551,7 +1005,9 @@ fn test_timer_countdown() {
         game.tick(1000);
     }
 
-    let (min, sec) = game.hw.clock.white_time;
+    let TimeDisplay::MinutesSeconds(min, sec) = game.hw.clock.white_time else {
+        panic!("expected minutes and seconds");
+    };
     assert!(
         min == 8 && (sec == 20 || sec == 21),
         "Timer should be around 8:20 after 100 seconds, got {}:{:02}", min, sec
@@ -696,7 +1152,7 @@ fn test_game_reset_after_end() {
 
     assert_eq!(game.app.state(), AppState::GameEnded);
 
-    game.app.on_white_button(&mut game.hw.display);
+    game.press(ButtonEvent::Short(Button::White));
 
     assert_eq!(game.app.state(), AppState::WaitingForSetup);
     assert!(
@@ -799,9 +1255,7 @@ fn test_clock_display_format() {
 
     game.tick(100);
 
-    let (min, sec) = game.hw.clock.white_time;
-    assert_eq!(min, 10);
-    assert_eq!(sec, 0);
+    assert_eq!(game.hw.clock.white_time, TimeDisplay::MinutesSeconds(10, 0));
 }
 
 #[test]
@@ -856,8 +1310,33 @@ fn test_display_shows_last_move() {
             .display
             .messages
             .iter()
-            .any(|m| matches!(m, DisplayMessage::LastMove { from: 12, to: 28 })),
-        "Display should show last move e2-e4"
+            .any(|m| matches!(m, DisplayMessage::LastMove { san } if san == "e4")),
+        "Display should show last move e4"
+    );
+}
+
+#[test]
+fn test_display_shows_capture_and_check_in_san() {
+    let mut game = TestGame::new();
+
+    game.make_move(12, 28);
+    game.make_move(51, 35);
+    game.make_capture(28, 35);
+    game.make_move(52, 44);
+    game.make_move(5, 33);
+
+    assert_eq!(
+        game.hw.display.messages.iter().rev().find(|m| matches!(m, DisplayMessage::LastMove { .. })),
+        Some(&DisplayMessage::LastMove { san: "Bb5+".into() }),
+        "Display should show the bishop check in SAN"
+    );
+    assert!(
+        game.hw
+            .display
+            .messages
+            .iter()
+            .any(|m| matches!(m, DisplayMessage::LastMove { san } if san == "exd5")),
+        "Display should show the pawn capture in SAN"
     );
 }
 
@@ -925,3 +1404,945 @@ fn test_leds_clear_after_checkmate() {
         "LEDs should be clear after checkmate"
     );
 }
+
+#[test]
+fn test_replay_lights_next_move() {
+    let mut game = TestGame::new_replay(b"1. e4 e5 2. Nf3 *");
+
+    assert_eq!(*game.app.led_state(), LedState::from_move(12, 28));
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::ReplayMove {
+            move_number: 1,
+            is_white: true,
+            san: "e4".into()
+        })
+    );
+
+    game.make_move(12, 28);
+    assert_eq!(game.app.state(), AppState::Replaying);
+    assert_eq!(*game.app.led_state(), LedState::from_move(52, 36));
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::ReplayMove {
+            move_number: 1,
+            is_white: false,
+            san: "e5".into()
+        })
+    );
+
+    game.make_move(52, 36);
+    game.make_move(6, 21);
+
+    assert_eq!(game.app.game().move_count(), 3);
+    assert_eq!(game.app.state(), AppState::GameEnded);
+    assert_eq!(game.hw.display.last_message, Some(DisplayMessage::ReplayComplete));
+}
+
+#[test]
+fn test_replay_rejects_different_move() {
+    let mut game = TestGame::new_replay(b"1. e4 e5 *");
+
+    game.make_move(11, 27);
+
+    assert_eq!(game.app.game().move_count(), 0);
+    assert!(game.hw.buzzer.sounds.contains(&BuzzerSound::Error));
+    assert!(game.hw.display.messages.contains(&DisplayMessage::InvalidMove));
+    assert_eq!(*game.app.led_state(), LedState::from_move(12, 28));
+}
+
+#[test]
+fn test_replay_follows_takeback() {
+    let mut game = TestGame::new_replay(b"1. e4 e5 2. Nf3 *");
+
+    game.make_move(12, 28);
+    game.make_move(52, 36);
+    game.slide_piece(36, 52);
+
+    assert_eq!(game.app.game().move_count(), 1);
+    assert_eq!(game.app.state(), AppState::Replaying);
+    assert_eq!(*game.app.led_state(), LedState::from_move(52, 36));
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::ReplayMove {
+            move_number: 1,
+            is_white: false,
+            san: "e5".into()
+        })
+    );
+}
+
+#[test]
+fn test_replay_rejects_invalid_game() {
+    let mut game = TestGame::new_calibrated();
+
+    assert!(!game.app.load_replay(b"1. e4 e5 2. Ke3 *", &mut game.hw.display));
+    assert!(!game.app.load_replay(b"1. e4 {open comment", &mut game.hw.display));
+    assert_eq!(game.app.state(), AppState::WaitingForSetup);
+}
+
+#[test]
+fn test_replay_keeps_time_control() {
+    let mut game = TestGame::new_calibrated();
+    assert!(game.app.set_time_control(TimeControl::BLITZ_3_2));
+
+    assert!(game.app.load_replay(b"1. e4 e5 *", &mut game.hw.display));
+    assert_eq!(game.app.game().timer().time_remaining(Color::White), 180_000);
+}
+
+#[test]
+fn test_white_button_cycles_to_study_game() {
+    let mut game = TestGame::new_calibrated();
+    game.app.set_study_game(include_bytes!("../games/opera.pgn"));
+    assert!(game.app.setup_chess960(454, &mut game.hw.display));
+
+    game.press(ButtonEvent::Short(Button::White));
+    assert_eq!(game.app.chess960(), None);
+    assert!(game.app.replay_loaded());
+    assert_eq!(game.hw.display.last_message, Some(DisplayMessage::WaitingForSetup));
+    let reading = poll_reading(&mut game.hw.board);
+    game.app
+        .on_board_reading(reading, &mut game.hw.display, &mut game.hw.buzzer, &mut game.hw.delay);
+    assert_eq!(game.app.state(), AppState::Replaying);
+    assert_eq!(*game.app.led_state(), LedState::from_move(12, 28));
+}
+
+#[test]
+fn test_white_button_leaves_study_game() {
+    let mut game = TestGame::new_calibrated();
+    assert!(game.app.load_replay(include_bytes!("../games/opera.pgn"), &mut game.hw.display));
+
+    game.press(ButtonEvent::Short(Button::White));
+    assert!(!game.app.replay_loaded());
+    game.start_game();
+    assert_eq!(game.app.state(), AppState::GameInProgress);
+}
+
+#[test]
+fn test_replay_exit_with_white_button() {
+    let mut game = TestGame::new_replay(b"1. e4 e5 *");
+
+    game.press(ButtonEvent::Short(Button::White));
+
+    assert_eq!(game.app.state(), AppState::WaitingForSetup);
+    assert!(game.app.led_state().as_bytes().iter().all(|&b| b == 0));
+}
+
+/// Rearrange the standard setup into Chess960 position 454 (RNBNKBQR)
+fn setup_chess960_454(game: &mut TestGame) {
+    assert!(game.app.setup_chess960(454, &mut game.hw.display));
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::Chess960Setup {
+            index: 454,
+            back_rank: "RNBNKBQR".into()
+        })
+    );
+
+    // Swap queens and g-file knights
+    for (a, b) in [(3, 6), (59, 62)] {
+        swap_pieces(game, a, b);
+    }
+}
+
+fn start_chess960_454() -> TestGame {
+    let mut game = TestGame::new_calibrated();
+    setup_chess960_454(&mut game);
+    game
+}
+
+#[test]
+fn test_chess960_setup_and_castling() {
+    let mut game = start_chess960_454();
+
+    game.start_game();
+    assert_eq!(game.app.state(), AppState::GameInProgress);
+    assert_eq!(game.app.chess960(), Some(454));
+
+    // Clear b1, c1 and d1 while black shuffles a knight
+    for (from, to) in [(1, 16), (57, 40), (3, 20), (40, 57), (11, 19), (57, 40), (2, 11), (40, 57)] {
+        game.make_move(from, to);
+    }
+
+    // The king placed on c1 and the rook next to it castle queenside
+    game.hw.buzzer.sounds.clear();
+    game.make_move(4, 2);
+    game.make_move(0, 3);
+
+    assert!(game.hw.buzzer.sounds.contains(&BuzzerSound::Move));
+    let board = game.app.game().board();
+    assert_eq!(board.get(2).map(|p| p.piece_type), Some(PieceType::King));
+    assert_eq!(board.get(3).map(|p| p.piece_type), Some(PieceType::Rook));
+    assert_eq!(board.get(0), None);
+}
+
+#[test]
+fn test_chess960_recalibration_uses_shuffled_layout() {
+    let mut game = start_chess960_454();
+
+    game.press(ButtonEvent::Short(Button::Blue));
+    assert_eq!(game.app.state(), AppState::Calibrating);
+
+    let reading = poll_reading(&mut game.hw.board);
+    game.app
+        .on_board_reading(reading, &mut game.hw.display, &mut game.hw.buzzer, &mut game.hw.delay);
+    assert_eq!(game.app.state(), AppState::WaitingForSetup);
+    assert_eq!(game.app.chess960(), Some(454));
+
+    game.start_game();
+    assert_eq!(game.app.state(), AppState::GameInProgress);
+}
+
+#[test]
+fn test_chess960_white_button_returns_to_standard() {
+    let mut game = start_chess960_454();
+
+    game.press(ButtonEvent::Short(Button::White));
+
+    assert_eq!(game.app.chess960(), None);
+    assert_eq!(game.hw.display.last_message, Some(DisplayMessage::WaitingForSetup));
+    assert_eq!(
+        game.app.game().board().hash(),
+        ChessBoard::starting_position().hash()
+    );
+}
+
+fn shuffle_knights(game: &mut TestGame) {
+    game.make_move(6, 21);
+    game.make_move(62, 45);
+    game.make_move(21, 6);
+    game.make_move(45, 62);
+}
+
+#[test]
+fn test_threefold_repetition_claimed_with_blue_button() {
+    let mut game = TestGame::new();
+
+    shuffle_knights(&mut game);
+    game.press(ButtonEvent::Short(Button::Blue));
+    assert_eq!(game.app.state(), AppState::GameInProgress, "Cannot claim after two occurrences");
+
+    shuffle_knights(&mut game);
+    game.app.update_display(&mut game.hw.display);
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::DrawClaimable {
+            reason: "3-fold".into()
+        })
+    );
+
+    game.press(ButtonEvent::Short(Button::Blue));
+    assert_eq!(game.app.state(), AppState::GameEnded);
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::Draw {
+            reason: "3-fold".into()
+        })
+    );
+}
+
+#[test]
+fn test_fivefold_repetition_ends_game() {
+    let mut game = TestGame::new();
+
+    for _ in 0..4 {
+        shuffle_knights(&mut game);
+    }
+
+    assert_eq!(game.app.state(), AppState::GameEnded);
+    assert!(game
+        .hw
+        .display
+        .messages
+        .contains(&DisplayMessage::Draw {
+            reason: "5-fold".into()
+        }));
+}
+
+fn start_vs_computer(opponent: ComputerOpponent) -> TestGame {
+    let mut game = TestGame::new_calibrated();
+    assert!(game.app.setup_vs_computer(Some(opponent), &mut game.hw.display));
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::VsComputerSetup { level: opponent.level })
+    );
+
+    game.start_game();
+    assert_eq!(game.app.state(), AppState::GameInProgress);
+    game
+}
+
+#[test]
+fn test_white_button_selects_vs_computer() {
+    let mut game = TestGame::new_calibrated();
+
+    game.press(ButtonEvent::Short(Button::White));
+
+    assert_eq!(game.app.computer_opponent(), Some(ComputerOpponent::default()));
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::VsComputerSetup { level: 3 })
+    );
+
+    // Then a random Chess960 position, which replaces the computer opponent
+    game.hw.random.value = 1414;
+    game.press(ButtonEvent::Short(Button::White));
+    assert_eq!(game.app.chess960(), Some(454));
+    assert_eq!(game.app.computer_opponent(), None);
+}
+
+#[test]
+fn test_computer_move_is_lit_and_made_by_player() {
+    let mut game = start_vs_computer(ComputerOpponent::default());
+    assert_eq!(game.app.computer_move(), None);
+
+    game.make_move(12, 28);
+
+    // The engine thinks over the following ticks
+    assert_eq!(game.app.computer_move(), None);
+    let reply = game.await_computer_move();
+    assert_eq!(*game.app.led_state(), LedState::from_move(reply.from, reply.to));
+    assert!(game
+        .hw
+        .display
+        .messages
+        .iter()
+        .any(|m| matches!(m, DisplayMessage::ComputerMove { .. })));
+
+    // No clock runs until the reply is on the board
+    game.tick(5000);
+    let timer = game.app.game().timer();
+    assert_eq!(timer.time_remaining(Color::White), timer.time_remaining(Color::Black));
+    assert!(!game.hw.clock.white_active && !game.hw.clock.black_active);
+
+    game.make_move(reply.from, reply.to);
+
+    assert_eq!(game.app.computer_move(), None);
+    assert_eq!(game.app.game().move_count(), 2);
+    assert_eq!(game.app.game().current_turn(), Color::White);
+
+    game.tick(1000);
+    let timer = game.app.game().timer();
+    assert!(timer.time_remaining(Color::White) < timer.time_remaining(Color::Black));
+}
+
+#[test]
+fn test_other_move_rejected_on_computer_turn() {
+    let mut game = start_vs_computer(ComputerOpponent::default());
+    game.make_move(12, 28);
+    let reply = game.await_computer_move();
+
+    // Some other black knight move
+    let (from, to) = if (reply.from, reply.to) == (62, 45) { (57, 42) } else { (62, 45) };
+    game.hw.buzzer.sounds.clear();
+    game.make_move(from, to);
+
+    assert!(game.hw.buzzer.sounds.contains(&BuzzerSound::Error));
+    assert_eq!(game.app.game().move_count(), 1);
+    assert_eq!(game.app.computer_move(), Some(reply));
+    assert_eq!(*game.app.led_state(), LedState::from_move(reply.from, reply.to));
+}
+
+#[test]
+fn test_no_move_or_clock_while_computer_thinks() {
+    let mut game = start_vs_computer(ComputerOpponent {
+        color: Color::Black,
+        level: 8,
+    });
+    game.make_move(12, 28);
+    game.tick(100);
+    assert_eq!(game.app.computer_move(), None);
+
+    // Moving for the computer before it has chosen is refused
+    game.hw.buzzer.sounds.clear();
+    game.make_move(52, 36);
+    assert!(game.hw.buzzer.sounds.contains(&BuzzerSound::Error));
+    assert_eq!(game.app.game().move_count(), 1);
+    let timer = game.app.game().timer();
+    assert_eq!(timer.time_remaining(Color::White), timer.time_remaining(Color::Black));
+}
+
+#[test]
+fn test_computer_playing_white_moves_first() {
+    let mut game = start_vs_computer(ComputerOpponent {
+        color: Color::White,
+        level: 1,
+    });
+
+    let first = game.await_computer_move();
+    assert_eq!(*game.app.led_state(), LedState::from_move(first.from, first.to));
+}
+
+#[test]
+fn test_takeback_on_computer_turn_cancels_reply() {
+    let mut game = start_vs_computer(ComputerOpponent::default());
+    game.make_move(12, 28);
+    game.await_computer_move();
+
+    // Slide the pawn back within one reading
+    let pawn = game.hw.board.virtual_board().get(28).cloned().unwrap();
+    game.hw.board.virtual_board_mut().remove_piece(28);
+    game.hw
+        .board
+        .virtual_board_mut()
+        .place_piece_with_chip_id(12, pawn.piece, pawn.chip_id);
+    let reading = poll_reading(&mut game.hw.board);
+    game.app
+        .on_board_reading(reading, &mut game.hw.display, &mut game.hw.buzzer, &mut game.hw.delay);
+
+    assert_eq!(game.app.game().move_count(), 0);
+    assert_eq!(game.app.computer_move(), None);
+}
+
+#[test]
+fn test_time_control_with_increment() {
+    let mut game = TestGame::new_calibrated();
+    assert!(game.app.set_time_control(TimeControl::BLITZ_3_2));
+
+    game.start_game();
+    assert_eq!(game.app.state(), AppState::GameInProgress);
+    assert!(!game.app.set_time_control(TimeControl::RAPID_15_10));
+
+    game.tick(4000);
+    game.make_move(12, 28);
+    game.tick(100);
+    game.tick(100);
+
+    assert_eq!(game.app.game().timer().time_remaining(Color::White), 178_000);
+    assert_eq!(game.hw.clock.white_time, TimeDisplay::MinutesSeconds(2, 58));
+    assert_eq!(game.hw.clock.black_time, TimeDisplay::MinutesSeconds(2, 59));
+}
+
+#[test]
+fn test_simple_delay_shown_on_clock() {
+    let mut game = TestGame::new_calibrated();
+    let delay = TimeControl::minutes(5, 0).with_delay(Delay::Simple(5000));
+    assert!(game.app.set_time_control(delay));
+
+    game.start_game();
+
+    for _ in 0..30 {
+        game.tick(100);
+    }
+    assert_eq!(game.app.game().timer().time_remaining(Color::White), 300_000);
+    assert!(game.hw.clock.white_in_delay);
+    assert!(!game.hw.clock.black_in_delay);
+
+    for _ in 0..30 {
+        game.tick(100);
+    }
+    assert_eq!(game.app.game().timer().time_remaining(Color::White), 299_000);
+    assert!(!game.hw.clock.white_in_delay);
+
+    // Black's delay starts with black's move
+    game.make_move(12, 28);
+    game.tick(100);
+    game.tick(100);
+    assert!(game.hw.clock.black_in_delay);
+    assert!(!game.hw.clock.white_in_delay);
+}
+
+#[test]
+fn test_new_time_stage_shown() {
+    let mut game = TestGame::new_calibrated();
+    let stages = TimeControl::minutes(5, 0).with_stage(TimeStage {
+        after_moves: 1,
+        added_ms: 60_000,
+        increment_ms: 0,
+    });
+    assert!(game.app.set_time_control(stages));
+
+    game.start_game();
+
+    game.make_move(12, 28);
+
+    assert_eq!(game.app.game().timer().time_remaining(Color::White), 360_000);
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::TimeStage {
+            is_white: true,
+            stage: 2
+        })
+    );
+
+    // The notice gives way to the last move after a few seconds
+    for _ in 0..30 {
+        game.tick(100);
+    }
+    game.app.update_display(&mut game.hw.display);
+    assert_eq!(game.hw.display.last_message, Some(DisplayMessage::LastMove { san: "e4".into() }));
+}
+
+fn start_with_time_control(time_control: TimeControl) -> TestGame {
+    let mut game = TestGame::new_calibrated();
+    assert!(game.app.set_time_control(time_control));
+
+    game.start_game();
+    assert_eq!(game.app.state(), AppState::GameInProgress);
+    game
+}
+
+#[test]
+fn test_fixed_time_per_move_loss() {
+    let mut game = start_with_time_control(TimeControl::per_move(30_000, MoveTimePenalty::Loss));
+
+    for _ in 0..200 {
+        game.tick(100);
+    }
+    game.make_move(12, 28);
+    assert_eq!(game.app.game().timer().time_remaining(Color::White), 30_000);
+
+    for _ in 0..300 {
+        game.tick(100);
+    }
+    assert_eq!(game.app.state(), AppState::GameEnded);
+    assert!(game.hw.buzzer.sounds.contains(&BuzzerSound::TimeExpired));
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::GameOver {
+            winner: "White".into(),
+            reason: "Time".into()
+        })
+    );
+}
+
+#[test]
+fn test_fixed_time_per_move_overrun_beeps() {
+    let mut game = start_with_time_control(TimeControl::per_move(10_000, MoveTimePenalty::Overruns(2)));
+
+    game.hw.buzzer.sounds.clear();
+    for _ in 0..100 {
+        game.tick(100);
+    }
+    assert_eq!(game.app.state(), AppState::GameInProgress);
+    assert_eq!(game.app.game().timer().overruns(Color::White), 1);
+    assert!(game.hw.buzzer.sounds.contains(&BuzzerSound::Error));
+
+    for _ in 0..100 {
+        game.tick(100);
+    }
+    assert_eq!(game.app.state(), AppState::GameEnded);
+}
+
+#[test]
+fn test_hourglass_clock() {
+    let mut game = start_with_time_control(TimeControl::hourglass(60_000));
+
+    for _ in 0..50 {
+        game.tick(100);
+    }
+    let timer = game.app.game().timer();
+    assert_eq!(timer.time_remaining(Color::White), 55_000);
+    assert_eq!(timer.time_remaining(Color::Black), 65_000);
+}
+
+#[test]
+fn test_delayed_tick_loses_no_time() {
+    let mut game = TestGame::new();
+
+    // The main loop was blocked for 2.5s, so only one tick got through
+    game.tick_at(2500);
+    assert_eq!(game.app.game().timer().time_remaining(Color::White), 597_500);
+
+    game.tick_at(2500);
+    game.tick_at(2600);
+    assert_eq!(game.app.game().timer().time_remaining(Color::White), 597_400);
+}
+
+#[test]
+fn test_move_timed_from_reading_arrival() {
+    let mut game = TestGame::new();
+    game.tick_at(3000);
+
+    let pawn = game.hw.board.virtual_board().get(12).cloned().unwrap();
+    game.hw.board.virtual_board_mut().remove_piece(12);
+    game.reading_at(4000);
+    game.reading_at(4200);
+    game.hw
+        .board
+        .virtual_board_mut()
+        .place_piece_with_chip_id(28, pawn.piece, pawn.chip_id);
+    game.reading_at(5000);
+    game.reading_at(5200);
+    assert_eq!(game.app.game().move_count(), 1);
+
+    // Processed late, but black's clock only runs from the reading that
+    // confirmed the placement
+    game.tick_at(7000);
+
+    let timer = game.app.game().timer();
+    assert_eq!(timer.time_remaining(Color::White), 594_800);
+    assert_eq!(timer.time_remaining(Color::Black), 598_200);
+    assert_eq!(game.app.game().clock_history().next().unwrap().elapsed_ms, 5200);
+}
+
+#[test]
+fn test_flag_falls_before_late_reading() {
+    let mut game = TestGame::new_calibrated();
+    assert!(game.app.set_time_control(TimeControl::minutes(1, 0)));
+    game.reading_at(0);
+    game.press(ButtonEvent::Short(Button::White));
+    assert_eq!(game.app.state(), AppState::GameInProgress);
+
+    game.hw.board.virtual_board_mut().remove_piece(12);
+    game.reading_at(61_000);
+
+    assert_eq!(game.app.state(), AppState::GameEnded);
+    assert!(game.hw.buzzer.sounds.contains(&BuzzerSound::TimeExpired));
+}
+
+#[test]
+fn test_reading_filter_ignores_flicker() {
+    let mut game = TestGame::new();
+    game.reading_at(0);
+
+    game.hw.board.generate_flicker(12);
+    game.reading_at(100);
+    game.reading_at(200);
+
+    assert_eq!(game.app.game().lifted_piece(), None);
+    assert!(!game.app.led_state().is_on(28));
+    assert!(!game.hw.buzzer.sounds.contains(&BuzzerSound::Error));
+
+    game.make_move(12, 28);
+    assert_eq!(game.app.game().move_count(), 1);
+}
+
+#[test]
+fn test_reading_filter_ignores_piece_slid_across_squares() {
+    let mut game = TestGame::new();
+    game.reading_at(0);
+
+    // The pawn passes over e3 on its way to e4
+    let pawn = game.hw.board.virtual_board().get(12).cloned().unwrap();
+    game.hw.board.virtual_board_mut().remove_piece(12);
+    game.hw
+        .board
+        .virtual_board_mut()
+        .place_piece_with_chip_id(20, pawn.piece, pawn.chip_id);
+    game.reading_at(100);
+    game.hw.board.virtual_board_mut().remove_piece(20);
+    game.hw
+        .board
+        .virtual_board_mut()
+        .place_piece_with_chip_id(28, pawn.piece, pawn.chip_id);
+    game.reading_at(200);
+    game.reading_at(300);
+    game.reading_at(400);
+
+    assert_eq!(game.app.game().move_count(), 1);
+    assert_eq!(game.app.game().move_history().next().unwrap().to, 28);
+    assert!(!game.hw.buzzer.sounds.contains(&BuzzerSound::Error));
+}
+
+#[test]
+fn test_reading_filter_off_acts_on_every_reading() {
+    let mut game = TestGame::new();
+    game.app.set_reading_filter(FilterConfig::OFF);
+    game.reading_at(0);
+
+    game.hw.board.generate_flicker(12);
+    game.reading_at(100);
+
+    assert_eq!(game.app.game().lifted_piece(), Some(12));
+}
+
+#[test]
+fn test_board_mismatch_recovery() {
+    let mut game = TestGame::new();
+
+    // A black pawn knocked off the board
+    let pawn = game.hw.board.virtual_board().get(52).cloned().unwrap();
+    game.lift_piece(52);
+    game.tick(2900);
+    assert_eq!(game.app.state(), AppState::GameInProgress);
+
+    game.tick(100);
+    assert_eq!(game.app.state(), AppState::Recovering);
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::BoardMismatch { squares: 1 })
+    );
+    assert!(game.app.led_state().is_on(52));
+
+    // The clock waits for the board
+    game.tick(5000);
+    assert_eq!(game.app.game().timer().time_remaining(Color::White), 597_000);
+
+    game.place_piece(52, pawn.piece, pawn.chip_id);
+    assert_eq!(game.app.state(), AppState::GameInProgress);
+    assert!(!game.app.led_state().is_on(52));
+
+    game.tick(1000);
+    assert_eq!(game.app.game().timer().time_remaining(Color::White), 596_000);
+    game.make_move(12, 28);
+    assert_eq!(game.app.game().move_count(), 1);
+}
+
+#[test]
+fn test_board_mismatch_count_follows_board() {
+    let mut game = TestGame::new();
+
+    let piece = game.hw.board.virtual_board().get(12).cloned().unwrap();
+    game.lift_piece(12);
+    game.place_piece(21, piece.piece, piece.chip_id);
+    assert!(game.hw.buzzer.sounds.contains(&BuzzerSound::Error));
+    game.tick(3000);
+
+    assert_eq!(game.app.state(), AppState::Recovering);
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::BoardMismatch { squares: 2 })
+    );
+    assert!(game.app.led_state().is_on(12));
+    assert!(game.app.led_state().is_on(21));
+
+    game.lift_piece(21);
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::BoardMismatch { squares: 1 })
+    );
+    assert!(!game.app.led_state().is_on(21));
+
+    game.place_piece(12, piece.piece, piece.chip_id);
+    assert_eq!(game.app.state(), AppState::GameInProgress);
+}
+
+#[test]
+fn test_holding_piece_is_not_a_mismatch() {
+    let mut game = TestGame::new();
+
+    game.lift_piece(12);
+    game.tick(10_000);
+
+    assert_eq!(game.app.state(), AppState::GameInProgress);
+}
+
+#[test]
+fn test_clock_shows_tenths_under_ten_seconds() {
+    let mut game = TestGame::new();
+
+    game.tick(590_000);
+    game.tick(500);
+    game.tick(100);
+
+    assert_eq!(game.hw.clock.white_time, TimeDisplay::SecondsTenths(9, 5));
+    assert_eq!(game.hw.clock.black_time, TimeDisplay::MinutesSeconds(10, 0));
+}
+
+#[test]
+fn test_low_time_warnings_sound_once_per_threshold() {
+    let mut game = TestGame::new();
+
+    for _ in 0..6000 {
+        game.tick(100);
+    }
+    assert_eq!(game.app.state(), AppState::GameEnded);
+
+    let warnings: Vec<_> = game
+        .hw
+        .buzzer
+        .sounds
+        .iter()
+        .filter(|sound| matches!(sound, BuzzerSound::LowTime(_) | BuzzerSound::Countdown))
+        .cloned()
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            BuzzerSound::LowTime(0),
+            BuzzerSound::LowTime(1),
+            BuzzerSound::LowTime(2),
+            BuzzerSound::Countdown,
+            BuzzerSound::Countdown,
+            BuzzerSound::Countdown,
+            BuzzerSound::Countdown,
+            BuzzerSound::Countdown,
+        ]
+    );
+}
+
+#[test]
+fn test_low_time_warnings_configurable() {
+    let mut game = TestGame::new();
+    game.app.set_low_time_warnings(LowTimeWarnings {
+        thresholds_ms: [Some(300_000), None, None],
+        countdown_ms: 0,
+    });
+
+    game.tick(299_000);
+    assert!(!game.hw.buzzer.sounds.contains(&BuzzerSound::LowTime(0)));
+    game.tick(2_000);
+    assert!(game.hw.buzzer.sounds.contains(&BuzzerSound::LowTime(0)));
+
+    game.tick(290_000);
+    assert!(!game.hw.buzzer.sounds.contains(&BuzzerSound::Countdown));
+}
+
+#[test]
+fn test_time_control_menu_scrolls_presets() {
+    let mut game = TestGame::new_calibrated();
+
+    let reading = poll_reading(&mut game.hw.board);
+    game.app
+        .on_board_reading(reading, &mut game.hw.display, &mut game.hw.buzzer, &mut game.hw.delay);
+    assert_eq!(game.app.state(), AppState::SelectingTimeControl);
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::TimeControlMenu { minutes: 10, increment: 0, custom: false })
+    );
+
+    // Blue scrolls, and the clocks preview the base time
+    game.press(ButtonEvent::Short(Button::Blue));
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::TimeControlMenu { minutes: 15, increment: 10, custom: false })
+    );
+    game.tick(100);
+    assert_eq!(game.hw.clock.white_time, TimeDisplay::MinutesSeconds(15, 0));
+    assert!(!game.hw.clock.white_active);
+
+    // A double press scrolls two entries
+    game.press(ButtonEvent::Double(Button::Blue));
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::TimeControlMenu { minutes: 10, increment: 0, custom: true })
+    );
+
+    // Past the custom entry, back to the first preset
+    game.press(ButtonEvent::Short(Button::Blue));
+    game.press(ButtonEvent::Short(Button::White));
+    assert_eq!(game.app.state(), AppState::GameInProgress);
+    assert_eq!(game.app.time_control(), TimeControl::minutes(1, 0));
+    assert_eq!(game.app.game().timer().time_remaining(Color::White), 60_000);
+}
+
+#[test]
+fn test_custom_time_control_from_menu() {
+    let mut game = TestGame::new_calibrated();
+    let reading = poll_reading(&mut game.hw.board);
+    game.app
+        .on_board_reading(reading, &mut game.hw.display, &mut game.hw.buzzer, &mut game.hw.delay);
+
+    for _ in 0..3 {
+        game.press(ButtonEvent::Short(Button::Blue));
+    }
+    game.press(ButtonEvent::Short(Button::White));
+    assert_eq!(game.app.state(), AppState::SelectingTimeControl);
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::CustomTimeControl {
+            minutes: 10,
+            increment: 0,
+            editing_increment: false
+        })
+    );
+
+    game.press(ButtonEvent::Short(Button::Blue));
+    game.press(ButtonEvent::Short(Button::White));
+    game.press(ButtonEvent::Short(Button::Blue));
+    game.press(ButtonEvent::Short(Button::Blue));
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::CustomTimeControl {
+            minutes: 15,
+            increment: 2,
+            editing_increment: true
+        })
+    );
+
+    game.press(ButtonEvent::Short(Button::White));
+    assert_eq!(game.app.state(), AppState::GameInProgress);
+    assert_eq!(game.app.time_control(), TimeControl::minutes(15, 2));
+}
+
+#[test]
+fn test_time_control_menu_keeps_configured_control() {
+    let mut game = TestGame::new_calibrated();
+    assert!(game.app.set_time_control(TimeControl::hourglass(120_000)));
+
+    let reading = poll_reading(&mut game.hw.board);
+    game.app
+        .on_board_reading(reading, &mut game.hw.display, &mut game.hw.buzzer, &mut game.hw.delay);
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::TimeControlMenu { minutes: 2, increment: 0, custom: true })
+    );
+    assert!(!game.app.set_time_control(TimeControl::BLITZ_3_2));
+
+    game.press(ButtonEvent::Short(Button::White));
+    assert_eq!(game.app.time_control(), TimeControl::hourglass(120_000));
+}
+
+#[test]
+fn test_takeback_restores_clocks() {
+    let mut game = TestGame::new();
+
+    game.tick(3000);
+    game.make_move(12, 28);
+    game.tick(5000);
+    game.make_move(52, 36);
+    game.tick(2000);
+
+    game.slide_piece(36, 52);
+    assert_eq!(game.app.game().move_count(), 1);
+    let timer = game.app.game().timer();
+    assert_eq!(timer.time_remaining(Color::White), 597_000);
+    assert_eq!(timer.time_remaining(Color::Black), 600_000);
+    assert!(game.hw.display.messages.contains(&DisplayMessage::TakebackComplete));
+}
+
+#[test]
+fn test_takeback_disabled() {
+    let mut game = TestGame::new();
+    game.app.set_takeback_policy(TakebackPolicy::Disabled);
+    game.make_move(12, 28);
+
+    game.hw.buzzer.sounds.clear();
+    game.slide_piece(28, 12);
+
+    assert_eq!(game.app.game().move_count(), 1);
+    assert!(game.hw.buzzer.sounds.contains(&BuzzerSound::Error));
+}
+
+#[test]
+fn test_takeback_needs_opponent_confirmation() {
+    let mut game = TestGame::new();
+    game.app.set_takeback_policy(TakebackPolicy::OpponentConfirms);
+    game.make_move(12, 28);
+    game.tick(4000);
+
+    game.slide_piece(28, 12);
+    assert_eq!(game.app.game().move_count(), 1);
+    game.tick(100);
+    assert_eq!(
+        game.hw.display.last_message,
+        Some(DisplayMessage::TakebackRequest { opponent_is_white: false })
+    );
+
+    // White's button is not black's to press
+    game.press(ButtonEvent::Short(Button::White));
+    assert_eq!(game.app.state(), AppState::GameInProgress);
+    assert_eq!(game.app.game().move_count(), 1);
+
+    game.press(ButtonEvent::Short(Button::Blue));
+    assert_eq!(game.app.game().move_count(), 0);
+    assert_eq!(game.app.game().timer().time_remaining(Color::Black), 600_000);
+    assert_eq!(game.app.game().current_turn(), Color::White);
+}
+
+#[test]
+fn test_takeback_request_withdrawn() {
+    let mut game = TestGame::new();
+    game.app.set_takeback_policy(TakebackPolicy::OpponentConfirms);
+    game.make_move(12, 28);
+
+    game.slide_piece(28, 12);
+    game.slide_piece(12, 28);
+
+    // The request is gone and play continues with black
+    game.press(ButtonEvent::Short(Button::Blue));
+    assert_eq!(game.app.game().move_count(), 1);
+    game.make_move(52, 36);
+    assert_eq!(game.app.game().move_count(), 2);
+}
@@ -0,0 +1,96 @@
+//! Move generator correctness against published perft node counts
+//!
+//! Positions and counts from https://www.chessprogramming.org/Perft_Results
+
+use certabo::game::chess::ChessBoard;
+
+fn perft(fen: &str, depth: u32) -> u64 {
+    ChessBoard::from_fen(fen).expect("valid FEN").perft(depth)
+}
+
+const INITIAL: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+const POSITION_4_MIRRORED: &str = "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+const POSITION_6: &str = "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+
+#[test]
+fn test_perft_initial_position() {
+    assert_eq!(perft(INITIAL, 1), 20);
+    assert_eq!(perft(INITIAL, 2), 400);
+    assert_eq!(perft(INITIAL, 3), 8_902);
+    assert_eq!(perft(INITIAL, 4), 197_281);
+}
+
+#[test]
+fn test_perft_kiwipete() {
+    assert_eq!(perft(KIWIPETE, 1), 48);
+    assert_eq!(perft(KIWIPETE, 2), 2_039);
+    assert_eq!(perft(KIWIPETE, 3), 97_862);
+}
+
+#[test]
+fn test_perft_en_passant_pins() {
+    // Rank pins through en passant captures and discovered checks
+    assert_eq!(perft(POSITION_3, 1), 14);
+    assert_eq!(perft(POSITION_3, 2), 191);
+    assert_eq!(perft(POSITION_3, 3), 2_812);
+    assert_eq!(perft(POSITION_3, 4), 43_238);
+    assert_eq!(perft(POSITION_3, 5), 674_624);
+}
+
+#[test]
+fn test_perft_promotions_and_castling() {
+    assert_eq!(perft(POSITION_4, 1), 6);
+    assert_eq!(perft(POSITION_4, 2), 264);
+    assert_eq!(perft(POSITION_4, 3), 9_467);
+    assert_eq!(perft(POSITION_4_MIRRORED, 3), 9_467);
+}
+
+#[test]
+fn test_perft_promotion_captures() {
+    assert_eq!(perft(POSITION_5, 1), 44);
+    assert_eq!(perft(POSITION_5, 2), 1_486);
+    assert_eq!(perft(POSITION_5, 3), 62_379);
+}
+
+#[test]
+fn test_perft_middlegame() {
+    assert_eq!(perft(POSITION_6, 1), 46);
+    assert_eq!(perft(POSITION_6, 2), 2_079);
+    assert_eq!(perft(POSITION_6, 3), 89_890);
+}
+
+#[test]
+fn test_divide_sums_to_perft() {
+    let board = ChessBoard::from_fen(KIWIPETE).unwrap();
+
+    let mut moves = 0;
+    let total = board.divide(2, |_, nodes| {
+        moves += 1;
+        assert!(nodes > 0);
+    });
+
+    assert_eq!(moves, 48);
+    assert_eq!(total, 2_039);
+}
+
+// Chess960 positions, castling rights in Shredder-FEN
+const CHESS960_1: &str = "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9";
+const CHESS960_2: &str = "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9";
+const CHESS960_3: &str = "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9";
+
+#[test]
+fn test_perft_chess960() {
+    assert_eq!(perft(CHESS960_1, 1), 21);
+    assert_eq!(perft(CHESS960_1, 2), 528);
+    assert_eq!(perft(CHESS960_1, 3), 12_189);
+    assert_eq!(perft(CHESS960_2, 1), 21);
+    assert_eq!(perft(CHESS960_2, 2), 807);
+    assert_eq!(perft(CHESS960_2, 3), 18_002);
+    assert_eq!(perft(CHESS960_3, 1), 20);
+    assert_eq!(perft(CHESS960_3, 2), 479);
+    assert_eq!(perft(CHESS960_3, 3), 10_471);
+}